@@ -8,11 +8,15 @@ use serde::{Deserialize as SerdeDeserialize, Serialize as SerdeSerialize};
 #[derive(Archive, Deserialize, Serialize, CheckBytes, SerdeSerialize, SerdeDeserialize, Debug, Clone, PartialEq)]
 #[rkyv(derive(Debug))]
 pub struct Action {
-	pub id:      CompactString,
-	pub name:    CompactString,
-	pub icon:    CompactString,
-	pub enabled: bool,
-	pub kind:    ActionKind,
+	pub id:       CompactString,
+	pub name:     CompactString,
+	pub icon:     CompactString,
+	pub enabled:  bool,
+	pub kind:     ActionKind,
+	/// Added to the score of every result of this action, so users can rank
+	/// one action above others matching the same query.
+	#[serde(default)]
+	pub priority: i32,
 }
 
 #[derive(
@@ -21,20 +25,42 @@ pub struct Action {
 #[rkyv(derive(Debug))]
 #[repr(u8)]
 pub enum ActionKind {
-	QuickLink { keyword: CompactString, url: CompactString },
-	Pattern { pattern: CompactString, action: PatternActionType },
-	ScriptFilter { keyword: CompactString, script_path: CompactString, extension_dir: CompactString },
+	QuickLink {
+		keyword: CompactString,
+		url:     CompactString,
+	},
+	Pattern {
+		pattern: CompactString,
+		action:  PatternActionType,
+	},
+	ScriptFilter {
+		keyword:       CompactString,
+		script_path:   CompactString,
+		extension_dir: CompactString,
+		#[serde(default)]
+		timeout_ms:    Option<u64>,
+		#[serde(default)]
+		cache_ttl_ms:  Option<u64>,
+		#[serde(default)]
+		env:           Vec<(String, String)>,
+	},
+	RegexPattern {
+		pattern: CompactString,
+		action:  PatternActionType,
+	},
 }
 
-#[derive(
-	Archive, Deserialize, Serialize, CheckBytes, SerdeSerialize, SerdeDeserialize, Debug, Clone, PartialEq, Eq,
-)]
+#[derive(Archive, Deserialize, Serialize, SerdeSerialize, SerdeDeserialize, Debug, Clone, PartialEq, Eq)]
 #[rkyv(derive(Debug))]
+#[rkyv(serialize_bounds(__S: rkyv::ser::Writer + rkyv::ser::Allocator, __S::Error: rkyv::rancor::Source))]
+#[rkyv(deserialize_bounds(__D::Error: rkyv::rancor::Source))]
+#[rkyv(bytecheck(bounds(__C: rkyv::validation::ArchiveContext, __C::Error: rkyv::rancor::Source)))]
 #[repr(u8)]
 pub enum PatternActionType {
 	OpenUrl(String),
 	CopyText(String),
 	RunCommand { cmd: String, args: Vec<String> },
+	Multi(#[rkyv(omit_bounds)] Vec<PatternActionType>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -65,11 +91,12 @@ impl Action {
 		icon: impl Into<CompactString>,
 	) -> Self {
 		Self {
-			id:      id.into(),
-			name:    name.into(),
-			icon:    icon.into(),
-			enabled: true,
-			kind:    ActionKind::QuickLink { keyword: keyword.into(), url: url.into() },
+			id:       id.into(),
+			name:     name.into(),
+			icon:     icon.into(),
+			enabled:  true,
+			priority: 0,
+			kind:     ActionKind::QuickLink { keyword: keyword.into(), url: url.into() },
 		}
 	}
 
@@ -81,11 +108,31 @@ impl Action {
 		icon: impl Into<CompactString>,
 	) -> Self {
 		Self {
-			id:      id.into(),
-			name:    name.into(),
-			icon:    icon.into(),
-			enabled: true,
-			kind:    ActionKind::Pattern { pattern: pattern.into(), action },
+			id:       id.into(),
+			name:     name.into(),
+			icon:     icon.into(),
+			enabled:  true,
+			priority: 0,
+			kind:     ActionKind::Pattern { pattern: pattern.into(), action },
+		}
+	}
+
+	/// Like [`Self::pattern`], but `pattern` is a regex that must match the
+	/// whole query; its named groups fill `{name}` in the action templates.
+	pub fn regex_pattern(
+		id: impl Into<CompactString>,
+		name: impl Into<CompactString>,
+		pattern: impl Into<CompactString>,
+		action: PatternActionType,
+		icon: impl Into<CompactString>,
+	) -> Self {
+		Self {
+			id:       id.into(),
+			name:     name.into(),
+			icon:     icon.into(),
+			enabled:  true,
+			priority: 0,
+			kind:     ActionKind::RegexPattern { pattern: pattern.into(), action },
 		}
 	}
 
@@ -98,18 +145,49 @@ impl Action {
 		icon: impl Into<CompactString>,
 	) -> Self {
 		Self {
-			id:      id.into(),
-			name:    name.into(),
-			icon:    icon.into(),
-			enabled: true,
-			kind:    ActionKind::ScriptFilter {
+			id:       id.into(),
+			name:     name.into(),
+			icon:     icon.into(),
+			enabled:  true,
+			priority: 0,
+			kind:     ActionKind::ScriptFilter {
 				keyword:       keyword.into(),
 				script_path:   script_path.into(),
 				extension_dir: extension_dir.into(),
+				timeout_ms:    None,
+				cache_ttl_ms:  None,
+				env:           Vec::new(),
 			},
 		}
 	}
 
+	#[must_use]
+	pub const fn with_priority(mut self, priority: i32) -> Self {
+		self.priority = priority;
+		self
+	}
+
+	/// Overrides the script timeout and result cache TTL of a script filter;
+	/// `None` keeps the defaults and a TTL of zero disables caching.
+	#[must_use]
+	pub const fn with_script_limits(mut self, timeout: Option<u64>, cache_ttl: Option<u64>) -> Self {
+		if let ActionKind::ScriptFilter { timeout_ms, cache_ttl_ms, .. } = &mut self.kind {
+			*timeout_ms = timeout;
+			*cache_ttl_ms = cache_ttl;
+		}
+		self
+	}
+
+	/// Sets environment variables for a script filter that take precedence
+	/// over the `env` of its extension manifest.
+	#[must_use]
+	pub fn with_script_env(mut self, vars: Vec<(String, String)>) -> Self {
+		if let ActionKind::ScriptFilter { env, .. } = &mut self.kind {
+			*env = vars;
+		}
+		self
+	}
+
 	#[must_use]
 	pub fn triggers(&self) -> Vec<&str> {
 		match &self.kind {
@@ -117,6 +195,7 @@ impl Action {
 			ActionKind::Pattern { pattern, .. } => {
 				vec![pattern.split_whitespace().next().unwrap_or(pattern)]
 			}
+			ActionKind::RegexPattern { pattern, .. } => vec![pattern.as_str()],
 		}
 	}
 }
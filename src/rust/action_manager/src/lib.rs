@@ -2,24 +2,252 @@ pub mod action;
 pub mod pattern;
 pub mod script_filter;
 
-use std::path::Path;
+use std::{path::{Path, PathBuf}, sync::{Arc, atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering}}};
 
+use bytecheck::CheckBytes;
+use compact_str::CompactString;
+use parking_lot::Mutex;
+use regex::Regex;
+use rkyv::rancor::Error;
+use rustc_hash::{FxHashMap, FxHashSet};
+use search_engine::fuzzy_matcher::FuzzyMatcher;
 use shared_utils::KeywordMatcherCache;
-use storage_utils::RkyvStorage;
+use storage_utils::{RkyvStorage, load_from_disk, save_to_disk_async};
 
-use crate::{action::{Action, ActionKind, ActionResult}, pattern::{create_result, match_pattern}};
+use crate::{action::{Action, ActionKind, ActionResult, PatternActionType}, pattern::{compile_regex_pattern, create_results, match_pattern, match_regex}};
+
+const SCHEMA_VERSION: u8 = 4;
+const MIN_FUZZY_KEYWORD_LEN: usize = 3;
+/// A fuzzy keyword must start with the keyword's first character and cover
+/// at least this share of its characters.
+const MIN_FUZZY_KEYWORD_COVERAGE_PERCENT: usize = 75;
+const USAGE_BONUS_PER_USE: f32 = 2.0;
+const MAX_USAGE_BONUS: f32 = 50.0;
+
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, CheckBytes)]
+struct UsageCount {
+	id:    String,
+	count: u64,
+}
+
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+struct LegacyAction<K> {
+	id:      CompactString,
+	name:    CompactString,
+	icon:    CompactString,
+	enabled: bool,
+	kind:    K,
+}
+
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, CheckBytes)]
+#[repr(u8)]
+enum ActionKindV1 {
+	QuickLink { keyword: CompactString, url: CompactString },
+	Pattern { pattern: CompactString, action: PatternActionType },
+	ScriptFilter { keyword: CompactString, script_path: CompactString, extension_dir: CompactString },
+	RegexPattern { pattern: CompactString, action: PatternActionType },
+}
+
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, CheckBytes)]
+#[repr(u8)]
+enum ActionKindV2 {
+	QuickLink {
+		keyword: CompactString,
+		url:     CompactString,
+	},
+	Pattern {
+		pattern: CompactString,
+		action:  PatternActionType,
+	},
+	ScriptFilter {
+		keyword:       CompactString,
+		script_path:   CompactString,
+		extension_dir: CompactString,
+		timeout_ms:    Option<u64>,
+		cache_ttl_ms:  Option<u64>,
+	},
+	RegexPattern {
+		pattern: CompactString,
+		action:  PatternActionType,
+	},
+}
+
+impl From<ActionKindV1> for ActionKind {
+	fn from(old: ActionKindV1) -> Self {
+		match old {
+			ActionKindV1::QuickLink { keyword, url } => ActionKindV2::QuickLink { keyword, url },
+			ActionKindV1::Pattern { pattern, action } => ActionKindV2::Pattern { pattern, action },
+			ActionKindV1::ScriptFilter { keyword, script_path, extension_dir } => {
+				ActionKindV2::ScriptFilter { keyword, script_path, extension_dir, timeout_ms: None, cache_ttl_ms: None }
+			}
+			ActionKindV1::RegexPattern { pattern, action } => ActionKindV2::RegexPattern { pattern, action },
+		}
+		.into()
+	}
+}
+
+impl From<ActionKindV2> for ActionKind {
+	fn from(old: ActionKindV2) -> Self {
+		match old {
+			ActionKindV2::QuickLink { keyword, url } => Self::QuickLink { keyword, url },
+			ActionKindV2::Pattern { pattern, action } => Self::Pattern { pattern, action },
+			ActionKindV2::ScriptFilter { keyword, script_path, extension_dir, timeout_ms, cache_ttl_ms } => {
+				Self::ScriptFilter { keyword, script_path, extension_dir, timeout_ms, cache_ttl_ms, env: Vec::new() }
+			}
+			ActionKindV2::RegexPattern { pattern, action } => Self::RegexPattern { pattern, action },
+		}
+	}
+}
+
+impl<K: Into<ActionKind>> From<LegacyAction<K>> for Action {
+	fn from(old: LegacyAction<K>) -> Self {
+		Self {
+			id:       old.id,
+			name:     old.name,
+			icon:     old.icon,
+			enabled:  old.enabled,
+			kind:     old.kind.into(),
+			priority: 0,
+		}
+	}
+}
+
+fn migrate(version: u8, bytes: &[u8]) -> Option<Vec<Action>> {
+	match version {
+		0 | 1 => {
+			let old = rkyv::from_bytes::<Vec<LegacyAction<ActionKindV1>>, Error>(bytes).ok()?;
+			Some(old.into_iter().map(Action::from).collect())
+		}
+		2 => {
+			let old = rkyv::from_bytes::<Vec<LegacyAction<ActionKindV2>>, Error>(bytes).ok()?;
+			Some(old.into_iter().map(Action::from).collect())
+		}
+		3 => {
+			let old = rkyv::from_bytes::<Vec<LegacyAction<ActionKind>>, Error>(bytes).ok()?;
+			Some(old.into_iter().map(Action::from).collect())
+		}
+		_ => None,
+	}
+}
+
+/// A script filter that matched the query but had no cached results, to be
+/// run off the search thread.
+struct PendingScript {
+	action_id:     CompactString,
+	script_path:   CompactString,
+	extension_dir: CompactString,
+	query:         String,
+	timeout_ms:    Option<u64>,
+	cache_ttl_ms:  Option<u64>,
+	env:           Vec<(String, String)>,
+	bonus:         f32,
+}
+
+impl PendingScript {
+	fn cached(&self) -> Option<Vec<ActionResult>> {
+		script_filter::cached_script_filter(
+			&self.script_path,
+			&self.extension_dir,
+			&self.query,
+			self.cache_ttl_ms,
+			&self.env,
+		)
+	}
+
+	fn run(&self) -> Vec<ActionResult> {
+		match script_filter::execute_script_filter(
+			&self.script_path,
+			&self.extension_dir,
+			&self.query,
+			&self.action_id,
+			self.timeout_ms,
+			self.cache_ttl_ms,
+			&self.env,
+		) {
+			Ok(results) => results,
+			Err(e) => {
+				let message = e.to_string();
+				vec![ActionResult::new(
+					format!("{}:error:{}", self.action_id, e.kind()),
+					e.title(),
+					message.clone(),
+					e.icon(),
+					0.0,
+					crate::action::ResultAction::CopyText(message),
+				)]
+			}
+		}
+	}
+}
+
+/// The built-in web searches added by [`ActionManager::import_defaults`].
+#[must_use]
+pub fn default_actions() -> Vec<Action> {
+	vec![
+		Action::quick_link("google", "Google", "g", "https://www.google.com/search?q={query}", "web:google"),
+		Action::quick_link("duckduckgo", "DuckDuckGo", "ddg", "https://duckduckgo.com/?q={query}", "web:duckduckgo"),
+		Action::quick_link("github", "GitHub", "gh", "https://github.com/search?q={query}", "web:github"),
+		Action::quick_link(
+			"stackoverflow",
+			"Stack Overflow",
+			"so",
+			"https://stackoverflow.com/search?q={query}",
+			"web:stackoverflow",
+		),
+		Action::quick_link(
+			"youtube",
+			"YouTube",
+			"yt",
+			"https://www.youtube.com/results?search_query={query}",
+			"web:youtube",
+		),
+	]
+}
+
+const fn keyword_of(kind: &ActionKind) -> Option<&CompactString> {
+	match kind {
+		ActionKind::QuickLink { keyword, .. } | ActionKind::ScriptFilter { keyword, .. } => Some(keyword),
+		ActionKind::Pattern { .. } | ActionKind::RegexPattern { .. } => None,
+	}
+}
+
+fn sort_by_score(results: &mut [ActionResult]) { results.sort_by(|a, b| b.score.total_cmp(&a.score)); }
+
+#[allow(clippy::cast_precision_loss)]
+fn usage_bonus(count: u64) -> f32 { (count as f32 * USAGE_BONUS_PER_USE).min(MAX_USAGE_BONUS) }
 
 pub struct ActionManager {
-	storage:         RkyvStorage<Action>,
-	keyword_matcher: KeywordMatcherCache,
+	storage:              RkyvStorage<Action>,
+	keyword_matcher:      KeywordMatcherCache,
+	min_script_query_len: AtomicUsize,
+	regex_cache:          Mutex<FxHashMap<CompactString, Option<Regex>>>,
+	usage:                Mutex<FxHashMap<CompactString, u64>>,
+	usage_path:           PathBuf,
+	search_generation:    Arc<AtomicU64>,
+	fuzzy_keywords:       AtomicBool,
+	fuzzy:                FuzzyMatcher,
 }
 
 impl ActionManager {
 	pub fn new(storage_path: impl AsRef<Path>) -> std::io::Result<Self> {
-		let storage = RkyvStorage::new(storage_path)?;
+		let usage_path = storage_path.as_ref().with_extension("usage");
+		let storage = RkyvStorage::new_versioned(storage_path, SCHEMA_VERSION, migrate)?;
 		let keyword_matcher = KeywordMatcherCache::new();
+		let counts: Vec<UsageCount> =
+			if usage_path.exists() { load_from_disk(&usage_path).unwrap_or_default() } else { Vec::new() };
+		let usage = counts.into_iter().map(|u| (CompactString::from(u.id), u.count)).collect();
 
-		let manager = Self { storage, keyword_matcher };
+		let manager = Self {
+			storage,
+			keyword_matcher,
+			min_script_query_len: AtomicUsize::new(0),
+			regex_cache: Mutex::new(FxHashMap::default()),
+			usage: Mutex::new(usage),
+			usage_path,
+			search_generation: Arc::new(AtomicU64::new(0)),
+			fuzzy_keywords: AtomicBool::new(false),
+			fuzzy: FuzzyMatcher::new(),
+		};
 		manager.rebuild_keyword_matcher();
 		Ok(manager)
 	}
@@ -27,9 +255,16 @@ impl ActionManager {
 	fn invalidate_matcher_if_modified(&self, modified: bool) {
 		if modified {
 			self.keyword_matcher.invalidate();
+			self.regex_cache.lock().clear();
 		}
 	}
 
+	// Invalid patterns are cached as `None` so they are not recompiled on
+	// every query.
+	fn cached_regex(&self, pattern: &CompactString) -> Option<Regex> {
+		self.regex_cache.lock().entry(pattern.clone()).or_insert_with(|| compile_regex_pattern(pattern)).clone()
+	}
+
 	pub fn add(&self, action: Action) -> std::io::Result<()> {
 		self.storage.add(action)?;
 		self.invalidate_matcher_if_modified(true);
@@ -76,28 +311,161 @@ impl ActionManager {
 		Ok(modified)
 	}
 
+	pub fn import(&self, imported: Vec<Action>, merge: bool) -> std::io::Result<usize> {
+		let count = imported.len();
+		self.storage.update(|actions| {
+			if merge {
+				for action in imported {
+					if let Some(pos) = actions.iter().position(|a| a.id == action.id) {
+						actions[pos] = action;
+					} else {
+						actions.push(action);
+					}
+				}
+			} else {
+				*actions = imported;
+			}
+			true
+		})?;
+
+		self.invalidate_matcher_if_modified(true);
+		Ok(count)
+	}
+
 	#[inline]
 	#[must_use]
 	pub fn get_all(&self) -> std::sync::Arc<Vec<Action>> { self.storage.get_all() }
 
+	#[must_use]
+	pub fn get_by_id(&self, id: &str) -> Option<Action> { self.storage.get_all().iter().find(|a| a.id == id).cloned() }
+
+	#[must_use]
+	pub fn get_enabled(&self) -> Vec<Action> { self.storage.get_all().iter().filter(|a| a.enabled).cloned().collect() }
+
+	#[must_use]
+	pub fn count(&self) -> usize { self.storage.len() }
+
 	#[must_use]
 	pub fn get_by_type(&self, filter: impl Fn(&ActionKind) -> bool) -> Vec<Action> {
 		self.storage.get_all().iter().filter(|a| filter(&a.kind)).cloned().collect()
 	}
 
+	pub fn set_min_script_query_len(&self, len: usize) { self.min_script_query_len.store(len, Ordering::Relaxed); }
+
+	#[must_use]
+	pub fn min_script_query_len(&self) -> usize { self.min_script_query_len.load(Ordering::Relaxed) }
+
+	/// Lets a mistyped first word trigger the closest keyword when no keyword
+	/// matches exactly. Off by default.
+	pub fn set_fuzzy_keywords(&self, enabled: bool) { self.fuzzy_keywords.store(enabled, Ordering::Relaxed); }
+
+	#[must_use]
+	pub fn fuzzy_keywords(&self) -> bool { self.fuzzy_keywords.load(Ordering::Relaxed) }
+
+	/// Counts a launch of the result `result_id`, which is resolved back to the
+	/// action that produced it. Returns false if no action matches.
+	#[allow(clippy::significant_drop_tightening)]
+	pub fn record_use(&self, result_id: &str) -> bool {
+		let actions = self.storage.get_all();
+		let Some(action) = actions
+			.iter()
+			.filter(|a| {
+				result_id
+					.strip_prefix(a.id.as_str())
+					.is_some_and(|rest| rest.is_empty() || rest.starts_with(':') || rest.starts_with('-'))
+			})
+			.max_by_key(|a| a.id.len())
+		else {
+			return false;
+		};
+
+		let mut usage = self.usage.lock();
+		*usage.entry(action.id.clone()).or_default() += 1;
+		let counts: Vec<UsageCount> = usage.iter().map(|(id, &count)| UsageCount { id: id.to_string(), count }).collect();
+		// Queued under the lock so snapshots reach the writer in order.
+		save_to_disk_async(&self.usage_path, counts);
+		true
+	}
+
+	#[must_use]
+	pub fn use_count(&self, action_id: &str) -> u64 { self.usage.lock().get(action_id).copied().unwrap_or_default() }
+
+	#[must_use]
+	pub fn search(&self, query: &str) -> Vec<ActionResult> { self.collect_results(query, None) }
+
+	/// Returns local and cached results immediately and runs the remaining
+	/// script filters on a worker thread, which hands their results to
+	/// `callback` together with the generation returned here. The callback is
+	/// only invoked if a script had to run; scripts are skipped once a newer
+	/// search has started.
+	pub fn search_async<F>(&self, query: &str, callback: F) -> (u64, Vec<ActionResult>)
+	where
+		F: FnOnce(u64, Vec<ActionResult>) + Send + 'static,
+	{
+		let generation = self.search_generation.fetch_add(1, Ordering::SeqCst) + 1;
+		let mut pending = Vec::new();
+		let results = self.collect_results(query, Some(&mut pending));
+
+		if !pending.is_empty() {
+			let current = Arc::clone(&self.search_generation);
+			std::thread::spawn(move || {
+				let mut results = Vec::new();
+				for script in pending {
+					if current.load(Ordering::SeqCst) != generation {
+						break;
+					}
+					results.extend(script.run().into_iter().map(|mut result| {
+						result.score += script.bonus;
+						result
+					}));
+				}
+				sort_by_score(&mut results);
+				callback(generation, results);
+			});
+		}
+
+		(generation, results)
+	}
+
 	#[must_use]
-	pub fn search(&self, query: &str) -> Vec<ActionResult> {
+	pub fn is_current_search(&self, generation: u64) -> bool {
+		self.search_generation.load(Ordering::SeqCst) == generation
+	}
+
+	fn collect_results(&self, query: &str, mut deferred: Option<&mut Vec<PendingScript>>) -> Vec<ActionResult> {
 		if self.keyword_matcher.needs_rebuild() {
 			self.rebuild_keyword_matcher();
 		}
 
+		let query = shared_utils::normalize_command(query);
+		let query = query.as_ref();
 		let actions = self.storage.get_all();
 		let mut results = Vec::with_capacity(actions.len().min(10));
+		let min_script_query_len = self.min_script_query_len();
+		let fuzzy_query;
+		let (keyword_query, keywords) = match self.matched_keywords(query) {
+			Some(found) if found.is_empty() && self.fuzzy_keywords() => match self.fuzzy_keyword_query(&actions, query) {
+				Some(rewritten) => {
+					fuzzy_query = rewritten;
+					(fuzzy_query.as_str(), self.matched_keywords(&fuzzy_query))
+				}
+				None => (query, Some(found)),
+			},
+			found => (query, found),
+		};
 
 		for action in actions.iter().filter(|a| a.enabled) {
+			if let (Some(keywords), Some(keyword)) = (&keywords, keyword_of(&action.kind))
+				&& !keywords.contains(keyword.as_str())
+			{
+				continue;
+			}
+			let first_result = results.len();
+			#[allow(clippy::cast_precision_loss)]
+			let bonus = usage_bonus(self.use_count(&action.id)) + action.priority as f32;
 			match &action.kind {
 				ActionKind::QuickLink { keyword, url } => {
-					if let Some(search_query) = Self::match_quick_link(query, keyword.as_str()) {
+					if let Some(search_query) = Self::match_quick_link(keyword_query, keyword.as_str()) {
 						let expanded_url = url.replace("{query}", &urlencoding::encode(search_query));
 						results.push(ActionResult::new(
 							format!("{}:{search_query}", action.id),
@@ -112,48 +480,106 @@ impl ActionManager {
 
 				ActionKind::Pattern { pattern, action: action_type } => {
 					if let Some(captures) = match_pattern(pattern.as_str(), query) {
-						let result = create_result(
+						results.extend(create_results(
 							action.id.as_str(),
 							action.name.as_str(),
 							pattern.as_str(),
 							action_type,
 							&captures,
 							action.icon.as_str(),
-						);
-						results.push(result);
+						));
 					}
 				}
 
-				ActionKind::ScriptFilter { keyword, script_path, extension_dir } => {
-					if let Some(search_query) = Self::match_quick_link(query, keyword.as_str()) {
-						match script_filter::execute_script_filter(
-							script_path.as_str(),
-							extension_dir.as_str(),
-							search_query,
+				ActionKind::RegexPattern { pattern, action: action_type } => {
+					if let Some(captures) = self.cached_regex(pattern).and_then(|regex| match_regex(&regex, query)) {
+						results.extend(create_results(
 							action.id.as_str(),
-						) {
-							Ok(script_results) => {
-								results.extend(script_results);
-							}
-							Err(e) => {
-								results.push(ActionResult::new(
-									format!("{}:error", action.id),
-									"Script Error",
-									format!("Failed to execute: {e}"),
-									"exclamationmark.triangle",
-									0.0,
-									crate::action::ResultAction::CopyText(e),
-								));
-							}
+							action.name.as_str(),
+							query,
+							action_type,
+							&captures,
+							action.icon.as_str(),
+						));
+					}
+				}
+
+				ActionKind::ScriptFilter { keyword, script_path, extension_dir, timeout_ms, cache_ttl_ms, env } => {
+					if let Some(search_query) = Self::match_quick_link(keyword_query, keyword.as_str())
+						&& search_query.chars().count() >= min_script_query_len
+					{
+						let script = PendingScript {
+							action_id: action.id.clone(),
+							script_path: script_path.clone(),
+							extension_dir: extension_dir.clone(),
+							query: search_query.to_owned(),
+							timeout_ms: *timeout_ms,
+							cache_ttl_ms: *cache_ttl_ms,
+							env: env.clone(),
+							bonus,
+						};
+						match deferred.as_deref_mut() {
+							Some(pending) => match script.cached() {
+								Some(cached) => results.extend(cached),
+								None => pending.push(script),
+							},
+							None => results.extend(script.run()),
 						}
 					}
 				}
 			}
+
+			for result in &mut results[first_result..] {
+				result.score += bonus;
+			}
 		}
 
+		sort_by_score(&mut results);
 		results
 	}
 
+	/// Keywords that `query` starts with at a word boundary, looked up in the
+	/// cached automaton. `None` while the automaton is unavailable, in which
+	/// case every keyword action has to be checked.
+	fn matched_keywords<'q>(&self, query: &'q str) -> Option<FxHashSet<&'q str>> {
+		let trimmed = query.trim();
+		self.keyword_matcher.with_automaton(|automaton| {
+			trimmed
+				.char_indices()
+				.filter(|&(_, c)| c == ' ' || c == '\t')
+				.map(|(i, _)| i)
+				.chain(std::iter::once(trimmed.len()))
+				.map(|end| &trimmed[..end])
+				.filter(|prefix| automaton.find(*prefix).is_some_and(|m| m.start() == 0 && m.end() == prefix.len()))
+				.collect()
+		})
+	}
+
+	/// Rewrites `query` so that its first word is replaced by the enabled
+	/// keyword it most likely misspells, if any is close enough.
+	fn fuzzy_keyword_query(&self, actions: &[Action], query: &str) -> Option<String> {
+		let trimmed = query.trim();
+		let (token, rest) = trimmed.split_once([' ', '\t']).unwrap_or((trimmed, ""));
+		let token_len = token.chars().count();
+		if token_len < MIN_FUZZY_KEYWORD_LEN {
+			return None;
+		}
+
+		let (_, keyword) = actions
+			.iter()
+			.filter(|a| a.enabled)
+			.filter_map(|a| keyword_of(&a.kind))
+			.filter(|keyword| token_len * 100 >= keyword.chars().count() * MIN_FUZZY_KEYWORD_COVERAGE_PERCENT)
+			.filter_map(|keyword| {
+				let (score, indices) = self.fuzzy.match_with_indices(keyword, token)?;
+				(indices.first() == Some(&0)).then_some((score, keyword))
+			})
+			.reduce(|best, candidate| if candidate.0 > best.0 { candidate } else { best })?;
+
+		let rest = rest.trim();
+		Some(if rest.is_empty() { keyword.to_string() } else { format!("{keyword} {rest}") })
+	}
+
 	fn match_quick_link<'a>(query: &'a str, keyword: &str) -> Option<&'a str> {
 		let trimmed = query.trim();
 
@@ -166,35 +592,24 @@ impl ActionManager {
 		None
 	}
 
-	pub fn import_defaults(&self) -> std::io::Result<()> {
-		let defaults = vec![
-			Action::quick_link("google", "Google", "g", "https://www.google.com/search?q={query}", "web:google"),
-			Action::quick_link("duckduckgo", "DuckDuckGo", "ddg", "https://duckduckgo.com/?q={query}", "web:duckduckgo"),
-			Action::quick_link("github", "GitHub", "gh", "https://github.com/search?q={query}", "web:github"),
-			Action::quick_link(
-				"stackoverflow",
-				"Stack Overflow",
-				"so",
-				"https://stackoverflow.com/search?q={query}",
-				"web:stackoverflow",
-			),
-			Action::quick_link(
-				"youtube",
-				"YouTube",
-				"yt",
-				"https://www.youtube.com/results?search_query={query}",
-				"web:youtube",
-			),
-		];
+	pub fn import_defaults(&self) -> std::io::Result<()> { self.import_defaults_from(default_actions()).map(|_| ()) }
 
-		let existing_actions = self.storage.get_all();
-		for action in defaults {
-			if !existing_actions.iter().any(|a| a.id == action.id) {
-				self.add(action)?;
+	/// Adds each of `defaults` whose id is not taken yet and returns how many
+	/// were added.
+	pub fn import_defaults_from(&self, defaults: Vec<Action>) -> std::io::Result<usize> {
+		let mut added = 0;
+		let modified = self.storage.update(|actions| {
+			for action in defaults {
+				if !actions.iter().any(|a| a.id == action.id) {
+					actions.push(action);
+					added += 1;
+				}
 			}
-		}
+			added > 0
+		})?;
 
-		Ok(())
+		self.invalidate_matcher_if_modified(modified);
+		Ok(added)
 	}
 
 	#[must_use]
@@ -203,14 +618,228 @@ impl ActionManager {
 	fn rebuild_keyword_matcher(&self) {
 		let actions = self.storage.get_all();
 		self.keyword_matcher.rebuild(|| {
-			actions
+			actions.iter().filter(|a| a.enabled).filter_map(|a| keyword_of(&a.kind).map(CompactString::as_str)).collect()
+		});
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::action::PatternActionType;
+
+	#[cfg(unix)]
+	pub(crate) fn write_script(dir: &Path, body: &str) -> String {
+		use std::os::unix::fs::PermissionsExt;
+
+		let path = dir.join("filter.sh");
+		std::fs::write(&path, format!("#!/bin/sh\n{body}\n")).unwrap();
+		std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+		path.to_string_lossy().into_owned()
+	}
+
+	#[test]
+	fn test_usage_bonus_reorders_results() {
+		let temp = tempfile::TempDir::new().unwrap();
+		let path = temp.path().join("actions.bin");
+		let manager = ActionManager::new(&path).unwrap();
+		manager.add(Action::quick_link("maps", "Maps", "m", "https://maps.example/?q={query}", "web")).unwrap();
+		manager
+			.add(Action::pattern("metric", "Metric", "m {value}", PatternActionType::CopyText("{value}".to_owned()), "calc"))
+			.unwrap();
+
+		let score = |results: &[ActionResult], prefix: &str| {
+			results.iter().find(|r| r.id.starts_with(prefix)).map(|r| r.score).unwrap()
+		};
+		let results = manager.search("m 5");
+		assert!(score(&results, "maps:") > score(&results, "metric:"));
+
+		for _ in 0..3 {
+			assert!(manager.record_use("metric:m 5"));
+		}
+		assert!(!manager.record_use("unknown:m 5"));
+		let results = manager.search("m 5");
+		assert!(score(&results, "metric:") > score(&results, "maps:"));
+
+		assert!(storage_utils::flush_all());
+		let reopened = ActionManager::new(&path).unwrap();
+		assert_eq!(reopened.use_count("metric"), 3);
+		assert_eq!(reopened.use_count("maps"), 0);
+	}
+
+	#[test]
+	fn test_schema_1_file_migrates() {
+		let temp = tempfile::TempDir::new().unwrap();
+		let path = temp.path().join("actions.bin");
+		let old = LegacyAction {
+			id:      "gh".into(),
+			name:    "GitHub".into(),
+			icon:    "code".into(),
+			enabled: true,
+			kind:    ActionKindV1::ScriptFilter {
+				keyword:       "gh".into(),
+				script_path:   "filter.sh".into(),
+				extension_dir: "/ext".into(),
+			},
+		};
+		storage_utils::save_to_disk_versioned(&path, &vec![old], 1).unwrap();
+
+		let manager = ActionManager::new(&path).unwrap();
+		assert_eq!(manager.get_all()[0], Action::script_filter("gh", "GitHub", "gh", "filter.sh", "/ext", "code"));
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn test_search_async_delivers_script_results() {
+		let temp = tempfile::TempDir::new().unwrap();
+		let ext = temp.path().join("echo");
+		std::fs::create_dir(&ext).unwrap();
+		write_script(&ext, r#"echo "{\"items\":[{\"title\":\"echo $1\"}]}""#);
+
+		let manager = ActionManager::new(temp.path().join("actions.bin")).unwrap();
+		manager.add(Action::script_filter("echo", "Echo", "e", "filter.sh", ext.to_string_lossy(), "terminal")).unwrap();
+		manager.add(Action::quick_link("web", "Web", "e", "https://example.com/?q={query}", "web")).unwrap();
+
+		let (tx, rx) = std::sync::mpsc::channel();
+		let (generation, immediate) = manager.search_async("e hello", move |generation, results| {
+			tx.send((generation, results)).unwrap();
+		});
+		assert_eq!(immediate.len(), 1);
+		assert!(immediate[0].id.starts_with("web:"));
+
+		let (delivered, results) = rx.recv_timeout(std::time::Duration::from_secs(5)).unwrap();
+		assert_eq!(delivered, generation);
+		assert!(manager.is_current_search(generation));
+		assert_eq!(results.len(), 1);
+		assert_eq!(results[0].title, "echo hello");
+
+		let (_, cached) = manager.search_async("e hello", |_, _| panic!("cached results should return immediately"));
+		assert!(cached.iter().any(|r| r.title == "echo hello"));
+		assert!(!manager.is_current_search(generation));
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn test_script_cache_is_scoped_to_extension() {
+		let temp = tempfile::TempDir::new().unwrap();
+		let manager = ActionManager::new(temp.path().join("actions.bin")).unwrap();
+		for name in ["alpha", "beta"] {
+			let ext = temp.path().join(name);
+			std::fs::create_dir(&ext).unwrap();
+			write_script(&ext, &format!(r#"echo '{{"items":[{{"title":"{name}"}}]}}'"#));
+			manager.add(Action::script_filter(name, name, &name[..1], "filter.sh", ext.to_string_lossy(), "x")).unwrap();
+		}
+
+		assert_eq!(manager.search("a q")[0].title, "alpha");
+		assert_eq!(manager.search("b q")[0].title, "beta");
+	}
+
+	#[test]
+	fn test_priority_reorders_quick_links() {
+		let temp = tempfile::TempDir::new().unwrap();
+		let manager = ActionManager::new(temp.path().join("actions.bin")).unwrap();
+		manager.add(Action::quick_link("maps", "Maps", "m", "https://maps.example/?q={query}", "web")).unwrap();
+		manager.add(Action::quick_link("music", "Music", "m", "https://music.example/?q={query}", "web")).unwrap();
+
+		let ids = |results: Vec<ActionResult>| results.into_iter().map(|r| r.id).collect::<Vec<_>>();
+		assert_eq!(ids(manager.search("m jazz")), ["maps:jazz", "music:jazz"]);
+
+		let music = manager.get_all().iter().find(|a| a.id == "music").cloned().unwrap();
+		assert!(manager.update(music.with_priority(10)).unwrap());
+		assert_eq!(ids(manager.search("m jazz")), ["music:jazz", "maps:jazz"]);
+	}
+
+	#[test]
+	fn test_lookup_by_id_and_enabled() {
+		let temp = tempfile::TempDir::new().unwrap();
+		let manager = ActionManager::new(temp.path().join("actions.bin")).unwrap();
+		manager.add(Action::quick_link("maps", "Maps", "m", "https://maps.example/?q={query}", "web")).unwrap();
+		manager.add(Action::quick_link("wiki", "Wiki", "w", "https://wiki.example/?q={query}", "web")).unwrap();
+		assert!(manager.toggle("wiki").unwrap());
+
+		assert_eq!(manager.count(), 2);
+		assert_eq!(manager.get_by_id("wiki").map(|a| a.name), Some("Wiki".into()));
+		assert!(manager.get_by_id("missing").is_none());
+		let enabled: Vec<_> = manager.get_enabled().into_iter().map(|a| a.id).collect();
+		assert_eq!(enabled, ["maps"]);
+	}
+
+	#[test]
+	fn test_keyword_lookup_matches_full_scan() {
+		let temp = tempfile::TempDir::new().unwrap();
+		let manager = ActionManager::new(temp.path().join("actions.bin")).unwrap();
+		let mut actions: Vec<Action> = (0..2000)
+			.map(|i| Action::quick_link(format!("q{i}"), "Link", format!("k{i}"), "https://x/?q={query}", "web"))
+			.collect();
+		actions.push(Action::quick_link("short", "Short", "a", "https://a/?q={query}", "web"));
+		actions.push(Action::quick_link("spaced", "Spaced", "a b", "https://ab/?q={query}", "web"));
+		actions.push(Action::pattern("num", "Num", "k{n:int}", PatternActionType::CopyText("{n}".to_owned()), "calc"));
+		manager.import(actions, false).unwrap();
+
+		let full_scan = |query: &str| {
+			let query = shared_utils::normalize_command(query);
+			let query = query.as_ref();
+			let mut ids: Vec<_> = manager
+				.get_all()
 				.iter()
-				.filter(|a| a.enabled)
 				.filter_map(|a| match &a.kind {
-					ActionKind::QuickLink { keyword, .. } | ActionKind::ScriptFilter { keyword, .. } => Some(keyword.as_str()),
-					ActionKind::Pattern { .. } => None,
+					ActionKind::QuickLink { keyword, .. } => {
+						ActionManager::match_quick_link(query, keyword).map(|q| format!("{}:{q}", a.id))
+					}
+					ActionKind::Pattern { pattern, .. } => match_pattern(pattern, query).map(|_| format!("{}:{query}", a.id)),
+					_ => None,
 				})
-				.collect()
-		});
+				.collect();
+			ids.sort();
+			ids
+		};
+		for query in ["k1999 rust", "k5", "k12", "a bc", "a b c", "  a\tb", "zzz", ""] {
+			let mut ids: Vec<_> = manager.search(query).into_iter().map(|r| r.id.to_string()).collect();
+			ids.sort();
+			assert_eq!(ids, full_scan(query), "query {query:?}");
+		}
+	}
+
+	#[test]
+	fn test_fuzzy_keyword_fallback() {
+		let temp = tempfile::TempDir::new().unwrap();
+		let manager = ActionManager::new(temp.path().join("actions.bin")).unwrap();
+		manager.add(Action::quick_link("git", "Git", "git", "https://git.example/?q={query}", "web")).unwrap();
+		manager
+			.add(Action::quick_link("github", "GitHub", "github", "https://github.com/search?q={query}", "web"))
+			.unwrap();
+		manager
+			.add(Action::quick_link("gitlab", "GitLab", "gitlab", "https://gitlab.com/search?q={query}", "web"))
+			.unwrap();
+
+		let ids = |query: &str| manager.search(query).into_iter().map(|r| r.id).collect::<Vec<_>>();
+		assert!(ids("githb rust").is_empty());
+
+		manager.set_fuzzy_keywords(true);
+		assert_eq!(ids("githb rust"), ["github:rust"]);
+		assert_eq!(ids("gitlb"), ["gitlab:"]);
+		assert_eq!(ids("git rust"), ["git:rust"]);
+		assert_eq!(ids("github rust"), ["github:rust"]);
+		assert!(ids("xyz rust").is_empty());
+	}
+
+	#[test]
+	fn test_import_custom_defaults_skips_existing_ids() {
+		let temp = tempfile::TempDir::new().unwrap();
+		let manager = ActionManager::new(temp.path().join("actions.bin")).unwrap();
+		manager.add(Action::quick_link("qwant", "My Qwant", "q", "https://qwant.example/?q={query}", "web")).unwrap();
+
+		let defaults = vec![
+			Action::quick_link("qwant", "Qwant", "qw", "https://www.qwant.com/?q={query}", "web:qwant"),
+			Action::quick_link("ecosia", "Ecosia", "eco", "https://www.ecosia.org/search?q={query}", "web:ecosia"),
+		];
+		assert_eq!(manager.import_defaults_from(defaults.clone()).unwrap(), 1);
+		assert_eq!(manager.count(), 2);
+		assert_eq!(manager.get_by_id("qwant").unwrap().name, "My Qwant");
+		assert_eq!(manager.search("eco trees")[0].id, "ecosia:trees");
+
+		assert_eq!(manager.import_defaults_from(defaults).unwrap(), 0);
+		manager.import_defaults().unwrap();
+		assert_eq!(manager.count(), 2 + default_actions().len());
 	}
 }
@@ -1,7 +1,41 @@
+use regex::{Regex, RegexBuilder};
 use rustc_hash::FxHashMap;
 
 use crate::action::{ActionResult, PatternActionType, ResultAction};
 
+/// Checks a captured value against the type of a `{name:type}` placeholder.
+/// Unknown types never match.
+fn capture_has_type(kind: &str, value: &str) -> bool {
+	match kind {
+		"num" => value.parse::<f64>().is_ok_and(f64::is_finite),
+		"int" => value.parse::<i64>().is_ok(),
+		"date" => is_iso_date(value),
+		_ => false,
+	}
+}
+
+fn is_iso_date(value: &str) -> bool {
+	let mut parts = value.split('-');
+	let (Some(year), Some(month), Some(day), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+		return false;
+	};
+	if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+		return false;
+	}
+	let (Ok(year), Ok(month), Ok(day)) = (year.parse::<u32>(), month.parse::<u32>(), day.parse::<u32>()) else {
+		return false;
+	};
+	let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+	let days = match month {
+		1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+		4 | 6 | 9 | 11 => 30,
+		2 if leap => 29,
+		2 => 28,
+		_ => return false,
+	};
+	(1..=days).contains(&day)
+}
+
 #[must_use]
 pub fn match_pattern(pattern: &str, input: &str) -> Option<FxHashMap<String, String>> {
 	let mut captures = FxHashMap::default();
@@ -25,7 +59,10 @@ pub fn match_pattern(pattern: &str, input: &str) -> Option<FxHashMap<String, Str
 					let var_end = pat.bytes().skip(var_start).position(|b| b == b'}').map(|p| var_start + p);
 
 					if let Some(var_end) = var_end {
-						let var_name = &pat[var_start..var_end];
+						let (var_name, var_type) = match pat[var_start..var_end].split_once(':') {
+							Some((name, kind)) => (name, Some(kind)),
+							None => (&pat[var_start..var_end], None),
+						};
 
 						let next_literal_idx = var_end + 1;
 						let value_end = if next_literal_idx < pat_bytes.len() {
@@ -37,6 +74,9 @@ pub fn match_pattern(pattern: &str, input: &str) -> Option<FxHashMap<String, Str
 
 						if value_end > inp_idx {
 							let value = &inp[inp_idx..value_end];
+							if var_type.is_some_and(|kind| !capture_has_type(kind, value)) {
+								return None;
+							}
 							captures.insert(var_name.to_string(), value.to_string());
 							inp_idx = value_end;
 							pat_idx = next_literal_idx;
@@ -69,6 +109,27 @@ pub fn match_pattern(pattern: &str, input: &str) -> Option<FxHashMap<String, Str
 	Some(captures)
 }
 
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+/// Compiles a regex pattern anchored to the whole query, or `None` if it is
+/// invalid or too large.
+#[must_use]
+pub fn compile_regex_pattern(pattern: &str) -> Option<Regex> {
+	RegexBuilder::new(&format!("^(?:{pattern})$")).size_limit(REGEX_SIZE_LIMIT).build().ok()
+}
+
+#[must_use]
+pub fn match_regex(regex: &Regex, input: &str) -> Option<FxHashMap<String, String>> {
+	let found = regex.captures(input)?;
+	Some(
+		regex
+			.capture_names()
+			.flatten()
+			.filter_map(|name| found.name(name).map(|value| (name.to_owned(), value.as_str().to_owned())))
+			.collect(),
+	)
+}
+
 #[must_use]
 pub fn expand_template<S: std::hash::BuildHasher>(
 	template: &str,
@@ -88,7 +149,8 @@ pub fn expand_template<S: std::hash::BuildHasher>(
 			let key_end = key_start + close;
 			let key = &template[key_start..key_end];
 
-			if let Some(value) = captures.get(key) {
+			let value = captures.get(key).or_else(|| key.split_once(':').and_then(|(name, _)| captures.get(name)));
+			if let Some(value) = value {
 				result.push_str(&template[last_end..i]);
 				result.push_str(value);
 				i = key_end + 1;
@@ -102,38 +164,114 @@ pub fn expand_template<S: std::hash::BuildHasher>(
 	result
 }
 
-#[must_use]
-pub fn create_result<S: std::hash::BuildHasher>(
-	action_id: &str,
-	_action_name: &str,
-	pattern: &str,
+fn expand_action<S: std::hash::BuildHasher>(
 	action_type: &PatternActionType,
 	captures: &std::collections::HashMap<String, String, S>,
-	icon: &str,
-) -> ActionResult {
-	let title = expand_template(pattern, captures);
-
-	let result_action = match action_type {
+	out: &mut Vec<ResultAction>,
+) {
+	match action_type {
 		PatternActionType::OpenUrl(url) => {
 			let expanded_url = expand_template(url, captures);
-			ResultAction::OpenUrl(expanded_url)
+			out.push(ResultAction::OpenUrl(expanded_url));
 		}
 		PatternActionType::CopyText(text) => {
 			let expanded_text = expand_template(text, captures);
-			ResultAction::CopyText(expanded_text)
+			out.push(ResultAction::CopyText(expanded_text));
 		}
 		PatternActionType::RunCommand { cmd, args } => {
 			let expanded_cmd = expand_template(cmd, captures);
 			let expanded_args = args.iter().map(|arg| expand_template(arg, captures)).collect();
-			ResultAction::RunCommand { cmd: expanded_cmd, args: expanded_args }
+			out.push(ResultAction::RunCommand { cmd: expanded_cmd, args: expanded_args });
 		}
-	};
+		PatternActionType::Multi(actions) => {
+			for action in actions {
+				expand_action(action, captures, out);
+			}
+		}
+	}
+}
 
-	let subtitle = match &result_action {
-		ResultAction::OpenUrl(url) => url.clone(),
-		ResultAction::CopyText(text) => format!("Copy: {text}"),
-		ResultAction::RunCommand { cmd, args } => format!("Run: {cmd} {}", args.join(" ")),
-	};
+#[must_use]
+pub fn create_results<S: std::hash::BuildHasher>(
+	action_id: &str,
+	_action_name: &str,
+	pattern: &str,
+	action_type: &PatternActionType,
+	captures: &std::collections::HashMap<String, String, S>,
+	icon: &str,
+) -> Vec<ActionResult> {
+	let title = expand_template(pattern, captures);
+
+	let mut result_actions = Vec::with_capacity(1);
+	expand_action(action_type, captures, &mut result_actions);
+	let single = result_actions.len() == 1;
+
+	result_actions
+		.into_iter()
+		.enumerate()
+		.map(|(i, result_action)| {
+			let subtitle = match &result_action {
+				ResultAction::OpenUrl(url) => url.clone(),
+				ResultAction::CopyText(text) => format!("Copy: {text}"),
+				ResultAction::RunCommand { cmd, args } => format!("Run: {cmd} {}", args.join(" ")),
+			};
+			let id = if single { format!("{action_id}:{title}") } else { format!("{action_id}:{title}:{i}") };
+			ActionResult::new(id, title.as_str(), subtitle, icon, 95.0, result_action)
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_multi_action_results() {
+		let action_type = PatternActionType::Multi(vec![
+			PatternActionType::CopyText("{expr}".to_owned()),
+			PatternActionType::OpenUrl("https://duckduckgo.com/?q={expr}".to_owned()),
+		]);
+		let captures = match_pattern("calc {expr}", "calc 2+2").unwrap();
+
+		let results = create_results("calc", "Calc", "calc {expr}", &action_type, &captures, "icon");
+		assert_eq!(results.len(), 2);
+		assert_eq!(results[0].id, "calc:calc 2+2:0");
+		assert_eq!(results[1].id, "calc:calc 2+2:1");
+		assert_eq!(results[0].action, ResultAction::CopyText("2+2".to_owned()));
+		assert_eq!(results[1].action, ResultAction::OpenUrl("https://duckduckgo.com/?q=2+2".to_owned()));
+	}
+
+	#[test]
+	fn test_regex_pattern_expands_url() {
+		let regex = compile_regex_pattern(r"weather in (?P<city>[\w ]+?)(?: (?P<day>today|tomorrow))?").unwrap();
+		let action_type = PatternActionType::OpenUrl("https://wttr.in/{city}?day={day}".to_owned());
+
+		let captures = match_regex(&regex, "weather in New York tomorrow").unwrap();
+		let results = create_results("wx", "Weather", "weather in New York tomorrow", &action_type, &captures, "icon");
+		assert_eq!(results[0].action, ResultAction::OpenUrl("https://wttr.in/New York?day=tomorrow".to_owned()));
 
-	ActionResult::new(format!("{action_id}:{title}"), title, subtitle, icon, 95.0, result_action)
+		let captures = match_regex(&regex, "weather in Paris").unwrap();
+		assert_eq!(captures.get("city").map(String::as_str), Some("Paris"));
+		assert!(!captures.contains_key("day"));
+
+		assert!(match_regex(&regex, "what is the weather in Paris").is_none());
+		assert!(compile_regex_pattern("(unclosed").is_none());
+	}
+
+	#[test]
+	fn test_typed_captures() {
+		assert_eq!(match_pattern("{n:int} x", "5 x").unwrap().get("n").map(String::as_str), Some("5"));
+		assert!(match_pattern("{n:int} x", "five x").is_none());
+		assert!(match_pattern("{n:int} x", "5.5 x").is_none());
+
+		let captures = match_pattern("convert {amount:num} usd", "convert 12.50 usd").unwrap();
+		assert_eq!(expand_template("convert {amount:num} usd", &captures), "convert 12.50 usd");
+		assert!(match_pattern("convert {amount:num} usd", "convert abc usd").is_none());
+		assert!(match_pattern("convert {amount} usd", "convert abc usd").is_some());
+
+		assert!(match_pattern("on {day:date}", "on 2024-02-29").is_some());
+		assert!(match_pattern("on {day:date}", "on 2023-02-29").is_none());
+		assert!(match_pattern("on {day:date}", "on tomorrow").is_none());
+		assert!(match_pattern("{n:hex}", "ff").is_none());
+	}
 }
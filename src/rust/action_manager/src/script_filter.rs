@@ -1,8 +1,7 @@
-use std::{io::Read, num::NonZeroUsize, path::{Path, PathBuf}, process::{Command, Stdio}, time::{Duration, Instant}};
+use std::{fmt, io::Read, num::NonZeroUsize, path::{Path, PathBuf}, process::{Command, Stdio}, time::{Duration, Instant}};
 
 use lru::LruCache;
 use parking_lot::RwLock;
-use rustc_hash::FxHasher;
 use serde::{Deserialize, Serialize};
 use sonic_rs::{JsonContainerTrait, JsonValueTrait};
 use wait_timeout::ChildExt;
@@ -23,6 +22,64 @@ const CACHE_TTL_MS: u64 = 2000;
 const CACHE_SIZE: usize = 100;
 const CACHE_SIZE_NZ: NonZeroUsize = NonZeroUsize::new(CACHE_SIZE).unwrap();
 
+#[derive(Debug, Clone)]
+pub enum ScriptFilterError {
+	NotFound(PathBuf),
+	Spawn(String),
+	Timeout(u64),
+	NonZeroExit { code: Option<i32>, stderr: String },
+	InvalidJson { message: String, output: String },
+}
+
+impl ScriptFilterError {
+	#[must_use]
+	pub const fn kind(&self) -> &'static str {
+		match self {
+			Self::NotFound(_) => "not_found",
+			Self::Spawn(_) => "spawn",
+			Self::Timeout(_) => "timeout",
+			Self::NonZeroExit { .. } => "exit",
+			Self::InvalidJson { .. } => "invalid_json",
+		}
+	}
+
+	#[must_use]
+	pub const fn title(&self) -> &'static str {
+		match self {
+			Self::NotFound(_) => "Script Not Found",
+			Self::Spawn(_) => "Script Error",
+			Self::Timeout(_) => "Script Timed Out",
+			Self::NonZeroExit { .. } => "Script Failed",
+			Self::InvalidJson { .. } => "Invalid Script Output",
+		}
+	}
+
+	#[must_use]
+	pub const fn icon(&self) -> &'static str {
+		match self {
+			Self::NotFound(_) => "questionmark.folder",
+			Self::Spawn(_) | Self::NonZeroExit { .. } => "exclamationmark.triangle",
+			Self::Timeout(_) => "clock.badge.exclamationmark",
+			Self::InvalidJson { .. } => "curlybraces",
+		}
+	}
+}
+
+impl fmt::Display for ScriptFilterError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::NotFound(path) => write!(f, "Script not found: {}", path.display()),
+			Self::Spawn(e) => write!(f, "Failed to execute script: {e}"),
+			Self::Timeout(ms) => write!(f, "Script timed out after {ms}ms"),
+			Self::NonZeroExit { code: Some(code), stderr } => write!(f, "Script exited with status {code}: {stderr}"),
+			Self::NonZeroExit { code: None, stderr } => write!(f, "Script was terminated: {stderr}"),
+			Self::InvalidJson { message, output } => write!(f, "Failed to parse JSON: {message}. Output: {output}"),
+		}
+	}
+}
+
+impl std::error::Error for ScriptFilterError {}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScriptOutput {
 	pub items:     Vec<ScriptItem>,
@@ -67,28 +124,30 @@ fn init_cache() {
 	}
 }
 
-fn cache_key(script_path: &str, query: &str) -> CacheKey {
-	use std::hash::{Hash, Hasher};
-	let mut hasher = FxHasher::default();
-	script_path.hash(&mut hasher);
-	query.hash(&mut hasher);
-	hasher.finish()
+fn cache_key(script_path: &str, extension_dir: &str, query: &str, env_overrides: &[(String, String)]) -> CacheKey {
+	shared_utils::stable_hash(&(script_path, extension_dir, query, env_overrides))
 }
 
-fn check_cache(key: CacheKey) -> Option<Vec<ActionResult>> {
+fn check_cache(key: CacheKey, ttl_ms: u64) -> Option<Vec<ActionResult>> {
+	if ttl_ms == 0 {
+		return None;
+	}
 	init_cache();
 	let mut cache = SCRIPT_CACHE.write();
 
 	if let Some(ref mut lru) = *cache
 		&& let Some(entry) = lru.get(&key)
-		&& entry.timestamp.elapsed() < Duration::from_millis(CACHE_TTL_MS)
+		&& entry.timestamp.elapsed() < Duration::from_millis(ttl_ms)
 	{
 		return Some(entry.results.clone());
 	}
 	None
 }
 
-fn store_cache(key: CacheKey, results: Vec<ActionResult>) {
+fn store_cache(key: CacheKey, results: Vec<ActionResult>, ttl_ms: u64) {
+	if ttl_ms == 0 {
+		return;
+	}
 	init_cache();
 	let mut cache = SCRIPT_CACHE.write();
 
@@ -97,15 +156,33 @@ fn store_cache(key: CacheKey, results: Vec<ActionResult>) {
 	}
 }
 
+/// Returns the results of a previous run of the script for `query` if they are
+/// still within the cache TTL, without running anything.
+#[must_use]
+pub fn cached_script_filter(
+	script_path: &str,
+	extension_dir: &str,
+	query: &str,
+	cache_ttl_ms: Option<u64>,
+	env_overrides: &[(String, String)],
+) -> Option<Vec<ActionResult>> {
+	check_cache(cache_key(script_path, extension_dir, query, env_overrides), cache_ttl_ms.unwrap_or(CACHE_TTL_MS))
+}
+
 #[allow(clippy::too_many_lines)]
 pub fn execute_script_filter(
 	script_path: &str,
 	extension_dir: &str,
 	query: &str,
 	action_id: &str,
-) -> Result<Vec<ActionResult>, String> {
-	let key = cache_key(script_path, query);
-	if let Some(cached) = check_cache(key) {
+	timeout_ms: Option<u64>,
+	cache_ttl_ms: Option<u64>,
+	env_overrides: &[(String, String)],
+) -> Result<Vec<ActionResult>, ScriptFilterError> {
+	let timeout_ms = timeout_ms.unwrap_or(SCRIPT_TIMEOUT_MS);
+	let cache_ttl_ms = cache_ttl_ms.unwrap_or(CACHE_TTL_MS);
+	let key = cache_key(script_path, extension_dir, query, env_overrides);
+	if let Some(cached) = check_cache(key, cache_ttl_ms) {
 		return Ok(cached);
 	}
 
@@ -116,7 +193,7 @@ pub fn execute_script_filter(
 	};
 
 	if !script_path.exists() {
-		return Err(format!("Script not found: {}", script_path.display()));
+		return Err(ScriptFilterError::NotFound(script_path));
 	}
 
 	let manifest_path = Path::new(extension_dir).join("manifest.json");
@@ -130,18 +207,18 @@ pub fn execute_script_filter(
 	let mut command = Command::new(&script_path);
 	command.arg(query).current_dir(extension_dir).stdout(Stdio::piped()).stderr(Stdio::piped());
 
-	for (key, value) in env_vars {
+	for (key, value) in env_vars.iter().chain(env_overrides) {
 		command.env(key, value);
 	}
 
-	let mut child = command.spawn().map_err(|e| format!("Failed to execute script: {e}"))?;
+	let mut child = command.spawn().map_err(|e| ScriptFilterError::Spawn(e.to_string()))?;
 
-	let timeout = Duration::from_millis(SCRIPT_TIMEOUT_MS);
+	let timeout = Duration::from_millis(timeout_ms);
 
-	let Some(status) = child.wait_timeout(timeout).map_err(|e| format!("Failed to wait for script: {e}"))? else {
+	let Some(status) = child.wait_timeout(timeout).map_err(|e| ScriptFilterError::Spawn(e.to_string()))? else {
 		let _ = child.kill();
 		let _ = child.wait();
-		return Err(format!("Script timed out after {SCRIPT_TIMEOUT_MS}ms"));
+		return Err(ScriptFilterError::Timeout(timeout_ms));
 	};
 
 	let mut stdout = Vec::with_capacity(4096);
@@ -157,12 +234,17 @@ pub fn execute_script_filter(
 	let output = std::process::Output { status, stdout, stderr };
 
 	if !output.status.success() {
-		let stderr = String::from_utf8_lossy(&output.stderr);
-		return Err(format!("Script failed: {stderr}"));
+		return Err(ScriptFilterError::NonZeroExit {
+			code:   output.status.code(),
+			stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+		});
 	}
 
-	let script_output: ScriptOutput = sonic_rs::from_slice(&output.stdout)
-		.map_err(|e| format!("Failed to parse JSON: {}. Output: {}", e, String::from_utf8_lossy(&output.stdout)))?;
+	let script_output: ScriptOutput =
+		sonic_rs::from_slice(&output.stdout).map_err(|e| ScriptFilterError::InvalidJson {
+			message: e.to_string(),
+			output:  String::from_utf8_lossy(&output.stdout).into_owned(),
+		})?;
 
 	let results: Vec<ActionResult> = script_output
 		.items
@@ -222,7 +304,69 @@ pub fn execute_script_filter(
 		})
 		.collect();
 
-	store_cache(key, results.clone());
+	store_cache(key, results.clone(), cache_ttl_ms);
 
 	Ok(results)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	#[cfg(unix)]
+	use crate::tests::write_script;
+
+	#[test]
+	fn test_missing_script_is_not_found() {
+		let err =
+			execute_script_filter("/nonexistent/summon-filter", "/nonexistent", "q", "ext", None, None, &[]).unwrap_err();
+		assert!(matches!(err, ScriptFilterError::NotFound(_)));
+		assert_eq!(err.kind(), "not_found");
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn test_raised_timeout_allows_slow_script() {
+		let dir = tempfile::TempDir::new().unwrap();
+		let ext = dir.path().to_string_lossy().into_owned();
+		let script = write_script(dir.path(), r#"sleep 0.3; echo '{"items":[{"title":"done"}]}'"#);
+
+		let err = execute_script_filter(&script, &ext, "q", "ext", Some(50), Some(0), &[]).unwrap_err();
+		assert!(matches!(err, ScriptFilterError::Timeout(50)));
+
+		let results = execute_script_filter(&script, &ext, "q", "ext", Some(5000), Some(0), &[]).unwrap();
+		assert_eq!(results[0].title, "done");
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn test_zero_ttl_bypasses_cache() {
+		let dir = tempfile::TempDir::new().unwrap();
+		let ext = dir.path().to_string_lossy().into_owned();
+		let script = write_script(
+			dir.path(),
+			r#"n=$(($(cat count 2>/dev/null || echo 0) + 1)); echo $n > count; echo "{\"items\":[{\"title\":\"$n\"}]}""#,
+		);
+		let run = |ttl| execute_script_filter(&script, &ext, "q", "ext", None, ttl, &[]).unwrap()[0].title.clone();
+
+		assert_eq!(run(Some(0)), "1");
+		assert_eq!(run(Some(0)), "2");
+		assert_eq!(run(Some(60_000)), "3");
+		assert_eq!(run(Some(60_000)), "3");
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn test_env_overrides_win_over_manifest() {
+		let dir = tempfile::TempDir::new().unwrap();
+		let ext = dir.path().to_string_lossy().into_owned();
+		std::fs::write(dir.path().join("manifest.json"), r#"{"env":{"API_KEY":"bundled","REGION":"eu"}}"#).unwrap();
+		let script = write_script(dir.path(), r#"echo "{\"items\":[{\"title\":\"$API_KEY $REGION\"}]}""#);
+
+		let overrides = [("API_KEY".to_owned(), "secret".to_owned())];
+		let results = execute_script_filter(&script, &ext, "q", "ext", None, Some(0), &overrides).unwrap();
+		assert_eq!(results[0].title, "secret eu");
+
+		let results = execute_script_filter(&script, &ext, "q", "ext", None, Some(0), &[]).unwrap();
+		assert_eq!(results[0].title, "bundled eu");
+	}
+}
@@ -4,6 +4,7 @@ use storage_utils::RkyvStorage;
 
 #[derive(Archive, Deserialize, Serialize, CheckBytes, Debug, Clone)]
 #[rkyv(derive(Debug))]
+#[derive(serde::Serialize, serde::Deserialize)]
 pub struct AppEntry {
 	pub name: String,
 	pub path: String,
@@ -1,11 +1,48 @@
-use std::collections::VecDeque;
+use std::{collections::VecDeque, io, path::Path};
 
-use chrono::{DateTime, Datelike, Local, TimeZone, Utc};
+use bytecheck::CheckBytes;
+use chrono::{DateTime, Days, Local, MappedLocalTime, Months, NaiveDate, NaiveTime, TimeZone, Utc};
 use chrono_tz::Tz;
+use evalexpr::{ContextWithMutableFunctions, ContextWithMutableVariables, Function, HashMapContext, Value};
+use parking_lot::{Mutex, RwLock};
+use rkyv::{Archive, Deserialize, Serialize};
 use rustc_hash::FxHashMap;
+use storage_utils::RkyvStorage;
 
 const MAX_HISTORY: usize = 50;
 
+type MathFn = fn(f64) -> f64;
+
+const MATH_FUNCTIONS: &[(&str, MathFn)] = &[
+	("sqrt", f64::sqrt),
+	("cbrt", f64::cbrt),
+	("abs", f64::abs),
+	("sin", f64::sin),
+	("cos", f64::cos),
+	("tan", f64::tan),
+	("asin", f64::asin),
+	("acos", f64::acos),
+	("atan", f64::atan),
+	("ln", f64::ln),
+	("log", f64::log10),
+	("log10", f64::log10),
+	("log2", f64::log2),
+	("exp", f64::exp),
+	("floor", f64::floor),
+	("ceil", f64::ceil),
+	("round", f64::round),
+];
+
+fn math_context() -> HashMapContext {
+	let mut context = HashMapContext::new();
+	for &(name, f) in MATH_FUNCTIONS {
+		let _ = context.set_function(name.to_owned(), Function::new(move |arg| Ok(Value::Float(f(arg.as_number()?)))));
+	}
+	let _ = context.set_value("pi".to_owned(), Value::Float(std::f64::consts::PI));
+	let _ = context.set_value("e".to_owned(), Value::Float(std::f64::consts::E));
+	context
+}
+
 fn normalize_timezone(tz: &str) -> &str {
 	match tz.to_uppercase().as_str() {
 		"EST" | "EDT" | "ET" => "America/New_York",
@@ -23,22 +60,158 @@ fn normalize_timezone(tz: &str) -> &str {
 	}
 }
 
-#[derive(Clone, Debug)]
+fn currency_symbol(code: &str) -> Option<(&'static str, usize)> {
+	match code {
+		"USD" => Some(("$", 2)),
+		"EUR" => Some(("€", 2)),
+		"GBP" => Some(("£", 2)),
+		"JPY" => Some(("¥", 0)),
+		"CNY" => Some(("¥", 2)),
+		"KRW" => Some(("₩", 0)),
+		"INR" => Some(("₹", 2)),
+		"AUD" => Some(("A$", 2)),
+		"CAD" => Some(("C$", 2)),
+		_ => None,
+	}
+}
+
+const CURRENCY_SYMBOLS: &[(&str, &str)] = &[("$", "USD"), ("€", "EUR"), ("£", "GBP"), ("¥", "JPY")];
+
+fn currency_code(token: &str) -> String {
+	CURRENCY_SYMBOLS
+		.iter()
+		.find(|(symbol, _)| *symbol == token)
+		.map_or_else(|| token.to_uppercase(), |(_, code)| (*code).to_owned())
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum UnitFamily {
+	Length,
+	Mass,
+	Temperature,
+}
+
+fn parse_unit(unit: &str) -> Option<(UnitFamily, &'static str, f64)> {
+	match unit.to_lowercase().as_str() {
+		"km" | "kilometer" | "kilometers" | "kilometre" | "kilometres" => Some((UnitFamily::Length, "km", 1000.0)),
+		"mi" | "mile" | "miles" => Some((UnitFamily::Length, "mi", 1609.344)),
+		"m" | "meter" | "meters" | "metre" | "metres" => Some((UnitFamily::Length, "m", 1.0)),
+		"ft" | "foot" | "feet" => Some((UnitFamily::Length, "ft", 0.3048)),
+		"in" | "inch" | "inches" => Some((UnitFamily::Length, "in", 0.0254)),
+		"cm" | "centimeter" | "centimeters" | "centimetre" | "centimetres" => Some((UnitFamily::Length, "cm", 0.01)),
+		"kg" | "kilogram" | "kilograms" => Some((UnitFamily::Mass, "kg", 1000.0)),
+		"lb" | "lbs" | "pound" | "pounds" => Some((UnitFamily::Mass, "lb", 453.592_37)),
+		"g" | "gram" | "grams" => Some((UnitFamily::Mass, "g", 1.0)),
+		"oz" | "ounce" | "ounces" => Some((UnitFamily::Mass, "oz", 28.349_523_125)),
+		"c" | "celsius" => Some((UnitFamily::Temperature, "°C", 1.0)),
+		"f" | "fahrenheit" => Some((UnitFamily::Temperature, "°F", 1.0)),
+		"k" | "kelvin" => Some((UnitFamily::Temperature, "K", 1.0)),
+		_ => None,
+	}
+}
+
+fn to_kelvin(value: f64, unit: &str) -> f64 {
+	match unit {
+		"°C" => value + 273.15,
+		"°F" => (value - 32.0).mul_add(5.0 / 9.0, 273.15),
+		_ => value,
+	}
+}
+
+fn from_kelvin(value: f64, unit: &str) -> f64 {
+	match unit {
+		"°C" => value - 273.15,
+		"°F" => (value - 273.15).mul_add(9.0 / 5.0, 32.0),
+		_ => value,
+	}
+}
+
+fn format_number(value: f64, decimals: usize) -> String {
+	let formatted = format!("{value:.decimals$}");
+	if formatted.contains('.') { formatted.trim_end_matches('0').trim_end_matches('.').to_owned() } else { formatted }
+}
+
+fn rewrite_percentage(expr: &str) -> Option<String> {
+	let lower = expr.to_lowercase();
+
+	if let Some((x, rest)) = lower.split_once(" is what") {
+		let y = rest.trim_start().strip_prefix('%')?.trim_start().strip_prefix("of ")?.trim();
+		return (!x.trim().is_empty() && !y.is_empty()).then(|| format!("({x}) * 100.0 / ({y})"));
+	}
+
+	if let Some((x, rest)) = lower.split_once('%')
+		&& let Some(y) = rest.trim_start().strip_prefix("of ")
+	{
+		return (!x.trim().is_empty() && !y.trim().is_empty()).then(|| format!("({x}) / 100.0 * ({})", y.trim()));
+	}
+
+	let body = lower.trim_end().strip_suffix('%')?;
+	let (base, pct) = body.split_at(body.rfind(['+', '-'])?);
+	let (op, pct) = pct.split_at(1);
+	let (base, pct) = (base.trim(), pct.trim());
+	if base.is_empty() || pct.parse::<f64>().is_err() {
+		return None;
+	}
+	Some(format!("({base}) * (1.0 {op} {pct} / 100.0)"))
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CurrencyFormat {
+	#[default]
+	Code,
+	Symbol,
+}
+
+impl CurrencyFormat {
+	#[must_use]
+	pub fn format(self, amount: f64, code: &str) -> String {
+		match (self, currency_symbol(code)) {
+			(Self::Symbol, Some((symbol, decimals))) => format!("{symbol}{amount:.decimals$}"),
+			_ => format_amount(code, amount),
+		}
+	}
+}
+
+const CRYPTO_CODES: &[&str] = &["BTC", "ETH", "SOL", "LTC", "XRP", "DOGE", "ADA", "DOT", "USDT", "USDC"];
+
+/// A timezone conversion result.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ZonedTime {
+	/// The converted time, followed by its date when that isn't today.
+	Exact(String),
+	/// The source time occurs twice as clocks fall back; both conversions,
+	/// earliest first.
+	Ambiguous(String, String),
+	/// The source time is skipped as clocks spring forward.
+	Gap,
+}
+
+#[must_use]
+pub fn format_amount(code: &str, amount: f64) -> String {
+	let decimals = if CRYPTO_CODES.contains(&code) { 8 } else { 2 };
+	format!("{amount:.decimals$} {code}")
+}
+
+#[derive(Archive, Deserialize, Serialize, CheckBytes, Clone, Debug)]
+#[rkyv(derive(Debug))]
 pub struct CalculationEntry {
 	pub query:  String,
 	pub result: String,
 }
 
 pub struct Calculator {
-	exchange_rates: FxHashMap<String, f64>,
-	history:        VecDeque<CalculationEntry>,
+	exchange_rates:  RwLock<FxHashMap<String, f64>>,
+	history:         Mutex<VecDeque<CalculationEntry>>,
+	currency_format: RwLock<CurrencyFormat>,
+	history_storage: Option<RkyvStorage<CalculationEntry>>,
+	math_context:    HashMapContext,
 }
 
 impl Calculator {
 	#[must_use]
 	pub fn new() -> Self {
 		Self {
-			exchange_rates: FxHashMap::from_iter([
+			exchange_rates:  RwLock::new(FxHashMap::from_iter([
 				("USD".to_owned(), 1.0),
 				("EUR".to_owned(), 0.92),
 				("GBP".to_owned(), 0.79),
@@ -49,15 +222,42 @@ impl Calculator {
 				("CHF".to_owned(), 0.88),
 				("INR".to_owned(), 83.2),
 				("KRW".to_owned(), 1320.0),
-			]),
-			history:        VecDeque::with_capacity(MAX_HISTORY),
+			])),
+			history:         Mutex::new(VecDeque::with_capacity(MAX_HISTORY)),
+			currency_format: RwLock::new(CurrencyFormat::default()),
+			history_storage: None,
+			math_context:    math_context(),
+		}
+	}
+
+	pub fn with_history_path(path: impl AsRef<Path>) -> io::Result<Self> {
+		let storage = RkyvStorage::new(path)?;
+		let entries = storage.get_all();
+		let mut calc = Self::new();
+		calc.history.get_mut().extend(entries.iter().skip(entries.len().saturating_sub(MAX_HISTORY)).cloned());
+		calc.history_storage = Some(storage);
+		Ok(calc)
+	}
+
+	pub fn update_rates(&self, rates: FxHashMap<String, f64>) {
+		let mut exchange_rates = self.exchange_rates.write();
+		for (code, rate) in rates {
+			let code = code.to_uppercase();
+			if code != "USD" && rate.is_finite() && rate > 0.0 {
+				exchange_rates.insert(code, rate);
+			}
 		}
 	}
 
+	#[must_use]
+	pub fn rate_count(&self) -> usize { self.exchange_rates.read().len() }
+
+	pub fn set_currency_format(&self, format: CurrencyFormat) { *self.currency_format.write() = format; }
+
 	#[must_use]
 	#[allow(clippy::cast_precision_loss)]
 	pub fn eval_math(&self, expr: &str) -> Option<f64> {
-		evalexpr::eval(expr).ok().and_then(|v| {
+		evalexpr::eval_with_context(expr, &self.math_context).ok().and_then(|v| {
 			v.as_float().ok().or_else(|| {
 				#[allow(clippy::cast_precision_loss)]
 				v.as_int().ok().map(|i| i as f64)
@@ -67,27 +267,36 @@ impl Calculator {
 
 	#[must_use]
 	pub fn convert_currency(&self, query: &str) -> Option<(f64, String, String, f64)> {
-		let parts: Vec<&str> = query.split_whitespace().collect();
+		let mut parts: Vec<&str> = query.split_whitespace().collect();
+
+		let first = *parts.first()?;
+		if let Some((symbol, amount)) =
+			CURRENCY_SYMBOLS.iter().find_map(|(symbol, _)| first.strip_prefix(symbol).map(|amount| (*symbol, amount)))
+		{
+			parts.splice(..1, [amount, symbol]);
+		}
 
 		if parts.len() < 3 {
 			return None;
 		}
 
-		let amount: f64 = parts.first()?.parse().ok()?;
+		let amount: f64 = parts.first()?.replace(',', "").parse().ok()?;
 
-		let from_currency = parts.get(1)?.to_uppercase();
+		let from_currency = currency_code(parts.get(1)?);
 		let to_currency = if parts.len() == 4 && parts.get(2)?.eq_ignore_ascii_case("to") {
-			parts.get(3)?.to_uppercase()
+			currency_code(parts.get(3)?)
 		} else if parts.len() == 3 {
-			parts.get(2)?.to_uppercase()
+			currency_code(parts.get(2)?)
 		} else if parts.len() >= 4 && parts.get(2)?.eq_ignore_ascii_case("in") {
-			parts.get(3)?.to_uppercase()
+			currency_code(parts.get(3)?)
 		} else {
 			return None;
 		};
 
-		let from_rate = self.exchange_rates.get(&from_currency)?;
-		let to_rate = self.exchange_rates.get(&to_currency)?;
+		let (from_rate, to_rate) = {
+			let exchange_rates = self.exchange_rates.read();
+			(*exchange_rates.get(&from_currency)?, *exchange_rates.get(&to_currency)?)
+		};
 
 		let usd_amount = amount / from_rate;
 		let result = usd_amount * to_rate;
@@ -96,9 +305,100 @@ impl Calculator {
 	}
 
 	#[must_use]
-	pub fn convert_timezone(&self, query: &str) -> Option<(String, String, String, String)> {
+	pub fn convert_unit(&self, query: &str) -> Option<(f64, String, String, f64)> {
+		let parts: Vec<&str> = query.split_whitespace().collect();
+
+		let amount: f64 = parts.first()?.parse().ok()?;
+		let to_unit = match parts.len() {
+			3 => parts.get(2)?,
+			4 if parts.get(2)?.eq_ignore_ascii_case("to") || parts.get(2)?.eq_ignore_ascii_case("in") => parts.get(3)?,
+			_ => return None,
+		};
+
+		let (from_family, from, from_factor) = parse_unit(parts.get(1)?)?;
+		let (to_family, to, to_factor) = parse_unit(to_unit)?;
+		if from_family != to_family {
+			return None;
+		}
+
+		let result = if from_family == UnitFamily::Temperature {
+			from_kelvin(to_kelvin(amount, from), to)
+		} else {
+			amount * from_factor / to_factor
+		};
+
+		Some((amount, from.to_owned(), to.to_owned(), result))
+	}
+
+	#[must_use]
+	pub fn convert_currency_chain(&self, query: &str) -> Option<(f64, String)> {
 		let parts: Vec<&str> = query.split_whitespace().collect();
 
+		if parts.len() < 5 || !(parts.get(2)?.eq_ignore_ascii_case("to") || parts.get(2)?.eq_ignore_ascii_case("in")) {
+			return None;
+		}
+
+		let rest = parts.get(4..)?.join(" ");
+		if !rest.starts_with(['+', '-', '*', '/', '^', '%']) {
+			return None;
+		}
+
+		let (_, _, to, converted) = self.convert_currency(&parts.get(..4)?.join(" "))?;
+		let result = self.eval_math(&format!("{converted} {rest}"))?;
+
+		Some((result, to))
+	}
+
+	#[must_use]
+	pub fn convert_base(&self, query: &str) -> Option<String> {
+		let parts: Vec<&str> = query.split_whitespace().collect();
+		let [number, connector, target] = parts.as_slice() else {
+			return None;
+		};
+		if !connector.eq_ignore_ascii_case("to") && !connector.eq_ignore_ascii_case("in") {
+			return None;
+		}
+
+		let (negative, digits) = number.strip_prefix('-').map_or((false, *number), |rest| (true, rest));
+		let lower = digits.to_ascii_lowercase();
+		let (radix, digits) = [("0x", 16), ("0b", 2), ("0o", 8)]
+			.iter()
+			.find_map(|(prefix, radix)| lower.strip_prefix(prefix).map(|rest| (*radix, rest)))
+			.unwrap_or((10, lower.as_str()));
+		let magnitude = u64::from_str_radix(digits, radix).ok()?;
+		let value = if negative { 0i64.checked_sub_unsigned(magnitude)? } else { i64::try_from(magnitude).ok()? };
+
+		let sign = if value < 0 { "-" } else { "" };
+		let abs = value.unsigned_abs();
+		match target.to_ascii_lowercase().as_str() {
+			"hex" | "hexadecimal" => Some(format!("{sign}0x{abs:x}")),
+			"bin" | "binary" => Some(format!("{sign}0b{abs:b}")),
+			"oct" | "octal" => Some(format!("{sign}0o{abs:o}")),
+			"dec" | "decimal" => Some(value.to_string()),
+			_ => None,
+		}
+	}
+
+	#[must_use]
+	pub fn convert_timezone(&self, query: &str) -> Option<(String, String, String, ZonedTime)> {
+		Self::convert_timezone_from(query, Local::now().date_naive())
+	}
+
+	fn convert_timezone_from(query: &str, today: NaiveDate) -> Option<(String, String, String, ZonedTime)> {
+		let mut parts: Vec<&str> = query.split_whitespace().collect();
+
+		let mut date = today;
+		if let [.., on, last] = parts.as_slice()
+			&& on.eq_ignore_ascii_case("on")
+			&& let Ok(parsed) = NaiveDate::parse_from_str(last, "%Y-%m-%d")
+		{
+			date = parsed;
+			parts.truncate(parts.len() - 2);
+		} else if let Some(Ok(parsed)) = parts.get(1).map(|part| NaiveDate::parse_from_str(part, "%Y-%m-%d")) {
+			date = parsed;
+			parts.remove(1);
+		}
+
 		if parts.len() < 3 {
 			return None;
 		}
@@ -114,7 +414,7 @@ impl Calculator {
 				"now".to_owned(),
 				"UTC".to_owned(),
 				parts.get(2)?.to_uppercase(),
-				target_time.format("%I:%M %p").to_string(),
+				ZonedTime::Exact(target_time.format("%I:%M %p").to_string()),
 			));
 		}
 
@@ -133,61 +433,122 @@ impl Calculator {
 		let from_tz: Tz = normalize_timezone(from_tz_str).parse().ok()?;
 		let to_tz: Tz = normalize_timezone(to_tz_str).parse().ok()?;
 
-		let time = Self::parse_time(time_str, from_tz)?;
-
-		let target_time = time.with_timezone(&to_tz);
+		let format = |time: DateTime<Tz>| {
+			let target_time = time.with_timezone(&to_tz);
+			if target_time.date_naive() == today {
+				target_time.format("%I:%M %p").to_string()
+			} else {
+				target_time.format("%I:%M %p %Y-%m-%d").to_string()
+			}
+		};
+		let result = match Self::parse_time(time_str, from_tz, date)? {
+			MappedLocalTime::Single(time) => ZonedTime::Exact(format(time)),
+			MappedLocalTime::Ambiguous(earliest, latest) => ZonedTime::Ambiguous(format(earliest), format(latest)),
+			MappedLocalTime::None => ZonedTime::Gap,
+		};
 
-		Some((
-			(*time_str).to_string(),
-			from_tz_str.to_uppercase(),
-			to_tz_str.to_uppercase(),
-			target_time.format("%I:%M %p").to_string(),
-		))
+		Some(((*time_str).to_string(), from_tz_str.to_uppercase(), to_tz_str.to_uppercase(), result))
 	}
 
-	fn parse_time(time_str: &str, tz: Tz) -> Option<DateTime<Tz>> {
-		let today = Local::now().date_naive();
-
-		if let Some((hours, minutes)) = time_str.split_once(':') {
+	/// Resolves `time_str` on `date` in `tz`. Returns `None` only when the
+	/// text isn't a time; DST gaps and overlaps come back as
+	/// `MappedLocalTime::None` and `MappedLocalTime::Ambiguous`.
+	fn parse_time(time_str: &str, tz: Tz, date: NaiveDate) -> Option<MappedLocalTime<DateTime<Tz>>> {
+		let time = if let Some((hours, minutes)) = time_str.split_once(':') {
 			let h: u32 = hours.parse().ok()?;
 			let m: u32 = minutes.parse().ok()?;
-			return tz.with_ymd_and_hms(today.year(), today.month(), today.day(), h, m, 0).single();
-		}
+			NaiveTime::from_hms_opt(h, m, 0)?
+		} else {
+			let lower = time_str.to_lowercase();
+			let h = if let Some(pm_idx) = lower.find("pm") {
+				let h: u32 = lower[..pm_idx].parse().ok()?;
+				if h == 12 { 12 } else { h + 12 }
+			} else if let Some(am_idx) = lower.find("am") {
+				let h: u32 = lower[..am_idx].parse().ok()?;
+				if h == 12 { 0 } else { h }
+			} else {
+				return None;
+			};
+			NaiveTime::from_hms_opt(h, 0, 0)?
+		};
 
-		let lower = time_str.to_lowercase();
-		if let Some(pm_idx) = lower.find("pm") {
-			let h: u32 = lower[..pm_idx].parse().ok()?;
-			let h = if h == 12 { 12 } else { h + 12 };
-			return tz.with_ymd_and_hms(today.year(), today.month(), today.day(), h, 0, 0).single();
-		}
+		Some(tz.from_local_datetime(&date.and_time(time)))
+	}
 
-		if let Some(am_idx) = lower.find("am") {
-			let h: u32 = lower[..am_idx].parse().ok()?;
-			let h = if h == 12 { 0 } else { h };
-			return tz.with_ymd_and_hms(today.year(), today.month(), today.day(), h, 0, 0).single();
-		}
+	#[must_use]
+	pub fn eval_date(&self, query: &str) -> Option<String> { Self::eval_date_from(query, Local::now().date_naive()) }
 
-		None
+	fn eval_date_from(query: &str, today: NaiveDate) -> Option<String> {
+		let lower = query.to_lowercase();
+		let parts: Vec<&str> = lower.split_whitespace().collect();
+
+		match parts.as_slice() {
+			["days", "until", date] => {
+				let target = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+				Some((target - today).num_days().to_string())
+			}
+			["today", op @ ("+" | "-"), amount, unit] => {
+				let amount: u32 = amount.parse().ok()?;
+				let date = match (*op, unit.trim_end_matches('s')) {
+					("+", "day") => today.checked_add_days(Days::new(amount.into())),
+					("-", "day") => today.checked_sub_days(Days::new(amount.into())),
+					("+", "week") => today.checked_add_days(Days::new(u64::from(amount) * 7)),
+					("-", "week") => today.checked_sub_days(Days::new(u64::from(amount) * 7)),
+					("+", "month") => today.checked_add_months(Months::new(amount)),
+					("-", "month") => today.checked_sub_months(Months::new(amount)),
+					_ => None,
+				}?;
+				Some(date.format("%Y-%m-%d").to_string())
+			}
+			_ => None,
+		}
 	}
 
-	pub fn evaluate(&mut self, query: &str) -> Option<String> {
+	pub fn evaluate(&self, query: &str) -> Option<String> {
 		let trimmed = query.trim();
 
 		if trimmed.split_whitespace().count() >= 3 {
+			if let Some(result_str) = self.convert_base(trimmed) {
+				self.add_to_history(trimmed.to_string(), result_str.clone());
+				return Some(result_str);
+			}
+
+			if let Some((result, to)) = self.convert_currency_chain(trimmed) {
+				let result_str = self.currency_format.read().format(result, &to);
+				self.add_to_history(trimmed.to_string(), result_str.clone());
+				return Some(result_str);
+			}
+
 			if let Some((_amount, _from, to, result)) = self.convert_currency(trimmed) {
-				let result_str = format!("{result:.2} {to}");
+				let result_str = self.currency_format.read().format(result, &to);
 				self.add_to_history(trimmed.to_string(), result_str.clone());
 				return Some(result_str);
 			}
 
-			if let Some((_, _from, to, result)) = self.convert_timezone(trimmed) {
-				let result_str = format!("{result} {to}");
+			if let Some((_amount, _from, to, result)) = self.convert_unit(trimmed) {
+				let result_str = format!("{} {to}", format_number(result, 2));
+				self.add_to_history(trimmed.to_string(), result_str.clone());
+				return Some(result_str);
+			}
+
+			if let Some((time, from, to, result)) = self.convert_timezone(trimmed) {
+				let result_str = match result {
+					ZonedTime::Exact(result) => format!("{result} {to}"),
+					ZonedTime::Ambiguous(earliest, latest) => format!("{earliest} {to} or {latest} {to}"),
+					ZonedTime::Gap => format!("{time} does not exist in {from} (DST gap)"),
+				};
+				self.add_to_history(trimmed.to_string(), result_str.clone());
+				return Some(result_str);
+			}
+
+			if let Some(result_str) = self.eval_date(trimmed) {
 				self.add_to_history(trimmed.to_string(), result_str.clone());
 				return Some(result_str);
 			}
 		}
 
-		if let Some(result) = self.eval_math(trimmed) {
+		let rewritten = rewrite_percentage(trimmed);
+		if let Some(result) = self.eval_math(rewritten.as_deref().unwrap_or(trimmed)) {
 			#[allow(clippy::cast_possible_truncation)]
 			let result_str = if result.fract() == 0.0 && result.abs() < 1e10 {
 				format!("{}", result as i64)
@@ -201,17 +562,39 @@ impl Calculator {
 		None
 	}
 
-	fn add_to_history(&mut self, query: String, result: String) {
-		if self.history.len() >= MAX_HISTORY {
-			self.history.pop_front();
+	fn add_to_history(&self, query: String, result: String) {
+		let mut history = self.history.lock();
+		if history.len() >= MAX_HISTORY {
+			history.pop_front();
+		}
+		let entry = CalculationEntry { query, result };
+		if let Some(ref storage) = self.history_storage {
+			let persisted = entry.clone();
+			storage.update_async(move |entries| {
+				entries.push(persisted.clone());
+				let excess = entries.len().saturating_sub(MAX_HISTORY);
+				entries.drain(..excess);
+				true
+			});
 		}
-		self.history.push_back(CalculationEntry { query, result });
+		history.push_back(entry);
 	}
 
 	#[must_use]
-	pub const fn get_history(&self) -> &VecDeque<CalculationEntry> { &self.history }
+	pub fn get_history(&self) -> VecDeque<CalculationEntry> { self.history.lock().clone() }
 
-	pub fn clear_history(&mut self) { self.history.clear(); }
+	pub fn clear_history(&self) {
+		self.history.lock().clear();
+		if let Some(ref storage) = self.history_storage {
+			let _ = storage.clear();
+		}
+	}
+
+	pub fn flush_history(&self) {
+		if let Some(ref storage) = self.history_storage {
+			storage.flush();
+		}
+	}
 }
 
 impl Default for Calculator {
@@ -232,7 +615,7 @@ mod tests {
 
 	#[test]
 	fn test_evaluate() {
-		let mut calc = Calculator::new();
+		let calc = Calculator::new();
 		assert_eq!(calc.evaluate("2 + 2"), Some("4".to_owned()));
 		assert_eq!(calc.evaluate("16 ^ 0.5"), Some("4".to_owned()));
 		assert_eq!(calc.evaluate("100 USD to EUR"), Some("92.00 EUR".to_owned()));
@@ -257,4 +640,211 @@ mod tests {
 		let result = calc.convert_timezone("3pm EST to PST");
 		assert!(result.is_some());
 	}
+
+	#[test]
+	fn test_currency_chain() {
+		let calc = Calculator::new();
+		assert_eq!(calc.evaluate("100 USD to EUR + 10"), Some("102.00 EUR".to_owned()));
+		assert_eq!(calc.evaluate("100 USD in EUR * 2"), Some("184.00 EUR".to_owned()));
+		assert_eq!(calc.evaluate("100 USD to EUR plus"), None);
+		assert_eq!(calc.evaluate("100 + 10 * 2"), Some("120".to_owned()));
+	}
+
+	#[test]
+	fn test_currency_symbol_format() {
+		let calc = Calculator::new();
+		calc.set_currency_format(CurrencyFormat::Symbol);
+		assert_eq!(calc.evaluate("100 EUR to USD"), Some("$108.70".to_owned()));
+		assert_eq!(calc.evaluate("100 USD to EUR"), Some("€92.00".to_owned()));
+		assert_eq!(calc.evaluate("100 USD to JPY"), Some("¥14950".to_owned()));
+		assert_eq!(calc.evaluate("100 USD to CHF"), Some("88.00 CHF".to_owned()));
+
+		calc.set_currency_format(CurrencyFormat::Code);
+		assert_eq!(calc.evaluate("100 USD to JPY"), Some("14950.00 JPY".to_owned()));
+	}
+
+	#[test]
+	fn test_update_rates() {
+		let calc = Calculator::new();
+		let initial = calc.rate_count();
+		calc.update_rates(FxHashMap::from_iter([
+			("EUR".to_owned(), 0.5),
+			("sek".to_owned(), 10.0),
+			("USD".to_owned(), 2.0),
+			("XYZ".to_owned(), f64::NAN),
+		]));
+		assert_eq!(calc.rate_count(), initial + 1);
+		assert_eq!(calc.evaluate("100 USD to EUR"), Some("50.00 EUR".to_owned()));
+		assert_eq!(calc.evaluate("100 EUR to SEK"), Some("2000.00 SEK".to_owned()));
+	}
+
+	#[test]
+	fn test_unit_conversion() {
+		let calc = Calculator::new();
+		assert_eq!(calc.evaluate("10 km to mi"), Some("6.21 mi".to_owned()));
+		assert_eq!(calc.evaluate("10 km to miles"), Some("6.21 mi".to_owned()));
+		assert_eq!(calc.evaluate("12 in to cm"), Some("30.48 cm".to_owned()));
+		assert_eq!(calc.evaluate("3 ft in"), Some("36 in".to_owned()));
+		assert_eq!(calc.evaluate("1 kg to lb"), Some("2.2 lb".to_owned()));
+		assert_eq!(calc.evaluate("16 oz in g"), Some("453.59 g".to_owned()));
+		assert_eq!(calc.evaluate("0 c to f"), Some("32 °F".to_owned()));
+		assert_eq!(calc.evaluate("212 F to C"), Some("100 °C".to_owned()));
+		assert_eq!(calc.evaluate("0 k to c"), Some("-273.15 °C".to_owned()));
+		assert_eq!(calc.evaluate("10 kg to miles"), None);
+		assert!(calc.convert_unit("10 kg to miles").is_none());
+	}
+
+	#[test]
+	fn test_percentages() {
+		let calc = Calculator::new();
+		assert_eq!(calc.evaluate("20% of 150"), Some("30".to_owned()));
+		assert_eq!(calc.evaluate("150 + 10%"), Some("165".to_owned()));
+		assert_eq!(calc.evaluate("150 - 10%"), Some("135".to_owned()));
+		assert_eq!(calc.evaluate("200+5%"), Some("210".to_owned()));
+		assert_eq!(calc.evaluate("30 is what % of 150"), Some("20".to_owned()));
+		assert_eq!(calc.evaluate("10 % 3"), Some("1".to_owned()));
+		assert_eq!(calc.evaluate("10 % 3 + 2"), Some("3".to_owned()));
+		assert_eq!(calc.get_history().back().map(|e| e.query.as_str()), Some("10 % 3 + 2"));
+		assert_eq!(calc.get_history().len(), 7);
+	}
+
+	#[test]
+	fn test_base_conversion() {
+		let calc = Calculator::new();
+		assert_eq!(calc.evaluate("255 to hex"), Some("0xff".to_owned()));
+		assert_eq!(calc.evaluate("0xff to dec"), Some("255".to_owned()));
+		assert_eq!(calc.evaluate("0XFF in bin"), Some("0b11111111".to_owned()));
+		assert_eq!(calc.evaluate("0b1010 to dec"), Some("10".to_owned()));
+		assert_eq!(calc.evaluate("0o17 to dec"), Some("15".to_owned()));
+		assert_eq!(calc.evaluate("-10 to hex"), Some("-0xa".to_owned()));
+		assert_eq!(calc.convert_base("0xffffffffffffffffff to dec"), None);
+		assert_eq!(calc.convert_base("-0x8000000000000000 to dec"), Some(i64::MIN.to_string()));
+		assert_eq!(calc.convert_base("12 to base"), None);
+		assert_eq!(calc.get_history().back().map(|e| e.result.as_str()), Some("-0xa"));
+	}
+
+	#[test]
+	fn test_persistent_history() {
+		let dir = tempfile::TempDir::new().unwrap();
+		let path = dir.path().join("history.bin");
+
+		{
+			let calc = Calculator::with_history_path(&path).unwrap();
+			for i in 0..(MAX_HISTORY + 5) {
+				calc.evaluate(&format!("{i} + 1"));
+			}
+			calc.flush_history();
+		}
+
+		let calc = Calculator::with_history_path(&path).unwrap();
+		assert_eq!(calc.get_history().len(), MAX_HISTORY);
+		assert_eq!(calc.get_history().front().map(|e| e.query.as_str()), Some("5 + 1"));
+		assert_eq!(calc.get_history().back().map(|e| e.result.as_str()), Some("55"));
+	}
+
+	#[test]
+	fn test_math_functions() {
+		let calc = Calculator::new();
+		assert_eq!(calc.evaluate("sqrt(16)"), Some("4".to_owned()));
+		assert_eq!(calc.evaluate("sin(0)"), Some("0".to_owned()));
+		assert_eq!(calc.evaluate("log10(1000)"), Some("3".to_owned()));
+		assert_eq!(calc.evaluate("pi * 2"), Some("6.283185".to_owned()));
+		assert_eq!(calc.evaluate("sqrt(2)"), Some("1.414214".to_owned()));
+	}
+
+	#[test]
+	fn test_date_math() {
+		let today = NaiveDate::from_ymd_opt(2025, 1, 20).unwrap();
+		assert_eq!(Calculator::eval_date_from("today + 30 days", today), Some("2025-02-19".to_owned()));
+		assert_eq!(Calculator::eval_date_from("today - 1 day", today), Some("2025-01-19".to_owned()));
+		assert_eq!(Calculator::eval_date_from("today + 2 weeks", today), Some("2025-02-03".to_owned()));
+		assert_eq!(Calculator::eval_date_from("Today + 1 month", today), Some("2025-02-20".to_owned()));
+		assert_eq!(Calculator::eval_date_from("days until 2025-12-25", today), Some("339".to_owned()));
+		assert_eq!(Calculator::eval_date_from("days until 2025-01-10", today), Some("-10".to_owned()));
+		assert_eq!(Calculator::eval_date_from("2 + 2", today), None);
+		assert_eq!(Calculator::eval_date_from("today + 3 years", today), None);
+
+		let calc = Calculator::new();
+		assert_eq!(calc.evaluate("2 + 2"), Some("4".to_owned()));
+		assert!(calc.evaluate("today + 0 days").is_some_and(|d| d == Local::now().date_naive().to_string()));
+	}
+
+	#[test]
+	fn test_currency_symbols_and_separators() {
+		let calc = Calculator::new();
+		assert_eq!(calc.evaluate("$100 to EUR"), Some("92.00 EUR".to_owned()));
+		assert_eq!(calc.evaluate("100 EUR to $"), Some("108.70 USD".to_owned()));
+		assert_eq!(calc.evaluate("£100 in €"), Some("116.46 EUR".to_owned()));
+		assert_eq!(calc.evaluate("1,000 JPY in usd"), Some("6.69 USD".to_owned()));
+		assert_eq!(calc.evaluate("100 USD to EUR"), Some("92.00 EUR".to_owned()));
+	}
+
+	#[test]
+	fn test_crypto_conversion() {
+		let calc = Calculator::new();
+		assert_eq!(calc.evaluate("1 BTC to USD"), None);
+
+		calc.update_rates(FxHashMap::from_iter([("BTC".to_owned(), 1.0 / 60000.0), ("eth".to_owned(), 1.0 / 3000.0)]));
+		assert_eq!(calc.evaluate("0.5 BTC to USD"), Some("30000.00 USD".to_owned()));
+		assert_eq!(calc.evaluate("1 BTC to ETH"), Some("20.00000000 ETH".to_owned()));
+		assert_eq!(calc.evaluate("$1500 in eth"), Some("0.50000000 ETH".to_owned()));
+
+		calc.set_currency_format(CurrencyFormat::Symbol);
+		assert_eq!(calc.evaluate("0.5 BTC to USD"), Some("$30000.00".to_owned()));
+		assert_eq!(calc.evaluate("3000 USD to BTC"), Some("0.05000000 BTC".to_owned()));
+	}
+
+	#[test]
+	fn test_concurrent_evaluate() {
+		let calc = std::sync::Arc::new(Calculator::new());
+		let handles: Vec<_> = (0..8)
+			.map(|t| {
+				let calc = std::sync::Arc::clone(&calc);
+				std::thread::spawn(move || {
+					for i in 0..25 {
+						assert_eq!(calc.evaluate(&format!("{t} * 100 + {i}")), Some((t * 100 + i).to_string()));
+						calc.update_rates(FxHashMap::from_iter([(format!("X{t}"), f64::from(i + 1))]));
+						assert!(calc.evaluate("10 usd to eur").is_some());
+					}
+				})
+			})
+			.collect();
+		for handle in handles {
+			handle.join().unwrap();
+		}
+
+		assert_eq!(calc.get_history().len(), MAX_HISTORY);
+		assert_eq!(calc.rate_count(), 18);
+	}
+
+	#[test]
+	fn test_timezone_conversion_with_date() {
+		let today = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+		let convert = |query: &str| Calculator::convert_timezone_from(query, today).map(|(.., result)| result);
+
+		assert_eq!(convert("9am 2025-03-09 EST to PST"), Some(ZonedTime::Exact("06:00 AM 2025-03-09".to_owned())));
+		assert_eq!(convert("9am EST to PST on 2025-03-09"), Some(ZonedTime::Exact("06:00 AM 2025-03-09".to_owned())));
+		assert_eq!(convert("9am EST to PST"), Some(ZonedTime::Exact("06:00 AM".to_owned())));
+		assert_eq!(convert("11pm 2025-01-01 PST to UTC"), Some(ZonedTime::Exact("07:00 AM 2025-01-02".to_owned())));
+	}
+
+	#[test]
+	fn test_timezone_dst_gap() {
+		let today = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+		let result = Calculator::convert_timezone_from("2:30 2025-03-09 EST to UTC", today);
+		assert_eq!(result.map(|(.., result)| result), Some(ZonedTime::Gap));
+
+		let calc = Calculator::new();
+		assert_eq!(calc.evaluate("2:30 2025-03-09 EST to UTC").as_deref(), Some("2:30 does not exist in EST (DST gap)"));
+	}
+
+	#[test]
+	fn test_timezone_dst_ambiguous() {
+		let today = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+		let result = Calculator::convert_timezone_from("1:30 2025-11-02 EST to UTC", today);
+		assert_eq!(
+			result.map(|(.., result)| result),
+			Some(ZonedTime::Ambiguous("05:30 AM 2025-11-02".to_owned(), "06:30 AM 2025-11-02".to_owned()))
+		);
+	}
 }
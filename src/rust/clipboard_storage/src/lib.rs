@@ -1,14 +1,21 @@
+use std::{io, path::Path, sync::{Arc, atomic::{AtomicUsize, Ordering}}};
+
 use bytecheck::CheckBytes;
-use rkyv::{Archive, Deserialize, Serialize};
+use rkyv::{Archive, Deserialize, Serialize, rancor::Error};
 use storage_utils::RkyvStorage;
 
+const SCHEMA_VERSION: u8 = 4;
+
 #[derive(Archive, Deserialize, Serialize, CheckBytes, Debug, Clone, PartialEq, Eq)]
 #[rkyv(derive(Debug))]
+#[derive(serde::Serialize, serde::Deserialize)]
 #[repr(u8)]
 pub enum ClipboardItemType {
 	Text,
 	Image,
 	Unknown,
+	Html,
+	Rtf,
 }
 
 impl ClipboardItemType {
@@ -18,6 +25,8 @@ impl ClipboardItemType {
 			Self::Text => 0,
 			Self::Image => 1,
 			Self::Unknown => 2,
+			Self::Html => 3,
+			Self::Rtf => 4,
 		}
 	}
 
@@ -26,6 +35,8 @@ impl ClipboardItemType {
 		match value {
 			0 => Self::Text,
 			1 => Self::Image,
+			3 => Self::Html,
+			4 => Self::Rtf,
 			_ => Self::Unknown,
 		}
 	}
@@ -33,6 +44,7 @@ impl ClipboardItemType {
 
 #[derive(Archive, Deserialize, Serialize, CheckBytes, Debug, Clone, Copy, PartialEq)]
 #[rkyv(derive(Debug))]
+#[derive(serde::Serialize, serde::Deserialize)]
 pub struct ImageSize {
 	pub width:  f64,
 	pub height: f64,
@@ -40,6 +52,7 @@ pub struct ImageSize {
 
 #[derive(Archive, Deserialize, Serialize, CheckBytes, Debug, Clone, PartialEq)]
 #[rkyv(derive(Debug))]
+#[derive(serde::Serialize, serde::Deserialize)]
 pub struct ClipboardEntry {
 	pub content:         String,
 	pub timestamp:       f64,
@@ -48,6 +61,13 @@ pub struct ClipboardEntry {
 	pub image_size:      Option<ImageSize>,
 	pub size:            i32,
 	pub source_app:      Option<String>,
+	#[serde(default)]
+	pub pinned:          bool,
+	#[serde(default)]
+	pub markup:          Option<String>,
+	/// Set when the text was cut to the size limit on insert.
+	#[serde(default)]
+	pub truncated:       bool,
 }
 
 impl ClipboardEntry {
@@ -61,6 +81,34 @@ impl ClipboardEntry {
 			image_size: None,
 			size,
 			source_app,
+			pinned: false,
+			markup: None,
+			truncated: false,
+		}
+	}
+
+	/// A rich copy: `plain` is the text fallback used for search and dedup,
+	/// `markup` the HTML or RTF source of the given `kind`.
+	#[must_use]
+	pub const fn new_rich(
+		plain: String,
+		markup: String,
+		kind: ClipboardItemType,
+		timestamp: f64,
+		size: i32,
+		source_app: Option<String>,
+	) -> Self {
+		Self {
+			content: plain,
+			timestamp,
+			item_type: kind,
+			image_file_path: None,
+			image_size: None,
+			size,
+			source_app,
+			pinned: false,
+			markup: Some(markup),
+			truncated: false,
 		}
 	}
 
@@ -82,11 +130,241 @@ impl ClipboardEntry {
 			image_size: Some(ImageSize { width, height }),
 			size,
 			source_app,
+			pinned: false,
+			markup: None,
+			truncated: false,
+		}
+	}
+
+	/// Whether the text was cut down on insert.
+	#[must_use]
+	pub const fn is_truncated(&self) -> bool { self.truncated }
+}
+
+// Layout before `pinned` was added, kept to read schema 1 and unversioned
+// files.
+#[derive(Archive, Deserialize, Serialize, CheckBytes)]
+struct ClipboardEntryV1 {
+	content:         String,
+	timestamp:       f64,
+	item_type:       ClipboardItemType,
+	image_file_path: Option<String>,
+	image_size:      Option<ImageSize>,
+	size:            i32,
+	source_app:      Option<String>,
+}
+
+// Schema 2 layout, before `markup` was added.
+#[derive(Archive, Deserialize, Serialize, CheckBytes)]
+struct ClipboardEntryV2 {
+	content:         String,
+	timestamp:       f64,
+	item_type:       ClipboardItemType,
+	image_file_path: Option<String>,
+	image_size:      Option<ImageSize>,
+	size:            i32,
+	source_app:      Option<String>,
+	pinned:          bool,
+}
+
+impl From<ClipboardEntryV1> for ClipboardEntryV2 {
+	fn from(e: ClipboardEntryV1) -> Self {
+		Self {
+			content:         e.content,
+			timestamp:       e.timestamp,
+			item_type:       e.item_type,
+			image_file_path: e.image_file_path,
+			image_size:      e.image_size,
+			size:            e.size,
+			source_app:      e.source_app,
+			pinned:          false,
+		}
+	}
+}
+
+// Schema 3 layout, before `truncated` was added.
+#[derive(Archive, Deserialize, Serialize, CheckBytes)]
+struct ClipboardEntryV3 {
+	content:         String,
+	timestamp:       f64,
+	item_type:       ClipboardItemType,
+	image_file_path: Option<String>,
+	image_size:      Option<ImageSize>,
+	size:            i32,
+	source_app:      Option<String>,
+	pinned:          bool,
+	markup:          Option<String>,
+}
+
+impl From<ClipboardEntryV2> for ClipboardEntryV3 {
+	fn from(e: ClipboardEntryV2) -> Self {
+		Self {
+			content:         e.content,
+			timestamp:       e.timestamp,
+			item_type:       e.item_type,
+			image_file_path: e.image_file_path,
+			image_size:      e.image_size,
+			size:            e.size,
+			source_app:      e.source_app,
+			pinned:          e.pinned,
+			markup:          None,
+		}
+	}
+}
+
+// Older schemas did not record truncation, so their entries read as whole.
+impl From<ClipboardEntryV3> for ClipboardEntry {
+	fn from(e: ClipboardEntryV3) -> Self {
+		Self {
+			content:         e.content,
+			timestamp:       e.timestamp,
+			item_type:       e.item_type,
+			image_file_path: e.image_file_path,
+			image_size:      e.image_size,
+			size:            e.size,
+			source_app:      e.source_app,
+			pinned:          e.pinned,
+			markup:          e.markup,
+			truncated:       false,
 		}
 	}
 }
 
-pub type ClipboardStorage = RkyvStorage<ClipboardEntry>;
+fn migrate(version: u8, bytes: &[u8]) -> Option<Vec<ClipboardEntry>> {
+	let old: Vec<ClipboardEntryV3> = match version {
+		0 | 1 => rkyv::from_bytes::<Vec<ClipboardEntryV1>, Error>(bytes)
+			.ok()?
+			.into_iter()
+			.map(|e| ClipboardEntryV2::from(e).into())
+			.collect(),
+		2 => rkyv::from_bytes::<Vec<ClipboardEntryV2>, Error>(bytes).ok()?.into_iter().map(Into::into).collect(),
+		3 => rkyv::from_bytes::<Vec<ClipboardEntryV3>, Error>(bytes).ok()?,
+		_ => return None,
+	};
+	Some(old.into_iter().map(Into::into).collect())
+}
+
+pub struct ClipboardStorage {
+	storage:         RkyvStorage<ClipboardEntry>,
+	max_text_bytes:  AtomicUsize,
+	max_image_bytes: AtomicUsize,
+}
+
+impl ClipboardStorage {
+	pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
+		Ok(Self::from_storage(RkyvStorage::new_versioned(path, SCHEMA_VERSION, migrate)?))
+	}
+
+	pub fn new_encrypted(path: impl AsRef<Path>, key: [u8; 32]) -> io::Result<Self> {
+		Ok(Self::from_storage(RkyvStorage::new_encrypted_versioned(path, key, SCHEMA_VERSION, migrate)?))
+	}
+
+	const fn from_storage(storage: RkyvStorage<ClipboardEntry>) -> Self {
+		Self { storage, max_text_bytes: AtomicUsize::new(0), max_image_bytes: AtomicUsize::new(0) }
+	}
+
+	/// Caps the text kept per entry; longer text is cut at a char boundary.
+	/// Zero means no limit.
+	pub fn set_max_text_bytes(&self, max: usize) { self.max_text_bytes.store(max, Ordering::Relaxed); }
+
+	/// Images whose `size` exceeds `max` are not stored. Zero means no limit.
+	pub fn set_max_image_bytes(&self, max: usize) { self.max_image_bytes.store(max, Ordering::Relaxed); }
+
+	/// Inserts `entry` at the front after applying the size limits, returning
+	/// false if it was rejected.
+	pub fn insert_at_front_async(&self, entry: ClipboardEntry) -> bool {
+		self.admit(entry).map(|entry| self.storage.insert_at_front_async(entry)).is_some()
+	}
+
+	pub fn insert_at_front(&self, entry: ClipboardEntry) -> io::Result<bool> {
+		self.admit(entry).map(|entry| self.storage.insert_at_front(entry)).transpose().map(|stored| stored.is_some())
+	}
+
+	// Cut text is flagged and keeps its original `size`.
+	// Markup cannot be cut safely and is dropped in favour of the plain text.
+	fn admit(&self, mut entry: ClipboardEntry) -> Option<ClipboardEntry> {
+		if entry.item_type == ClipboardItemType::Image {
+			let max = self.max_image_bytes.load(Ordering::Relaxed);
+			return (max == 0 || usize::try_from(entry.size).is_ok_and(|size| size <= max)).then_some(entry);
+		}
+
+		let max = self.max_text_bytes.load(Ordering::Relaxed);
+		if max == 0 {
+			return Some(entry);
+		}
+		if entry.content.len() > max {
+			entry.size = entry.size.max(i32::try_from(entry.content.len()).unwrap_or(i32::MAX));
+			let end = entry.content.floor_char_boundary(max);
+			entry.content.truncate(end);
+			entry.truncated = true;
+		}
+		if entry.markup.as_ref().is_some_and(|markup| markup.len() > max) {
+			entry.markup = None;
+		}
+		Some(entry)
+	}
+
+	/// Trims to `max` unpinned entries; pinned entries never age out.
+	pub fn trim_to(&self, max: usize) -> io::Result<Vec<ClipboardEntry>> {
+		self.storage.trim_to_matching(max, |entry| !entry.pinned)
+	}
+
+	/// Removes unpinned entries older than `cutoff_timestamp`, returning how
+	/// many went.
+	pub fn prune_older_than(&self, cutoff_timestamp: f64) -> usize {
+		let before = self.storage.len();
+		self.storage.update_async(move |entries| {
+			let len = entries.len();
+			entries.retain(|entry| entry.pinned || entry.timestamp >= cutoff_timestamp);
+			entries.len() < len
+		});
+		before.saturating_sub(self.storage.len())
+	}
+
+	pub fn set_pinned(&self, index: usize, pinned: bool) -> io::Result<bool> {
+		self.storage.update(|entries| entries.get_mut(index).map(|entry| entry.pinned = pinned).is_some())
+	}
+
+	/// Replaces every entry after applying the size limits; rejected entries
+	/// are dropped.
+	pub fn replace_all(&self, entries: Vec<ClipboardEntry>) -> io::Result<()> {
+		self.storage.replace_all(entries.into_iter().filter_map(|entry| self.admit(entry)).collect())
+	}
+
+	pub fn move_to_front(&self, index: usize) -> bool { self.storage.move_to_front(index) }
+
+	pub fn remove_at(&self, index: usize) -> bool {
+		self.storage.update_async(move |entries| {
+			if index < entries.len() {
+				entries.remove(index);
+				true
+			} else {
+				false
+			}
+		})
+	}
+
+	pub fn clear(&self) -> io::Result<()> { self.storage.clear() }
+
+	#[must_use]
+	pub fn get_all(&self) -> Arc<Vec<ClipboardEntry>> { self.storage.get_all() }
+
+	#[must_use]
+	pub fn get_range(&self, start: usize, count: usize) -> Vec<ClipboardEntry> { self.storage.get_range(start, count) }
+
+	#[must_use]
+	pub fn find_index(&self, predicate: impl Fn(&ClipboardEntry) -> bool) -> Option<usize> {
+		self.storage.find_index(predicate)
+	}
+
+	#[must_use]
+	pub fn len(&self) -> usize { self.storage.len() }
+
+	#[must_use]
+	pub fn is_empty(&self) -> bool { self.storage.is_empty() }
+
+	pub fn flush(&self) -> bool { self.storage.flush() }
+}
 
 #[cfg(test)]
 #[allow(clippy::indexing_slicing)]
@@ -153,4 +431,168 @@ mod tests {
 		assert_eq!(removed.len(), 5);
 		assert_eq!(storage.len(), 5);
 	}
+
+	#[test]
+	fn test_move_to_front() {
+		let temp = NamedTempFile::new().unwrap();
+		let storage = ClipboardStorage::new(temp.path()).unwrap();
+
+		for i in 0..4 {
+			storage.insert_at_front_async(ClipboardEntry::new_text(format!("Entry {i}"), f64::from(i), 7, None));
+		}
+
+		assert!(storage.move_to_front(2));
+		let contents: Vec<_> = storage.get_all().iter().map(|e| e.content.clone()).collect();
+		assert_eq!(contents, ["Entry 1", "Entry 3", "Entry 2", "Entry 0"]);
+
+		assert!(!storage.move_to_front(4));
+		assert_eq!(storage.len(), 4);
+	}
+
+	#[test]
+	fn test_trim_keeps_pinned() {
+		let temp = NamedTempFile::new().unwrap();
+		let storage = ClipboardStorage::new(temp.path()).unwrap();
+
+		for i in 0..6 {
+			storage.insert_at_front_async(ClipboardEntry::new_text(format!("Entry {i}"), f64::from(i), 7, None));
+		}
+		assert!(storage.set_pinned(5, true).unwrap());
+		assert!(!storage.set_pinned(6, true).unwrap());
+
+		let removed = storage.trim_to(2).unwrap();
+		assert_eq!(removed.len(), 3);
+		let contents: Vec<_> = storage.get_all().iter().map(|e| e.content.clone()).collect();
+		assert_eq!(contents, ["Entry 5", "Entry 4", "Entry 0"]);
+		assert!(storage.get_all()[2].pinned);
+	}
+
+	#[test]
+	fn test_schema_1_file_migrates() {
+		let temp = NamedTempFile::new().unwrap();
+		let old = ClipboardEntryV1 {
+			content:         "old".to_owned(),
+			timestamp:       1.0,
+			item_type:       ClipboardItemType::Text,
+			image_file_path: None,
+			image_size:      None,
+			size:            3,
+			source_app:      None,
+		};
+		storage_utils::save_to_disk_versioned(temp.path(), &vec![old], 1).unwrap();
+
+		let storage = ClipboardStorage::new(temp.path()).unwrap();
+		assert_eq!(storage.get_all()[0], ClipboardEntry::new_text("old".to_owned(), 1.0, 3, None));
+	}
+
+	#[test]
+	fn test_schema_3_file_migrates() {
+		let temp = NamedTempFile::new().unwrap();
+		let old = ClipboardEntryV3 {
+			content:         "<b>hi</b>".to_owned(),
+			timestamp:       1.0,
+			item_type:       ClipboardItemType::Html,
+			image_file_path: None,
+			image_size:      None,
+			size:            64,
+			source_app:      None,
+			pinned:          true,
+			markup:          Some("<b>hi</b>".to_owned()),
+		};
+		storage_utils::save_to_disk_versioned(temp.path(), &vec![old], 3).unwrap();
+
+		let entry = &ClipboardStorage::new(temp.path()).unwrap().get_all()[0];
+		assert!(entry.pinned);
+		assert_eq!(entry.markup.as_deref(), Some("<b>hi</b>"));
+		assert!(!entry.is_truncated());
+	}
+
+	#[test]
+	fn test_prune_older_than() {
+		let temp = NamedTempFile::new().unwrap();
+		let storage = ClipboardStorage::new(temp.path()).unwrap();
+
+		for timestamp in [100.0, 250.0, 50.0, 400.0, 10.0] {
+			storage.insert_at_front_async(ClipboardEntry::new_text(format!("at {timestamp}"), timestamp, 6, None));
+		}
+		assert!(storage.set_pinned(4, true).unwrap());
+
+		assert_eq!(storage.prune_older_than(200.0), 2);
+		let contents: Vec<_> = storage.get_all().iter().map(|e| e.content.clone()).collect();
+		assert_eq!(contents, ["at 400", "at 250", "at 100"]);
+		assert_eq!(storage.prune_older_than(200.0), 0);
+	}
+
+	#[test]
+	fn test_rich_entry_round_trip() {
+		let temp = NamedTempFile::new().unwrap();
+		let path = temp.path().to_path_buf();
+		let entry = ClipboardEntry::new_rich(
+			"Bold move".to_owned(),
+			"<b>Bold</b> move".to_owned(),
+			ClipboardItemType::Html,
+			5.0,
+			9,
+			Some("Browser".to_owned()),
+		);
+
+		{
+			let storage = ClipboardStorage::new(&path).unwrap();
+			storage.insert_at_front_async(entry.clone());
+			storage.flush();
+		}
+
+		let storage = ClipboardStorage::new(&path).unwrap();
+		let loaded = &storage.get_all()[0];
+		assert_eq!(*loaded, entry);
+		assert_eq!(loaded.content, "Bold move");
+		assert_eq!(loaded.markup.as_deref(), Some("<b>Bold</b> move"));
+		assert_eq!(ClipboardItemType::from_u8(loaded.item_type.clone().as_u8()), ClipboardItemType::Html);
+		assert_eq!(ClipboardItemType::from_u8(4), ClipboardItemType::Rtf);
+	}
+
+	#[test]
+	fn test_max_text_bytes() {
+		let temp = NamedTempFile::new().unwrap();
+		let storage = ClipboardStorage::new(temp.path()).unwrap();
+		storage.set_max_text_bytes(8);
+
+		assert!(storage.insert_at_front_async(ClipboardEntry::new_text("short".to_owned(), 1.0, 5, None)));
+		assert_eq!(storage.get_all()[0].content, "short");
+		assert!(!storage.get_all()[0].is_truncated());
+
+		assert!(storage.insert_at_front_async(ClipboardEntry::new_text("utf16".to_owned(), 1.5, 10, None)));
+		assert!(!storage.get_all()[0].is_truncated());
+
+		assert!(storage.insert_at_front_async(ClipboardEntry::new_text("naïve café au lait".to_owned(), 2.0, 0, None)));
+		let entry = &storage.get_all()[0];
+		assert_eq!(entry.content, "naïve c");
+		assert_eq!(entry.size, 20);
+		assert!(entry.is_truncated());
+	}
+
+	#[test]
+	fn test_max_image_bytes() {
+		let temp = NamedTempFile::new().unwrap();
+		let storage = ClipboardStorage::new(temp.path()).unwrap();
+		storage.set_max_image_bytes(1_000);
+
+		let image = |size| ClipboardEntry::new_image(String::new(), 1.0, "/tmp/a.png".to_owned(), 4.0, 4.0, size, None);
+		assert!(storage.insert_at_front_async(image(900)));
+		assert!(!storage.insert_at_front_async(image(5_000)));
+		assert_eq!(storage.len(), 1);
+	}
+
+	#[test]
+	fn test_replace_all_applies_limits() {
+		let temp = NamedTempFile::new().unwrap();
+		let storage = ClipboardStorage::new(temp.path()).unwrap();
+		storage.set_max_text_bytes(4);
+		storage.set_max_image_bytes(1_000);
+
+		let image = ClipboardEntry::new_image(String::new(), 1.0, "/tmp/a.png".to_owned(), 4.0, 4.0, 5_000, None);
+		storage.replace_all(vec![ClipboardEntry::new_text("clipboard".to_owned(), 2.0, 0, None), image]).unwrap();
+		assert_eq!(storage.len(), 1);
+		assert_eq!(storage.get_all()[0].content, "clip");
+	}
 }
@@ -1,17 +1,18 @@
 #![allow(clippy::missing_safety_doc, clippy::missing_panics_doc)]
-use std::{ffi::{CStr, CString}, ptr, sync::Arc};
+use std::{cell::RefCell, ffi::{CStr, CString, c_void}, fmt, path::Path, ptr, sync::{Arc, atomic::{AtomicU64, Ordering}}};
 
-use action_manager::{ActionManager, action::{Action, PatternActionType, ResultAction}};
+use action_manager::{ActionManager, action::{Action, ActionResult, PatternActionType, ResultAction}};
 use app_storage::{AppEntry, AppStorage};
-use calculator::Calculator;
+use calculator::{Calculator, CurrencyFormat};
 use clipboard_storage::{ClipboardEntry, ClipboardItemType, ClipboardStorage};
 use compact_str::CompactString;
 use file_indexer::{FileIndexer, FileIndexerConfig};
 use libc::{c_char, size_t};
-use parking_lot::Mutex;
-use search_engine::{SearchEngine, indexer::{IndexedItem, ItemType}};
+use parking_lot::RwLock;
+use rustc_hash::FxHashMap;
+use search_engine::{SearchEngine, fuzzy_matcher::ScoringWeights, indexer::{IndexedItem, ItemType}};
 use settings_storage::{AppSettings, SettingsStorage};
-use snippet_matcher::{Snippet, SnippetMatcher};
+use snippet_matcher::{ExpandContext, MatchPolicy, Snippet, SnippetDTO, SnippetMatcher};
 use snippet_storage::SnippetStorage;
 use sonic_rs::{JsonContainerTrait, JsonValueTrait};
 
@@ -43,6 +44,7 @@ macro_rules! cstr_owned {
 macro_rules! require_handle {
 	($handle:expr, $($ptr:expr),+) => {
 		if $handle.is_null() $(|| $ptr.is_null())+ {
+			set_last_error(NULL_ARGUMENT);
 			return false;
 		}
 	};
@@ -51,6 +53,7 @@ macro_rules! require_handle {
 macro_rules! require_handle_ptr {
 	($handle:expr, $($ptr:expr),+) => {
 		if $handle.is_null() $(|| $ptr.is_null())+ {
+			set_last_error(NULL_ARGUMENT);
 			return ptr::null_mut();
 		}
 	};
@@ -59,6 +62,7 @@ macro_rules! require_handle_ptr {
 macro_rules! require_handle_ret {
 	($ret_val:expr, $handle:expr, $($ptr:expr),+) => {
 		if $handle.is_null() $(|| $ptr.is_null())+ {
+			set_last_error(NULL_ARGUMENT);
 			return $ret_val;
 		}
 	};
@@ -67,6 +71,7 @@ macro_rules! require_handle_ret {
 macro_rules! with_handle {
 	($handle:expr, $body:expr) => {{
 		if $handle.is_null() {
+			set_last_error(NULL_ARGUMENT);
 			return false;
 		}
 		unsafe {
@@ -76,6 +81,7 @@ macro_rules! with_handle {
 	}};
 	($handle:expr,ret = $default:expr, $body:expr) => {{
 		if $handle.is_null() {
+			set_last_error(NULL_ARGUMENT);
 			return $default;
 		}
 		unsafe {
@@ -85,6 +91,17 @@ macro_rules! with_handle {
 	}};
 }
 
+const NULL_ARGUMENT: &str = "Null pointer argument";
+
+thread_local! {
+	static LAST_ERROR: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: impl fmt::Display) { LAST_ERROR.with(|e| *e.borrow_mut() = Some(message.to_string())); }
+
+/// Like `Result::ok`, but keeps the error message for `summon_last_error`.
+fn ok_or_record<T, E: fmt::Display>(result: Result<T, E>) -> Option<T> { result.map_err(set_last_error).ok() }
+
 #[inline]
 fn to_cstring_ptr(s: impl Into<Vec<u8>>) -> *mut c_char {
 	CString::new(s).ok().map_or(ptr::null_mut(), CString::into_raw)
@@ -149,24 +166,34 @@ macro_rules! storage_handle {
 	};
 }
 
+// The `private` form frees the string fields for an exported free function
+// that releases the rest of the struct itself, without exporting a symbol.
 macro_rules! array_free {
 	($fn_name:ident, $type:ty, $($field:ident),+) => {
 		#[unsafe(no_mangle)]
 		pub unsafe extern "C" fn $fn_name(items: *mut $type, count: size_t) {
-			if items.is_null() || count == 0 {
-				return;
-			}
-			unsafe {
-				for i in 0..count {
-					let item = &(*items.add(i));
-					$(
-						if !item.$field.is_null() {
-							drop(CString::from_raw(item.$field));
-						}
-					)+
-				}
-				drop(Vec::from_raw_parts(items, count, count));
+			array_free!(@body items, count, $($field),+);
+		}
+	};
+	(private $fn_name:ident, $type:ty, $($field:ident),+) => {
+		unsafe fn $fn_name(items: *mut $type, count: size_t) {
+			array_free!(@body items, count, $($field),+);
+		}
+	};
+	(@body $items:ident, $count:ident, $($field:ident),+) => {
+		if $items.is_null() || $count == 0 {
+			return;
+		}
+		unsafe {
+			for i in 0..$count {
+				let item = &(*$items.add(i));
+				$(
+					if !item.$field.is_null() {
+						drop(CString::from_raw(item.$field));
+					}
+				)+
 			}
+			drop(Vec::from_raw_parts($items, $count, $count));
 		}
 	};
 }
@@ -195,7 +222,7 @@ macro_rules! storage_clear {
 	($fn_name:ident, $handle_type:ty) => {
 		#[unsafe(no_mangle)]
 		pub unsafe extern "C" fn $fn_name(handle: *mut $handle_type) -> bool {
-			with_handle!(handle, |h: &$handle_type| h.inner.clear().is_ok())
+			with_handle!(handle, |h: &$handle_type| ok_or_record(h.inner.clear()).is_some())
 		}
 	};
 }
@@ -273,12 +300,18 @@ macro_rules! snippet_op {
 			category: *const c_char,
 		) -> bool {
 			with_handle!(handle, |h: &SnippetStorageHandle| {
+				let id = cstr_owned!(id);
+				// The C signature has no regex or case flags, so keep the stored ones.
+				let stored = h.inner.get_all().iter().find(|s| s.id == id).map(|s| (s.is_regex, s.match_case));
+				let (is_regex, match_case) = stored.unwrap_or_default();
 				h.inner.$method(snippet_storage::Snippet {
-					id: cstr_owned!(id),
+					id,
 					trigger: cstr_owned!(trigger),
 					content: cstr_owned!(content),
 					enabled,
 					category: cstr_owned!(category),
+					is_regex,
+					match_case,
 				})
 			})
 		}
@@ -289,7 +322,7 @@ macro_rules! storage_method {
 	($fn_name:ident, $handle_type:ty, $method:ident, $param_type:ty) => {
 		#[unsafe(no_mangle)]
 		pub unsafe extern "C" fn $fn_name(handle: *mut $handle_type, param: $param_type) -> bool {
-			with_handle!(handle, |h: &$handle_type| h.inner.$method(param).is_ok())
+			with_handle!(handle, |h: &$handle_type| ok_or_record(h.inner.$method(param)).is_some())
 		}
 	};
 }
@@ -309,9 +342,8 @@ macro_rules! manager_json_method {
 		#[unsafe(no_mangle)]
 		pub unsafe extern "C" fn $fn_name(handle: *mut ActionManagerHandle, json: *const c_char) -> bool {
 			require_handle!(handle, json);
-			let action: Action = match sonic_rs::from_str(cstr!(json)) {
-				Ok(a) => a,
-				Err(_) => return false,
+			let Some(action) = ok_or_record(sonic_rs::from_str::<Action>(cstr!(json))) else {
+				return false;
 			};
 			unsafe { (*handle).manager.$method(action).$result() }
 		}
@@ -334,28 +366,70 @@ macro_rules! new_from_path {
 }
 
 pub struct SearchEngineHandle {
-	engine:       Arc<Mutex<SearchEngine>>,
+	engine:       Arc<RwLock<SearchEngine>>,
 	last_results: parking_lot::Mutex<Option<Vec<search_engine::SearchResult>>>,
+	generation:   Arc<AtomicU64>,
 }
 
+pub type SearchResultsCallback = extern "C" fn(*mut CSearchResult, size_t, *mut c_void);
+
+struct UserData(*mut c_void);
+
+unsafe impl Send for UserData {}
+
 #[repr(C)]
 pub struct CSearchResult {
-	pub id:    *mut c_char,
-	pub name:  *mut c_char,
-	pub path:  *mut c_char,
-	pub score: i64,
+	pub id:            *mut c_char,
+	pub name:          *mut c_char,
+	pub path:          *mut c_char,
+	pub score:         i64,
+	/// UTF-8 byte offsets into `name`, one per matched character. Callers must
+	/// map them to their own string indexing (e.g. UTF-16 for `NSString`) before
+	/// highlighting.
+	pub match_indices: *mut usize,
+	pub match_count:   size_t,
+}
+
+fn match_byte_offsets(name: &str, char_indices: &[usize]) -> Vec<usize> {
+	if name.is_ascii() {
+		return char_indices.to_vec();
+	}
+	let offsets: Vec<usize> = name.char_indices().map(|(offset, _)| offset).collect();
+	char_indices.iter().filter_map(|&idx| offsets.get(idx).copied()).collect()
+}
+
+fn to_c_search_result(r: &search_engine::SearchResult) -> CSearchResult {
+	let indices = match_byte_offsets(&r.item.name, &r.match_indices);
+	CSearchResult {
+		id:            to_cstring_ptr(r.item.id.as_str()),
+		name:          to_cstring_ptr(r.item.name.as_str()),
+		path:          opt_to_cstring_ptr(r.item.path.as_ref().map(|p| p.as_str())),
+		score:         r.score,
+		match_count:   indices.len(),
+		match_indices: vec_to_c_array(indices),
+	}
 }
 
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn search_engine_new() -> *mut SearchEngineHandle {
 	Box::into_raw(Box::new(SearchEngineHandle {
-		engine:       Arc::new(Mutex::new(SearchEngine::new())),
+		engine:       Arc::new(RwLock::new(SearchEngine::new())),
 		last_results: parking_lot::Mutex::new(None),
+		generation:   Arc::new(AtomicU64::new(0)),
 	}))
 }
 
 handle_free!(search_engine_free, SearchEngineHandle);
 
+fn item_type_from_u8(item_type: u8) -> Option<ItemType> {
+	match item_type {
+		0 => Some(ItemType::Application),
+		1 => Some(ItemType::File),
+		4 => Some(ItemType::Custom("Command".to_owned())),
+		_ => None,
+	}
+}
+
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn search_engine_add_item(
 	handle: *mut SearchEngineHandle,
@@ -363,28 +437,118 @@ pub unsafe extern "C" fn search_engine_add_item(
 	name: *const c_char,
 	path: *const c_char,
 	item_type: u8,
+) -> bool {
+	unsafe { search_engine_add_item_with_aliases(handle, id, name, path, item_type, ptr::null()) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn search_engine_add_item_with_aliases(
+	handle: *mut SearchEngineHandle,
+	id: *const c_char,
+	name: *const c_char,
+	path: *const c_char,
+	item_type: u8,
+	aliases_json: *const c_char,
 ) -> bool {
 	with_handle!(handle, |h: &SearchEngineHandle| {
-		let item_type = match item_type {
-			0 => ItemType::Application,
-			1 => ItemType::File,
-			4 => ItemType::Custom("Command".to_owned()),
-			_ => return false,
+		let Some(item_type) = item_type_from_u8(item_type) else {
+			return false;
 		};
+		let aliases: Vec<CompactString> =
+			if aliases_json.is_null() { Vec::new() } else { sonic_rs::from_str(cstr!(aliases_json)).unwrap_or_default() };
 		let item = IndexedItem {
 			id: CompactString::new(cstr!(id)),
 			name: CompactString::new(cstr!(name)),
 			item_type,
 			path: Some(CompactString::new(cstr!(path))),
 			metadata: None,
+			aliases,
 		};
-		let engine = h.engine.lock();
+		let engine = h.engine.read();
 		engine.indexer().write().add_item(item);
 		engine.clear_cache();
 		true
 	})
 }
 
+fn parse_indexed_items(json: &str) -> Option<Vec<IndexedItem>> {
+	let entries: Vec<sonic_rs::Value> = sonic_rs::from_str(json).ok()?;
+	let items = entries
+		.iter()
+		.filter_map(|entry| {
+			let item_type = match entry.get("item_type") {
+				Some(value) => item_type_from_u8(u8::try_from(value.as_u64()?).ok()?)?,
+				None => ItemType::Application,
+			};
+			Some(IndexedItem {
+				id: CompactString::new(entry.get("id")?.as_str()?),
+				name: CompactString::new(entry.get("name")?.as_str()?),
+				item_type,
+				path: entry.get("path").and_then(|p| p.as_str()).map(CompactString::new),
+				metadata: None,
+				aliases: Vec::new(),
+			})
+		})
+		.collect();
+	Some(items)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn search_engine_add_items_json(
+	handle: *mut SearchEngineHandle,
+	json: *const c_char,
+	count_out: *mut size_t,
+) -> bool {
+	require_handle_ret!(false, handle, json);
+
+	let Some(items) = parse_indexed_items(cstr!(json)) else {
+		return false;
+	};
+	if !count_out.is_null() {
+		unsafe { *count_out = items.len() };
+	}
+	with_handle!(handle, |h: &SearchEngineHandle| {
+		let engine = h.engine.read();
+		engine.indexer().write().add_items(items);
+		engine.clear_cache();
+		true
+	})
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn search_engine_remove_item(handle: *mut SearchEngineHandle, id: *const c_char) -> bool {
+	require_handle_ret!(false, handle, id);
+	with_handle!(handle, |h: &SearchEngineHandle| {
+		let engine = h.engine.read();
+		let removed = engine.indexer().write().remove_item(cstr!(id)).is_some();
+		if removed {
+			engine.clear_cache();
+		}
+		removed
+	})
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn search_engine_remove_items_json(
+	handle: *mut SearchEngineHandle,
+	json_ids: *const c_char,
+) -> size_t {
+	require_handle_ret!(0, handle, json_ids);
+
+	let Some(ids) = ok_or_record(sonic_rs::from_str::<Vec<String>>(cstr!(json_ids))) else {
+		return 0;
+	};
+	let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+	with_handle!(handle, ret = 0, |h: &SearchEngineHandle| {
+		let engine = h.engine.read();
+		let removed = engine.indexer().write().remove_items(&ids);
+		if removed > 0 {
+			engine.clear_cache();
+		}
+		removed
+	})
+}
+
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn search_engine_search(
 	handle: *mut SearchEngineHandle,
@@ -394,31 +558,136 @@ pub unsafe extern "C" fn search_engine_search(
 ) -> *mut CSearchResult {
 	require_handle_ptr!(handle, query, out_count);
 
-	let results = {
-		let engine = unsafe { (*handle).engine.lock() };
-		if let Ok(results) = engine.search(cstr!(query), limit) {
-			results
-		} else {
-			unsafe { *out_count = 0 };
-			return ptr::null_mut();
+	let results = unsafe { (*handle).engine.read().search(cstr!(query), limit) };
+	unsafe { publish_search_results(handle, results.ok(), out_count) }
+}
+
+/// Runs the search on the rayon pool and returns a token identifying it.
+/// `callback` fires exactly once on a background thread; if a newer async
+/// search was started meanwhile the results are dropped and it receives a null
+/// array with a zero count. Non-null arrays are owned by the caller and must be
+/// released with `search_results_free`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn search_engine_search_async(
+	handle: *mut SearchEngineHandle,
+	query: *const c_char,
+	limit: size_t,
+	callback: SearchResultsCallback,
+	user_data: *mut c_void,
+) -> u64 {
+	require_handle_ret!(0, handle, query);
+
+	let query = cstr!(query).to_owned();
+	let user_data = UserData(user_data);
+	let (engine, generation) = unsafe { (Arc::clone(&(*handle).engine), Arc::clone(&(*handle).generation)) };
+	let token = generation.fetch_add(1, Ordering::SeqCst) + 1;
+
+	rayon::spawn(move || {
+		let user_data = user_data;
+		// Searches share a read lock, so only a settings change delays this
+		// one; a newer query may have arrived meanwhile, so check again once
+		// the lock is held and skip the scan if this one is stale.
+		let results = {
+			let engine = engine.read();
+			if generation.load(Ordering::SeqCst) == token {
+				engine.search(&query, limit).unwrap_or_default()
+			} else {
+				Vec::new()
+			}
+		};
+
+		if results.is_empty() || generation.load(Ordering::SeqCst) != token {
+			callback(ptr::null_mut(), 0, user_data.0);
+			return;
 		}
+		let c_results: Vec<CSearchResult> = results.iter().map(to_c_search_result).collect();
+		let count = c_results.len();
+		callback(vec_to_c_array(c_results), count, user_data.0);
+	});
+
+	token
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn search_engine_warm_json(
+	handle: *mut SearchEngineHandle,
+	queries_json: *const c_char,
+	limit: size_t,
+) -> bool {
+	require_handle_ret!(false, handle, queries_json);
+
+	let Some(queries) = ok_or_record(sonic_rs::from_str::<Vec<String>>(cstr!(queries_json))) else {
+		return false;
 	};
+	let queries: Vec<&str> = queries.iter().map(String::as_str).collect();
+	with_handle!(handle, |h: &SearchEngineHandle| {
+		h.engine.read().warm(&queries, limit);
+		true
+	})
+}
 
-	if results.is_empty() {
-		unsafe { *out_count = 0 };
-		return ptr::null_mut();
-	}
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn search_engine_search_counted(
+	handle: *mut SearchEngineHandle,
+	query: *const c_char,
+	limit: size_t,
+	out_count: *mut size_t,
+	out_total: *mut size_t,
+) -> *mut CSearchResult {
+	require_handle_ptr!(handle, query, out_count, out_total);
+
+	let outcome = unsafe { (*handle).engine.read().search_counted(cstr!(query), limit) };
+	let (results, total) = outcome.map_or((None, 0), |(results, total)| (Some(results), total));
+	unsafe { *out_total = total };
+	unsafe { publish_search_results(handle, results, out_count) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn search_engine_search_page(
+	handle: *mut SearchEngineHandle,
+	query: *const c_char,
+	offset: size_t,
+	limit: size_t,
+	out_count: *mut size_t,
+) -> *mut CSearchResult {
+	require_handle_ptr!(handle, query, out_count);
+
+	let results = unsafe { (*handle).engine.read().search_page(cstr!(query), offset, limit) };
+	unsafe { publish_search_results(handle, results.ok(), out_count) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn search_engine_search_with_deadline_ms(
+	handle: *mut SearchEngineHandle,
+	query: *const c_char,
+	limit: size_t,
+	deadline_ms: u64,
+	out_count: *mut size_t,
+	out_truncated: *mut bool,
+) -> *mut CSearchResult {
+	require_handle_ptr!(handle, query, out_count);
 
-	let mut c_results = Vec::with_capacity(results.len());
+	let budget = std::time::Duration::from_millis(deadline_ms);
+	let outcome = unsafe { (*handle).engine.read().search_with_deadline(cstr!(query), limit, Some(budget)) };
+	let (results, truncated) = outcome.map_or((None, false), |(results, truncated)| (Some(results), truncated));
 
-	for r in &results {
-		c_results.push(CSearchResult {
-			id:    to_cstring_ptr(r.item.id.as_str()),
-			name:  to_cstring_ptr(r.item.name.as_str()),
-			path:  opt_to_cstring_ptr(r.item.path.as_ref().map(|p| p.as_str())),
-			score: r.score,
-		});
+	if !out_truncated.is_null() {
+		unsafe { *out_truncated = truncated };
 	}
+	unsafe { publish_search_results(handle, results, out_count) }
+}
+
+unsafe fn publish_search_results(
+	handle: *mut SearchEngineHandle,
+	results: Option<Vec<search_engine::SearchResult>>,
+	out_count: *mut size_t,
+) -> *mut CSearchResult {
+	let Some(results) = results.filter(|r| !r.is_empty()) else {
+		unsafe { *out_count = 0 };
+		return ptr::null_mut();
+	};
+
+	let c_results: Vec<CSearchResult> = results.iter().map(to_c_search_result).collect();
 
 	*unsafe { (*handle).last_results.lock() } = Some(results);
 
@@ -426,7 +695,23 @@ pub unsafe extern "C" fn search_engine_search(
 	vec_to_c_array(c_results)
 }
 
-array_free!(search_results_free, CSearchResult, id, name, path);
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn search_results_free(items: *mut CSearchResult, count: size_t) {
+	if items.is_null() || count == 0 {
+		return;
+	}
+	unsafe {
+		for i in 0..count {
+			let item = &(*items.add(i));
+			if !item.match_indices.is_null() {
+				drop(Vec::from_raw_parts(item.match_indices, item.match_count, item.match_count));
+			}
+		}
+		search_results_free_strings(items, count);
+	}
+}
+
+array_free!(private search_results_free_strings, CSearchResult, id, name, path);
 
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn search_engine_stats(
@@ -437,7 +722,7 @@ pub unsafe extern "C" fn search_engine_stats(
 	snippets: *mut size_t,
 ) -> bool {
 	with_handle!(handle, |h: &SearchEngineHandle| {
-		let (total_count, apps_count, files_count, snippets_count) = h.engine.lock().indexer().read().stats();
+		let (total_count, apps_count, files_count, snippets_count) = h.engine.read().indexer().read().stats();
 
 		if !total.is_null() {
 			*total = total_count;
@@ -478,7 +763,7 @@ pub unsafe extern "C" fn search_engine_scan_apps(
 
 	let mut new_apps = Vec::with_capacity(100);
 	{
-		let lock = engine.lock();
+		let lock = engine.read();
 		let indexer = lock.indexer();
 		let reader = indexer.read();
 
@@ -524,10 +809,11 @@ pub unsafe extern "C" fn search_engine_scan_apps(
 				item_type: ItemType::Application,
 				path: Some(full_path),
 				metadata: None,
+				aliases: Vec::new(),
 			})
 			.collect();
 
-		let lock = engine.lock();
+		let lock = engine.read();
 		lock.indexer().write().add_items(items);
 		lock.clear_cache();
 	}
@@ -550,7 +836,7 @@ pub unsafe extern "C" fn search_engine_get_apps(
 
 	let c_apps: Vec<CIndexedApp> = {
 		let engine = unsafe { &(*handle).engine };
-		let lock = engine.lock();
+		let lock = engine.read();
 		let indexer = lock.indexer();
 		let reader = indexer.read();
 		reader
@@ -593,14 +879,14 @@ pub unsafe extern "C" fn search_engine_enable_file_search(
 	};
 
 	let engine = unsafe { &(*handle).engine };
-	engine.lock().enable_file_search(dirs, exts);
+	engine.write().enable_file_search(dirs, exts);
 	true
 }
 
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn search_engine_disable_file_search(handle: *mut SearchEngineHandle) -> bool {
 	with_handle!(handle, |h: &SearchEngineHandle| {
-		h.engine.lock().disable_file_search();
+		h.engine.write().disable_file_search();
 		true
 	})
 }
@@ -615,14 +901,181 @@ pub unsafe extern "C" fn search_engine_set_file_indexer(
 	}
 	let engine = unsafe { &(*search_handle).engine };
 	let indexer = unsafe { &(*indexer_handle).indexer };
-	engine.lock().set_file_indexer(Arc::clone(indexer));
+	engine.write().set_file_indexer(Arc::clone(indexer));
 	true
 }
 
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn search_engine_clear_file_indexer(handle: *mut SearchEngineHandle) -> bool {
 	with_handle!(handle, |h: &SearchEngineHandle| {
-		h.engine.lock().clear_file_indexer();
+		h.engine.write().clear_file_indexer();
+		true
+	})
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn search_engine_set_dedup(handle: *mut SearchEngineHandle, mode: u8) -> bool {
+	let dedup = match mode {
+		0 => None,
+		1 => Some(search_engine::DedupConfig::default()),
+		2 => Some(search_engine::DedupConfig { key: search_engine::DedupKey::NameAndPath, ..Default::default() }),
+		_ => return false,
+	};
+	with_handle!(handle, |h: &SearchEngineHandle| {
+		h.engine.write().set_dedup(dedup);
+		true
+	})
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn search_engine_set_consecutive_exponent(
+	handle: *mut SearchEngineHandle,
+	exponent: f64,
+) -> bool {
+	if !exponent.is_finite() || exponent < 0.0 {
+		return false;
+	}
+	with_handle!(handle, |h: &SearchEngineHandle| {
+		let mut engine = h.engine.write();
+		let weights = ScoringWeights { consecutive_exponent: exponent, ..engine.weights() };
+		engine.set_weights(weights);
+		true
+	})
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn search_engine_set_weights_json(handle: *mut SearchEngineHandle, json: *const c_char) -> bool {
+	require_handle_ret!(false, handle, json);
+
+	let Some(weights) = ok_or_record(sonic_rs::from_str::<ScoringWeights>(cstr!(json))) else {
+		return false;
+	};
+	if !weights.consecutive_exponent.is_finite() || weights.consecutive_exponent < 0.0 {
+		set_last_error("consecutive_exponent must be a finite, non-negative number");
+		return false;
+	}
+	with_handle!(handle, |h: &SearchEngineHandle| {
+		h.engine.write().set_weights(weights);
+		true
+	})
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn search_engine_set_default_limit(handle: *mut SearchEngineHandle, limit: size_t) -> bool {
+	with_handle!(handle, |h: &SearchEngineHandle| {
+		h.engine.write().set_default_limit(limit);
+		true
+	})
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn search_engine_set_recent_capacity(handle: *mut SearchEngineHandle, capacity: size_t) -> bool {
+	with_handle!(handle, |h: &SearchEngineHandle| {
+		h.engine.write().set_recent_capacity(capacity);
+		true
+	})
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn search_engine_record_selection(handle: *mut SearchEngineHandle, id: *const c_char) -> bool {
+	require_handle!(handle, id);
+	with_handle!(handle, |h: &SearchEngineHandle| {
+		h.engine.read().record_selection(cstr!(id));
+		true
+	})
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn search_engine_record_launch(handle: *mut SearchEngineHandle, id: *const c_char) -> bool {
+	require_handle!(handle, id);
+	with_handle!(handle, |h: &SearchEngineHandle| {
+		h.engine.read().record_launch(cstr!(id));
+		true
+	})
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn search_engine_set_pinned_json(
+	handle: *mut SearchEngineHandle,
+	json_ids: *const c_char,
+) -> bool {
+	require_handle!(handle, json_ids);
+
+	let Some(ids) = ok_or_record(sonic_rs::from_str::<Vec<String>>(cstr!(json_ids))) else {
+		return false;
+	};
+	let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+	with_handle!(handle, |h: &SearchEngineHandle| {
+		h.engine.read().set_pinned(&ids);
+		true
+	})
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn search_engine_set_pinned_path(handle: *mut SearchEngineHandle, path: *const c_char) -> bool {
+	require_handle!(handle, path);
+	with_handle!(handle, |h: &SearchEngineHandle| h.engine.write().set_pinned_path(cstr!(path)).is_ok())
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn search_engine_set_usage_path(handle: *mut SearchEngineHandle, path: *const c_char) -> bool {
+	require_handle!(handle, path);
+	with_handle!(handle, |h: &SearchEngineHandle| h.engine.write().set_usage_path(cstr!(path)).is_ok())
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn search_engine_get_recent_json(handle: *mut SearchEngineHandle, limit: size_t) -> *mut c_char {
+	with_handle!(handle, ret = ptr::null_mut(), |h: &SearchEngineHandle| {
+		let recent = h.engine.read().recent_ids(limit);
+		sonic_rs::to_string(&recent).map_or(ptr::null_mut(), to_cstring_ptr)
+	})
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn search_engine_set_fold_diacritics(handle: *mut SearchEngineHandle, enabled: bool) -> bool {
+	with_handle!(handle, |h: &SearchEngineHandle| {
+		h.engine.write().set_fold_diacritics(enabled);
+		true
+	})
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn search_engine_set_stable_file_ids(handle: *mut SearchEngineHandle, enabled: bool) -> bool {
+	with_handle!(handle, |h: &SearchEngineHandle| {
+		h.engine.write().set_stable_file_ids(enabled);
+		true
+	})
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn search_engine_cache_stats(
+	handle: *mut SearchEngineHandle,
+	hits_out: *mut u64,
+	misses_out: *mut u64,
+	entries_out: *mut size_t,
+) -> bool {
+	require_handle_ret!(false, handle, hits_out, misses_out, entries_out);
+	with_handle!(handle, |h: &SearchEngineHandle| {
+		let (hits, misses, entries) = h.engine.read().cache_stats();
+		*hits_out = hits;
+		*misses_out = misses;
+		*entries_out = entries;
+		true
+	})
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn search_engine_reset_cache_stats(handle: *mut SearchEngineHandle) -> bool {
+	with_handle!(handle, |h: &SearchEngineHandle| {
+		h.engine.read().reset_stats();
+		true
+	})
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn search_engine_set_match_paths(handle: *mut SearchEngineHandle, enabled: bool) -> bool {
+	with_handle!(handle, |h: &SearchEngineHandle| {
+		h.engine.write().set_match_paths(enabled);
 		true
 	})
 }
@@ -644,9 +1097,9 @@ pub unsafe extern "C" fn file_indexer_new(
 	let config = if config_json.is_null() {
 		FileIndexerConfig::default()
 	} else {
-		match sonic_rs::from_str::<FileIndexerConfig>(cstr!(config_json)) {
-			Ok(cfg) => cfg,
-			Err(_) => return ptr::null_mut(),
+		match ok_or_record(sonic_rs::from_str::<FileIndexerConfig>(cstr!(config_json))) {
+			Some(cfg) => cfg,
+			None => return ptr::null_mut(),
 		}
 	};
 
@@ -661,17 +1114,79 @@ handle_free!(file_indexer_free, FileIndexerHandle);
 void_method!(file_indexer_start_indexing, FileIndexerHandle, indexer, start_indexing);
 void_method!(file_indexer_enable, FileIndexerHandle, indexer, enable);
 void_method!(file_indexer_disable, FileIndexerHandle, indexer, disable);
+void_method!(file_indexer_pause, FileIndexerHandle, indexer, pause);
+void_method!(file_indexer_resume, FileIndexerHandle, indexer, resume);
+bool_method!(file_indexer_is_paused, FileIndexerHandle, indexer, is_paused);
 size_method!(file_indexer_file_count, FileIndexerHandle, indexer, file_count);
 bool_method!(file_indexer_is_enabled, FileIndexerHandle, indexer, is_enabled);
 bool_method!(file_indexer_refresh_if_needed, FileIndexerHandle, indexer, refresh_if_needed);
 
-pub struct CalculatorHandle {
-	calc: std::cell::RefCell<Calculator>,
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn file_indexer_contains(handle: *mut FileIndexerHandle, path: *const c_char) -> bool {
+	require_handle_ret!(false, handle, path);
+	with_handle!(handle, |h: &FileIndexerHandle| h.indexer.contains(cstr!(path)))
 }
 
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn calculator_new() -> *mut CalculatorHandle {
-	Box::into_raw(Box::new(CalculatorHandle { calc: std::cell::RefCell::new(Calculator::new()) }))
+pub unsafe extern "C" fn file_indexer_set_scan_progress_callback(
+	handle: *mut FileIndexerHandle,
+	callback: Option<extern "C" fn(usize, usize)>,
+) -> bool {
+	with_handle!(handle, |h: &FileIndexerHandle| {
+		h.indexer.set_scan_progress_callback(callback);
+		true
+	})
+}
+
+#[repr(C)]
+pub struct CFileEntry {
+	pub path:     *mut c_char,
+	pub name:     *mut c_char,
+	pub size:     u64,
+	pub modified: u64,
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn file_indexer_get_files(
+	handle: *mut FileIndexerHandle,
+	out_count: *mut size_t,
+) -> *mut CFileEntry {
+	require_handle_ptr!(handle, out_count);
+
+	let entries: Vec<CFileEntry> = unsafe { (*handle).indexer.get_all_files() }
+		.into_iter()
+		.map(|e| CFileEntry {
+			path:     to_cstring_ptr(e.path),
+			name:     to_cstring_ptr(e.name),
+			size:     e.size,
+			modified: e.modified,
+		})
+		.collect();
+
+	unsafe { *out_count = entries.len() };
+	vec_to_c_array(entries)
+}
+
+array_free!(file_entries_free, CFileEntry, path, name);
+
+pub struct CalculatorHandle {
+	calc: Arc<Calculator>,
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn calculator_new() -> *mut CalculatorHandle {
+	Box::into_raw(Box::new(CalculatorHandle { calc: Arc::new(Calculator::new()) }))
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn calculator_new_persistent(path: *const c_char) -> *mut CalculatorHandle {
+	if path.is_null() {
+		return ptr::null_mut();
+	}
+	match Calculator::with_history_path(cstr!(path)) {
+		Ok(calc) => Box::into_raw(Box::new(CalculatorHandle { calc: Arc::new(calc) })),
+		Err(_) => ptr::null_mut(),
+	}
 }
 
 handle_free!(calculator_free, CalculatorHandle);
@@ -682,20 +1197,47 @@ pub unsafe extern "C" fn calculator_evaluate(handle: *mut CalculatorHandle, expr
 		return ptr::null_mut();
 	}
 	with_handle!(handle, ret = ptr::null_mut(), |h: &CalculatorHandle| {
-		match h.calc.borrow_mut().evaluate(cstr!(expr)) {
+		match h.calc.evaluate(cstr!(expr)) {
 			Some(result) => to_cstring_ptr(result),
 			None => ptr::null_mut(),
 		}
 	})
 }
 
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn calculator_set_currency_symbols(handle: *mut CalculatorHandle, enabled: bool) {
+	if !handle.is_null() {
+		let format = if enabled { CurrencyFormat::Symbol } else { CurrencyFormat::Code };
+		unsafe { (*handle).calc.set_currency_format(format) };
+	}
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn calculator_update_rates_json(handle: *mut CalculatorHandle, json: *const c_char) -> bool {
+	require_handle_ret!(false, handle, json);
+
+	let Some(rates) = ok_or_record(sonic_rs::from_str::<FxHashMap<String, f64>>(cstr!(json))) else {
+		return false;
+	};
+	unsafe { (*handle).calc.update_rates(rates) };
+	true
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn calculator_rate_count(handle: *mut CalculatorHandle) -> size_t {
+	if handle.is_null() {
+		return 0;
+	}
+	unsafe { (*handle).calc.rate_count() }
+}
+
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn calculator_get_history_json(handle: *mut CalculatorHandle) -> *mut c_char {
 	if handle.is_null() {
 		return ptr::null_mut();
 	}
 	unsafe {
-		let history_vec: Vec<_> = (*handle).calc.borrow().get_history().iter().cloned().collect();
+		let history_vec = (*handle).calc.get_history();
 		let json_entries: Vec<_> =
 			history_vec.iter().map(|e| sonic_rs::json!({"query": e.query, "result": e.result})).collect();
 		match sonic_rs::to_string(&json_entries) {
@@ -708,16 +1250,38 @@ pub unsafe extern "C" fn calculator_get_history_json(handle: *mut CalculatorHand
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn calculator_clear_history(handle: *mut CalculatorHandle) {
 	if !handle.is_null() {
-		unsafe { (*handle).calc.borrow_mut().clear_history() };
+		unsafe { (*handle).calc.clear_history() };
 	}
 }
 
+#[unsafe(no_mangle)]
+pub extern "C" fn storage_set_durability(sync_mode: u8, async_mode: u8) {
+	storage_utils::set_sync_durability(storage_utils::Durability::from_u8(sync_mode));
+	storage_utils::set_async_durability(storage_utils::Durability::from_u8(async_mode));
+}
+
 pub struct ClipboardStorageHandle {
 	inner: Arc<ClipboardStorage>,
 }
 
 storage_handle!(ClipboardStorageHandle, ClipboardStorage, clipboard_storage);
 
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn clipboard_storage_new_encrypted(
+	path: *const c_char,
+	key_ptr: *const u8,
+	key_len: size_t,
+) -> *mut ClipboardStorageHandle {
+	if path.is_null() || key_ptr.is_null() || key_len != 32 {
+		return ptr::null_mut();
+	}
+	let mut key = [0u8; 32];
+	key.copy_from_slice(unsafe { std::slice::from_raw_parts(key_ptr, key_len) });
+
+	ok_or_record(ClipboardStorage::new_encrypted(cstr!(path), key))
+		.map_or(ptr::null_mut(), |inner| Box::into_raw(Box::new(ClipboardStorageHandle { inner: Arc::new(inner) })))
+}
+
 #[repr(C)]
 pub struct CClipboardEntry {
 	pub content:         *mut c_char,
@@ -728,6 +1292,9 @@ pub struct CClipboardEntry {
 	pub image_height:    f64,
 	pub size:            i32,
 	pub source_app:      *mut c_char,
+	pub pinned:          bool,
+	pub markup:          *mut c_char,
+	pub truncated:       bool,
 }
 
 #[unsafe(no_mangle)]
@@ -740,10 +1307,30 @@ pub unsafe extern "C" fn clipboard_storage_add_text(
 ) -> bool {
 	require_handle!(handle, content);
 	let entry = ClipboardEntry::new_text(cstr_owned!(content), timestamp, size, opt_string!(source_app));
-	with_handle!(handle, |h: &ClipboardStorageHandle| {
-		h.inner.insert_at_front_async(entry);
-		true
-	})
+	with_handle!(handle, |h: &ClipboardStorageHandle| h.inner.insert_at_front_async(entry))
+}
+
+/// `kind` is the `ClipboardItemType` value, 3 for HTML or 4 for RTF.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn clipboard_storage_add_rich(
+	handle: *mut ClipboardStorageHandle,
+	plain: *const c_char,
+	markup: *const c_char,
+	kind: u8,
+	timestamp: f64,
+	size: i32,
+	source_app: *const c_char,
+) -> bool {
+	require_handle!(handle, plain, markup);
+	let entry = ClipboardEntry::new_rich(
+		cstr_owned!(plain),
+		cstr_owned!(markup),
+		ClipboardItemType::from_u8(kind),
+		timestamp,
+		size,
+		opt_string!(source_app),
+	);
+	with_handle!(handle, |h: &ClipboardStorageHandle| h.inner.insert_at_front_async(entry))
 }
 
 #[unsafe(no_mangle)]
@@ -767,10 +1354,12 @@ pub unsafe extern "C" fn clipboard_storage_add_image(
 		size,
 		opt_string!(source_app),
 	);
-	with_handle!(handle, |h: &ClipboardStorageHandle| {
-		h.inner.insert_at_front_async(entry);
-		true
-	})
+	with_handle!(handle, |h: &ClipboardStorageHandle| h.inner.insert_at_front_async(entry))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn clipboard_format_age(timestamp: f64, now: f64) -> *mut c_char {
+	to_cstring_ptr(shared_utils::format_age(timestamp, now))
 }
 
 #[unsafe(no_mangle)]
@@ -793,15 +1382,19 @@ pub unsafe extern "C" fn clipboard_storage_get_entries(
 		.into_iter()
 		.map(|e| {
 			let (width, height) = e.image_size.map_or((0.0, 0.0), |s| (s.width, s.height));
+			let truncated = e.is_truncated();
 			CClipboardEntry {
-				content:         to_cstring_ptr(e.content),
-				timestamp:       e.timestamp,
-				item_type:       e.item_type.as_u8(),
+				content: to_cstring_ptr(e.content),
+				timestamp: e.timestamp,
+				item_type: e.item_type.as_u8(),
 				image_file_path: opt_to_cstring_ptr(e.image_file_path),
-				image_width:     width,
-				image_height:    height,
-				size:            e.size,
-				source_app:      opt_to_cstring_ptr(e.source_app),
+				image_width: width,
+				image_height: height,
+				size: e.size,
+				source_app: opt_to_cstring_ptr(e.source_app),
+				pinned: e.pinned,
+				truncated,
+				markup: opt_to_cstring_ptr(e.markup),
 			}
 		})
 		.collect();
@@ -810,7 +1403,7 @@ pub unsafe extern "C" fn clipboard_storage_get_entries(
 	vec_to_c_array(c_entries)
 }
 
-array_free!(clipboard_entries_free, CClipboardEntry, content, image_file_path, source_app);
+array_free!(clipboard_entries_free, CClipboardEntry, content, image_file_path, source_app, markup);
 
 storage_len!(clipboard_storage_len, ClipboardStorageHandle);
 storage_method!(clipboard_storage_trim, ClipboardStorageHandle, trim_to, size_t);
@@ -818,18 +1411,53 @@ storage_clear!(clipboard_storage_clear, ClipboardStorageHandle);
 
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn clipboard_storage_remove_at(handle: *mut ClipboardStorageHandle, index: size_t) -> bool {
+	with_handle!(handle, |h: &ClipboardStorageHandle| { h.inner.remove_at(index) })
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn clipboard_storage_set_max_text_bytes(
+	handle: *mut ClipboardStorageHandle,
+	max: size_t,
+) -> bool {
 	with_handle!(handle, |h: &ClipboardStorageHandle| {
-		h.inner.update_async(|entries| {
-			if index < entries.len() {
-				entries.remove(index);
-				true
-			} else {
-				false
-			}
-		})
+		h.inner.set_max_text_bytes(max);
+		true
+	})
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn clipboard_storage_set_max_image_bytes(
+	handle: *mut ClipboardStorageHandle,
+	max: size_t,
+) -> bool {
+	with_handle!(handle, |h: &ClipboardStorageHandle| {
+		h.inner.set_max_image_bytes(max);
+		true
 	})
 }
 
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn clipboard_storage_prune_older_than(
+	handle: *mut ClipboardStorageHandle,
+	cutoff: f64,
+) -> size_t {
+	with_handle!(handle, ret = 0, |h: &ClipboardStorageHandle| h.inner.prune_older_than(cutoff))
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn clipboard_storage_set_pinned(
+	handle: *mut ClipboardStorageHandle,
+	index: size_t,
+	pinned: bool,
+) -> bool {
+	with_handle!(handle, |h: &ClipboardStorageHandle| h.inner.set_pinned(index, pinned).unwrap_or(false))
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn clipboard_storage_move_to_front(handle: *mut ClipboardStorageHandle, index: size_t) -> bool {
+	with_handle!(handle, |h: &ClipboardStorageHandle| h.inner.move_to_front(index))
+}
+
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn clipboard_storage_find_duplicate_text(
 	handle: *mut ClipboardStorageHandle,
@@ -852,6 +1480,7 @@ pub struct SnippetMatcherHandle {
 pub struct CSnippetMatch {
 	pub trigger: *mut c_char,
 	pub content: *mut c_char,
+	pub cursor:  size_t,
 }
 
 #[unsafe(no_mangle)]
@@ -859,14 +1488,37 @@ pub unsafe extern "C" fn snippet_matcher_new() -> *mut SnippetMatcherHandle {
 	Box::into_raw(Box::new(SnippetMatcherHandle { matcher: Arc::new(SnippetMatcher::new()) }))
 }
 
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn snippet_matcher_new_with_usage(usage_path: *const c_char) -> *mut SnippetMatcherHandle {
+	if usage_path.is_null() {
+		return ptr::null_mut();
+	}
+	let matcher = SnippetMatcher::with_usage_path(cstr!(usage_path));
+	Box::into_raw(Box::new(SnippetMatcherHandle { matcher: Arc::new(matcher) }))
+}
+
+/// `policy`: 0 = leftmost-longest (last match wins), 1 = leftmost-first
+/// (first match wins), 2 = standard (overlapping, last match wins).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn snippet_matcher_new_with_policy(policy: u8) -> *mut SnippetMatcherHandle {
+	let policy = match policy {
+		1 => MatchPolicy::LeftmostFirst,
+		2 => MatchPolicy::Standard,
+		_ => MatchPolicy::LeftmostLongest,
+	};
+	let matcher = SnippetMatcher::new().with_match_policy(policy);
+	Box::into_raw(Box::new(SnippetMatcherHandle { matcher: Arc::new(matcher) }))
+}
+
 handle_free!(snippet_matcher_free, SnippetMatcherHandle);
 
-#[derive(serde::Deserialize)]
-struct SnippetDTO {
-	id:      String,
-	trigger: String,
-	content: String,
-	enabled: bool,
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn snippet_matcher_usage_json(handle: *mut SnippetMatcherHandle) -> *mut c_char {
+	with_handle!(handle, ret = ptr::null_mut(), |h: &SnippetMatcherHandle| {
+		let usage: Vec<_> =
+			h.matcher.usage_stats().into_iter().map(|(id, count)| sonic_rs::json!({"id": id, "count": count})).collect();
+		sonic_rs::to_string(&usage).map_or(ptr::null_mut(), to_cstring_ptr)
+	})
 }
 
 #[unsafe(no_mangle)]
@@ -875,25 +1527,31 @@ pub unsafe extern "C" fn snippet_matcher_update(handle: *mut SnippetMatcherHandl
 		return false;
 	}
 	with_handle!(handle, |h: &SnippetMatcherHandle| {
-		match sonic_rs::from_str::<Vec<SnippetDTO>>(cstr!(json)) {
-			Ok(dto_snippets) => {
-				let snippets = dto_snippets
-					.into_iter()
-					.map(|dto| Snippet {
-						id:      dto.id,
-						trigger: dto.trigger.into(),
-						content: dto.content.into(),
-						enabled: dto.enabled,
-					})
-					.collect();
+		match ok_or_record(sonic_rs::from_str::<Vec<SnippetDTO>>(cstr!(json))) {
+			Some(dto_snippets) => {
+				let snippets = dto_snippets.into_iter().map(Snippet::from).collect();
 				h.matcher.update_snippets(snippets);
 				true
 			}
-			Err(_) => false,
+			None => false,
 		}
 	})
 }
 
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn snippet_matcher_update_from_storage(
+	handle: *mut SnippetMatcherHandle,
+	storage_handle: *mut SnippetStorageHandle,
+) -> bool {
+	require_handle!(handle, storage_handle);
+	let snippets =
+		unsafe { (*storage_handle).inner.get_enabled() }.into_iter().map(|s| Snippet::from(SnippetDTO::from(s))).collect();
+	with_handle!(handle, |h: &SnippetMatcherHandle| {
+		h.matcher.update_snippets(snippets);
+		true
+	})
+}
+
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn snippet_matcher_find(
 	handle: *mut SnippetMatcherHandle,
@@ -903,10 +1561,37 @@ pub unsafe extern "C" fn snippet_matcher_find(
 		return ptr::null_mut();
 	}
 	with_handle!(handle, ret = ptr::null_mut(), |h: &SnippetMatcherHandle| {
-		match h.matcher.find_match(cstr!(text)) {
-			Some((trigger, content, _)) => Box::into_raw(Box::new(CSnippetMatch {
+		match h.matcher.find_match_with_cursor(cstr!(text), None) {
+			Some((trigger, content, _, cursor)) => Box::into_raw(Box::new(CSnippetMatch {
+				trigger: to_cstring_ptr(trigger.as_ref()),
+				content: to_cstring_ptr(content.as_ref()),
+				cursor,
+			})),
+			None => ptr::null_mut(),
+		}
+	})
+}
+
+/// Same as `snippet_matcher_find`, with placeholders in the content expanded.
+/// `clipboard` and `date_format` may be null.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn snippet_matcher_find_expanded(
+	handle: *mut SnippetMatcherHandle,
+	text: *const c_char,
+	clipboard: *const c_char,
+	date_format: *const c_char,
+) -> *mut CSnippetMatch {
+	require_handle_ptr!(handle, text);
+	let mut ctx = ExpandContext::new(cstr!(clipboard));
+	if let Some(format) = opt_string!(date_format) {
+		ctx = ctx.with_date_format(format);
+	}
+	with_handle!(handle, ret = ptr::null_mut(), |h: &SnippetMatcherHandle| {
+		match h.matcher.find_match_with_cursor(cstr!(text), Some(&ctx)) {
+			Some((trigger, content, _, cursor)) => Box::into_raw(Box::new(CSnippetMatch {
 				trigger: to_cstring_ptr(trigger.as_ref()),
 				content: to_cstring_ptr(content.as_ref()),
+				cursor,
 			})),
 			None => ptr::null_mut(),
 		}
@@ -923,11 +1608,13 @@ storage_handle!(SnippetStorageHandle, SnippetStorage, snippet_storage);
 
 #[repr(C)]
 pub struct CSnippet {
-	pub id:       *mut c_char,
-	pub trigger:  *mut c_char,
-	pub content:  *mut c_char,
-	pub enabled:  bool,
-	pub category: *mut c_char,
+	pub id:         *mut c_char,
+	pub trigger:    *mut c_char,
+	pub content:    *mut c_char,
+	pub enabled:    bool,
+	pub category:   *mut c_char,
+	pub is_regex:   bool,
+	pub match_case: bool,
 }
 
 #[unsafe(no_mangle)]
@@ -946,8 +1633,9 @@ pub unsafe extern "C" fn snippet_storage_add(
 			content: cstr_owned!(content),
 			enabled,
 			category: cstr_owned!(category),
-		});
-		true
+			is_regex: false,
+			match_case: false,
+		})
 	})
 }
 
@@ -969,11 +1657,13 @@ fn snippets_to_c(snippets: &[snippet_storage::Snippet]) -> (*mut CSnippet, size_
 	let c_snippets: Vec<CSnippet> = snippets
 		.iter()
 		.map(|s| CSnippet {
-			id:       to_cstring_ptr(s.id.as_str()),
-			trigger:  to_cstring_ptr(s.trigger.as_str()),
-			content:  to_cstring_ptr(s.content.as_str()),
-			enabled:  s.enabled,
-			category: to_cstring_ptr(s.category.as_str()),
+			id:         to_cstring_ptr(s.id.as_str()),
+			trigger:    to_cstring_ptr(s.trigger.as_str()),
+			content:    to_cstring_ptr(s.content.as_str()),
+			enabled:    s.enabled,
+			category:   to_cstring_ptr(s.category.as_str()),
+			is_regex:   s.is_regex,
+			match_case: s.match_case,
 		})
 		.collect();
 	let count = c_snippets.len();
@@ -1004,6 +1694,26 @@ pub unsafe extern "C" fn snippet_storage_get_enabled(
 	ptr
 }
 
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn snippet_storage_get_by_category(
+	handle: *mut SnippetStorageHandle,
+	category: *const c_char,
+	out_count: *mut size_t,
+) -> *mut CSnippet {
+	require_handle_ptr!(handle, category, out_count);
+	let snippets = unsafe { (*handle).inner.get_by_category(cstr!(category)) };
+	let (ptr, count) = snippets_to_c(&snippets);
+	unsafe { *out_count = count };
+	ptr
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn snippet_storage_categories_json(handle: *mut SnippetStorageHandle) -> *mut c_char {
+	with_handle!(handle, ret = ptr::null_mut(), |h: &SnippetStorageHandle| {
+		sonic_rs::to_string(&h.inner.categories()).map_or(ptr::null_mut(), to_cstring_ptr)
+	})
+}
+
 array_free!(snippets_free, CSnippet, id, trigger, content, category);
 
 storage_len!(snippet_storage_len, SnippetStorageHandle);
@@ -1024,6 +1734,19 @@ pub unsafe extern "C" fn snippet_storage_export_json(handle: *mut SnippetStorage
 	}
 }
 
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn snippet_storage_find_conflicts_json(handle: *mut SnippetStorageHandle) -> *mut c_char {
+	with_handle!(handle, ret = ptr::null_mut(), |h: &SnippetStorageHandle| {
+		let conflicts: Vec<_> = h
+			.inner
+			.find_conflicts()
+			.into_iter()
+			.map(|(trigger, ids)| sonic_rs::json!({"trigger": trigger, "ids": ids}))
+			.collect();
+		sonic_rs::to_string(&conflicts).map_or(ptr::null_mut(), to_cstring_ptr)
+	})
+}
+
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn snippet_storage_import_json(
 	handle: *mut SnippetStorageHandle,
@@ -1035,9 +1758,9 @@ pub unsafe extern "C" fn snippet_storage_import_json(
 	}
 
 	let json_str = cstr!(json);
-	match unsafe { (*handle).inner.import_from_json(json_str, merge) } {
-		Ok(count) => count as i64,
-		Err(_) => -1,
+	match ok_or_record(unsafe { (*handle).inner.import_from_json(json_str, merge) }) {
+		Some(count) => count as i64,
+		None => -1,
 	}
 }
 
@@ -1063,7 +1786,7 @@ pub unsafe extern "C" fn app_storage_add(
 		return false;
 	}
 	let entry = AppEntry::new(cstr_owned!(name), cstr_owned!(path));
-	unsafe { (*handle).inner.add(entry).is_ok() }
+	ok_or_record(unsafe { (*handle).inner.add(entry) }).is_some()
 }
 
 #[unsafe(no_mangle)]
@@ -1186,9 +1909,29 @@ pub unsafe extern "C" fn settings_storage_save(
 		clipboard_shortcut_key: clipboard_key,
 		clipboard_shortcut_mods: clipboard_mods,
 		search_folders,
+		extra: FxHashMap::default(),
 	};
 
-	unsafe { (*handle).inner.save(settings).is_ok() }
+	ok_or_record(unsafe { (*handle).inner.save_keeping_extra(settings) }).is_some()
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn settings_storage_get_extra(
+	handle: *mut SettingsStorageHandle,
+	key: *const c_char,
+) -> *mut c_char {
+	require_handle_ptr!(handle, key);
+	unsafe { (*handle).inner.get_extra(cstr!(key)).map_or(ptr::null_mut(), to_cstring_ptr) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn settings_storage_set_extra(
+	handle: *mut SettingsStorageHandle,
+	key: *const c_char,
+	value: *const c_char,
+) -> bool {
+	require_handle_ret!(false, handle, key);
+	ok_or_record(unsafe { (*handle).inner.set_extra(cstr!(key), opt_string!(value)) }).is_some()
 }
 
 struct_free!(settings_free, CAppSettings, theme, custom_font_name, font_size, quick_select_modifier);
@@ -1215,6 +1958,32 @@ pub struct CActionResult {
 	pub icon:     *mut c_char,
 	pub url:      *mut c_char,
 	pub score:    f32,
+	/// 0 = open `url`, 1 = copy `url` as text, 2 = run `url` as a command
+	/// with `args`.
+	pub kind:     u8,
+	pub args:     CStringArray,
+}
+
+pub type ActionResultsCallback = extern "C" fn(*mut CActionResult, size_t, u64, *mut c_void);
+
+fn to_c_action_result(r: ActionResult) -> CActionResult {
+	let (kind, url, args) = match r.action {
+		ResultAction::OpenUrl(url) => (0, to_cstring_ptr(url), Vec::new()),
+		ResultAction::CopyText(text) => (1, to_cstring_ptr(text), Vec::new()),
+		ResultAction::RunCommand { cmd, args } => (2, to_cstring_ptr(cmd), args),
+	};
+	let args: Vec<*mut c_char> = args.into_iter().map(to_cstring_ptr).collect();
+
+	CActionResult {
+		id: to_cstring_ptr(r.id),
+		title: to_cstring_ptr(r.title),
+		subtitle: to_cstring_ptr(r.subtitle),
+		icon: to_cstring_ptr(r.icon),
+		url,
+		score: r.score,
+		kind,
+		args: CStringArray { len: args.len(), data: vec_to_c_array(args) },
+	}
 }
 
 new_from_path!(action_manager_new, ActionManagerHandle, ActionManager, manager);
@@ -1238,31 +2007,76 @@ pub unsafe extern "C" fn action_manager_search(
 		return ptr::null_mut();
 	}
 
-	let c_results: Vec<CActionResult> = results
-		.into_iter()
-		.map(|r| {
-			let url = match r.action {
-				ResultAction::OpenUrl(url) => to_cstring_ptr(url),
-				ResultAction::CopyText(text) => to_cstring_ptr(text),
-				ResultAction::RunCommand { cmd, .. } => to_cstring_ptr(cmd),
-			};
+	let c_results: Vec<CActionResult> = results.into_iter().map(to_c_action_result).collect();
+
+	unsafe { *out_count = c_results.len() };
+	vec_to_c_array(c_results)
+}
+
+/// Returns local and cached results like `action_manager_search` and runs any
+/// other matching script filters in the background. Their results are passed
+/// to `callback` with the generation written to `out_generation`; callers
+/// should drop deliveries for a generation older than their latest search.
+/// Callback arrays are owned by the caller and must be released with
+/// `action_results_free`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn action_manager_search_async(
+	handle: *mut ActionManagerHandle,
+	query: *const c_char,
+	out_count: *mut size_t,
+	out_generation: *mut u64,
+	callback: ActionResultsCallback,
+	user_data: *mut c_void,
+) -> *mut CActionResult {
+	if handle.is_null() || query.is_null() || out_count.is_null() || out_generation.is_null() {
+		return ptr::null_mut();
+	}
 
-			CActionResult {
-				id: to_cstring_ptr(r.id),
-				title: to_cstring_ptr(r.title),
-				subtitle: to_cstring_ptr(r.subtitle),
-				icon: to_cstring_ptr(r.icon),
-				url,
-				score: r.score,
+	let user_data = UserData(user_data);
+	let (generation, results) = unsafe {
+		(*handle).manager.search_async(cstr!(query), move |generation, results| {
+			let user_data = user_data;
+			if results.is_empty() {
+				callback(ptr::null_mut(), 0, generation, user_data.0);
+				return;
 			}
+			let c_results: Vec<CActionResult> = results.into_iter().map(to_c_action_result).collect();
+			let count = c_results.len();
+			callback(vec_to_c_array(c_results), count, generation, user_data.0);
 		})
-		.collect();
+	};
 
-	unsafe { *out_count = c_results.len() };
-	vec_to_c_array(c_results)
+	unsafe {
+		*out_generation = generation;
+		*out_count = results.len();
+	}
+	if results.is_empty() {
+		return ptr::null_mut();
+	}
+	vec_to_c_array(results.into_iter().map(to_c_action_result).collect())
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn action_results_free(items: *mut CActionResult, count: size_t) {
+	if items.is_null() || count == 0 {
+		return;
+	}
+	unsafe {
+		for i in 0..count {
+			let args = &(*items.add(i)).args;
+			if !args.data.is_null() {
+				for arg in Vec::from_raw_parts(args.data, args.len, args.len) {
+					if !arg.is_null() {
+						drop(CString::from_raw(arg));
+					}
+				}
+			}
+		}
+		action_results_free_strings(items, count);
+	}
 }
 
-array_free!(action_results_free, CActionResult, id, title, subtitle, icon, url);
+array_free!(private action_results_free_strings, CActionResult, id, title, subtitle, icon, url);
 
 manager_json_method!(action_manager_add_json, add, is_ok);
 
@@ -1281,7 +2095,7 @@ pub unsafe extern "C" fn action_manager_add_quick_link(
 
 	let action = Action::quick_link(cstr!(id), cstr!(name), cstr!(keyword), cstr!(url), cstr!(icon));
 
-	unsafe { (*handle).manager.add(action).is_ok() }
+	ok_or_record(unsafe { (*handle).manager.add(action) }).is_some()
 }
 
 #[unsafe(no_mangle)]
@@ -1300,7 +2114,7 @@ pub unsafe extern "C" fn action_manager_add_pattern(
 	let action =
 		Action::pattern(cstr!(id), cstr!(name), cstr!(pattern), PatternActionType::OpenUrl(cstr_owned!(url)), cstr!(icon));
 
-	unsafe { (*handle).manager.add(action).is_ok() }
+	ok_or_record(unsafe { (*handle).manager.add(action) }).is_some()
 }
 
 #[unsafe(no_mangle)]
@@ -1309,17 +2123,22 @@ pub unsafe extern "C" fn action_manager_update_json(handle: *mut ActionManagerHa
 		return false;
 	}
 
-	let action: Action = match sonic_rs::from_str(cstr!(json)) {
-		Ok(a) => a,
-		Err(_) => return false,
+	let Some(action) = ok_or_record(sonic_rs::from_str::<Action>(cstr!(json))) else {
+		return false;
 	};
 
-	unsafe { (*handle).manager.update(action).unwrap_or(false) }
+	ok_or_record(unsafe { (*handle).manager.update(action) }).unwrap_or(false)
 }
 
 manager_str_method!(action_manager_remove, remove);
 manager_str_method!(action_manager_toggle, toggle);
 
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn action_manager_record_use(handle: *mut ActionManagerHandle, id: *const c_char) -> bool {
+	require_handle!(handle, id);
+	unsafe { (*handle).manager.record_use(cstr!(id)) }
+}
+
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn action_manager_get_all_json(handle: *mut ActionManagerHandle) -> *mut c_char {
 	if handle.is_null() {
@@ -1332,12 +2151,235 @@ pub unsafe extern "C" fn action_manager_get_all_json(handle: *mut ActionManagerH
 	}
 }
 
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn action_manager_get_by_id_json(
+	handle: *mut ActionManagerHandle,
+	id: *const c_char,
+) -> *mut c_char {
+	if handle.is_null() || id.is_null() {
+		return ptr::null_mut();
+	}
+	unsafe { (*handle).manager.get_by_id(cstr!(id)) }
+		.and_then(|action| sonic_rs::to_string(&action).ok())
+		.map_or(ptr::null_mut(), to_cstring_ptr)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn action_manager_count(handle: *mut ActionManagerHandle) -> size_t {
+	if handle.is_null() {
+		return 0;
+	}
+	unsafe { (*handle).manager.count() }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn action_manager_get_triggers_json(handle: *mut ActionManagerHandle) -> *mut c_char {
+	if handle.is_null() {
+		return ptr::null_mut();
+	}
+	unsafe {
+		let actions = (*handle).manager.get_all();
+		let triggers: Vec<sonic_rs::Value> = actions
+			.iter()
+			.filter(|a| a.enabled)
+			.map(|a| sonic_rs::json!({"id": a.id.as_str(), "name": a.name.as_str(), "triggers": a.triggers()}))
+			.collect();
+		sonic_rs::to_string(&triggers).map_or(ptr::null_mut(), to_cstring_ptr)
+	}
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn action_manager_set_min_script_query_len(
+	handle: *mut ActionManagerHandle,
+	len: size_t,
+) -> bool {
+	if handle.is_null() {
+		return false;
+	}
+	unsafe { (*handle).manager.set_min_script_query_len(len) };
+	true
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn action_manager_set_fuzzy_keywords(handle: *mut ActionManagerHandle, enabled: bool) -> bool {
+	if handle.is_null() {
+		return false;
+	}
+	unsafe { (*handle).manager.set_fuzzy_keywords(enabled) };
+	true
+}
+
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn action_manager_import_defaults(handle: *mut ActionManagerHandle) -> bool {
 	if handle.is_null() {
 		return false;
 	}
-	unsafe { (*handle).manager.import_defaults().is_ok() }
+	ok_or_record(unsafe { (*handle).manager.import_defaults() }).is_some()
+}
+
+/// Imports a JSON array of actions as defaults, skipping ids that already
+/// exist. Returns the number added, or -1 on error.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn action_manager_import_defaults_json(
+	handle: *mut ActionManagerHandle,
+	json: *const c_char,
+) -> i64 {
+	require_handle_ret!(-1, handle, json);
+	let Some(actions) = ok_or_record(sonic_rs::from_str::<Vec<Action>>(cstr!(json))) else {
+		return -1;
+	};
+	ok_or_record(unsafe { (*handle).manager.import_defaults_from(actions) }).map_or(-1, |count| count as i64)
+}
+
+const EXPORT_VERSION: u32 = 1;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ExportEnvelope {
+	version:   u32,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	clipboard: Option<Vec<ClipboardEntry>>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	snippets:  Option<Vec<snippet_storage::Snippet>>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	apps:      Option<Vec<AppEntry>>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	settings:  Option<AppSettings>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	actions:   Option<Vec<Action>>,
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn summon_export_all_json(
+	clipboard: *mut ClipboardStorageHandle,
+	snippets: *mut SnippetStorageHandle,
+	apps: *mut AppStorageHandle,
+	settings: *mut SettingsStorageHandle,
+	actions: *mut ActionManagerHandle,
+) -> *mut c_char {
+	let envelope = unsafe {
+		ExportEnvelope {
+			version:   EXPORT_VERSION,
+			clipboard: clipboard.as_ref().map(|h| h.inner.get_all().to_vec()),
+			snippets:  snippets.as_ref().map(|h| h.inner.get_all().to_vec()),
+			apps:      apps.as_ref().map(|h| h.inner.get_all().to_vec()),
+			settings:  settings.as_ref().map(|h| h.inner.get()),
+			actions:   actions.as_ref().map(|h| h.manager.get_all().to_vec()),
+		}
+	};
+
+	sonic_rs::to_string(&envelope).map_or(ptr::null_mut(), to_cstring_ptr)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn summon_import_all_json(
+	clipboard: *mut ClipboardStorageHandle,
+	snippets: *mut SnippetStorageHandle,
+	apps: *mut AppStorageHandle,
+	settings: *mut SettingsStorageHandle,
+	actions: *mut ActionManagerHandle,
+	json: *const c_char,
+) -> bool {
+	if json.is_null() {
+		return false;
+	}
+
+	let Some(envelope) = ok_or_record(sonic_rs::from_str::<ExportEnvelope>(cstr!(json))) else {
+		return false;
+	};
+
+	if envelope.version == 0 || envelope.version > EXPORT_VERSION {
+		set_last_error(format!("Unsupported export version {}", envelope.version));
+		return false;
+	}
+
+	if let Some(snippet) = envelope.snippets.iter().flatten().find(|s| !s.has_valid_trigger()) {
+		set_last_error(format!("Snippet {} has a blank trigger", snippet.id));
+		return false;
+	}
+
+	// Every section has been validated, so a failure below comes from the
+	// disk. Stop at the first one and name the sections already written.
+	let mut applied = Vec::new();
+	unsafe {
+		import_section(&mut applied, "clipboard", clipboard.as_ref().zip(envelope.clipboard), |h, entries| {
+			h.inner.replace_all(entries)
+		}) && import_section(&mut applied, "snippets", snippets.as_ref().zip(envelope.snippets), |h, items| {
+			h.inner.import(items, false)
+		}) && import_section(&mut applied, "apps", apps.as_ref().zip(envelope.apps), |h, entries| {
+			h.inner.replace_all(entries)
+		}) && import_section(&mut applied, "settings", settings.as_ref().zip(envelope.settings), |h, s| h.inner.save(s))
+			&& import_section(&mut applied, "actions", actions.as_ref().zip(envelope.actions), |h, items| {
+				h.manager.import(items, false)
+			})
+	}
+}
+
+fn import_section<H, S, T, E: fmt::Display>(
+	applied: &mut Vec<&'static str>,
+	name: &'static str,
+	section: Option<(&H, S)>,
+	import: impl FnOnce(&H, S) -> Result<T, E>,
+) -> bool {
+	let Some((handle, data)) = section else {
+		return true;
+	};
+	match import(handle, data) {
+		Ok(_) => {
+			applied.push(name);
+			true
+		}
+		Err(e) => {
+			let applied = if applied.is_empty() { "none".to_owned() } else { applied.join(", ") };
+			set_last_error(format!("Importing {name} failed: {e} (already imported: {applied})"));
+			false
+		}
+	}
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn summon_flush_all() -> bool { storage_utils::flush_all() }
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn summon_backup(paths_json: *const c_char, out_path: *const c_char) -> bool {
+	if paths_json.is_null() || out_path.is_null() {
+		return false;
+	}
+	let Some(paths) = ok_or_record(sonic_rs::from_str::<Vec<String>>(cstr!(paths_json))) else {
+		return false;
+	};
+	let paths: Vec<&Path> = paths.iter().map(Path::new).collect();
+	ok_or_record(storage_utils::backup_all(&paths, Path::new(cstr!(out_path)))).is_some()
+}
+
+/// Restores a backup into `dir`, returning a JSON report per file. Fails
+/// without writing anything while a storage handle is open on one of the
+/// restored files; free those handles first and reopen them afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn summon_restore(archive_path: *const c_char, dir: *const c_char) -> *mut c_char {
+	if archive_path.is_null() || dir.is_null() {
+		return ptr::null_mut();
+	}
+	let Some(restored) = ok_or_record(storage_utils::restore_all(Path::new(cstr!(archive_path)), Path::new(cstr!(dir))))
+	else {
+		return ptr::null_mut();
+	};
+
+	let report: Vec<sonic_rs::Value> = restored
+		.iter()
+		.map(|r| {
+			let error = r.result.as_ref().err().map(ToString::to_string);
+			sonic_rs::json!({"name": r.name.as_str(), "ok": r.result.is_ok(), "error": error})
+		})
+		.collect();
+	sonic_rs::to_string(&report).map_or(ptr::null_mut(), to_cstring_ptr)
+}
+
+/// Returns the message of the most recent failure on this thread and clears
+/// it, or null if nothing failed since the last call. Free the result with
+/// `string_free`.
+#[unsafe(no_mangle)]
+pub extern "C" fn summon_last_error() -> *mut c_char {
+	LAST_ERROR.with(|e| e.borrow_mut().take()).map_or(ptr::null_mut(), to_cstring_ptr)
 }
 
 #[unsafe(no_mangle)]
@@ -1346,3 +2388,249 @@ pub unsafe extern "C" fn string_free(s: *mut c_char) {
 		unsafe { drop(CString::from_raw(s)) };
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use search_engine::{SearchResult, indexer::{IndexedItem, ItemType}};
+
+	use super::*;
+
+	fn result_named(name: &str, indices: &[usize]) -> SearchResult {
+		SearchResult {
+			item:          Arc::new(IndexedItem {
+				id:        "1".into(),
+				name:      name.into(),
+				item_type: ItemType::Application,
+				path:      None,
+				metadata:  None,
+				aliases:   Vec::new(),
+			}),
+			score:         100,
+			match_indices: indices.iter().copied().collect(),
+		}
+	}
+
+	#[test]
+	fn test_match_indices_round_trip() {
+		let c_result = to_c_search_result(&result_named("Safari", &[0, 1, 2]));
+		assert_eq!(c_result.match_count, 3);
+		let indices = unsafe { std::slice::from_raw_parts(c_result.match_indices, c_result.match_count) };
+		assert_eq!(indices, [0, 1, 2]);
+
+		let accented = to_c_search_result(&result_named("Café Menu", &[3, 5]));
+		let indices = unsafe { std::slice::from_raw_parts(accented.match_indices, accented.match_count) };
+		assert_eq!(indices, [3, 6]);
+
+		let c_results = vec_to_c_array(vec![c_result, accented]);
+		unsafe { search_results_free(c_results, 2) };
+	}
+
+	#[test]
+	fn test_add_items_json_batch() {
+		let json = r#"[
+			{"id":"a","name":"Safari","path":"/Applications/Safari.app","item_type":0},
+			{"id":"b","name":"notes.txt","path":"/tmp/notes.txt","item_type":1},
+			{"id":"c","name":"Reload","item_type":4},
+			{"id":"d","item_type":0},
+			{"id":"e","name":"Bogus","item_type":9}
+		]"#;
+		let json = CString::new(json).unwrap();
+
+		unsafe {
+			let handle = search_engine_new();
+			let mut count = 0;
+			assert!(search_engine_add_items_json(handle, json.as_ptr(), &raw mut count));
+			assert_eq!(count, 3);
+
+			let indexer = (*handle).engine.read().indexer().clone();
+			let (total, apps, files, _) = indexer.read().stats();
+			assert_eq!((total, apps, files), (3, 1, 1));
+			assert_eq!(indexer.read().get_item("c").map(|i| i.path), Some(None));
+			search_engine_free(handle);
+		}
+	}
+
+	#[test]
+	fn test_remove_items() {
+		let json =
+			CString::new(r#"[{"id":"a","name":"Safari"},{"id":"b","name":"Notes"},{"id":"c","name":"Mail"}]"#).unwrap();
+		let missing = CString::new("zzz").unwrap();
+		let id = CString::new("a").unwrap();
+		let ids = CString::new(r#"["b","zzz"]"#).unwrap();
+
+		unsafe {
+			let handle = search_engine_new();
+			assert!(search_engine_add_items_json(handle, json.as_ptr(), ptr::null_mut()));
+			let indexer = (*handle).engine.read().indexer().clone();
+
+			assert!(search_engine_remove_item(handle, id.as_ptr()));
+			assert!(!search_engine_remove_item(handle, missing.as_ptr()));
+			assert_eq!(indexer.read().stats().0, 2);
+
+			assert_eq!(search_engine_remove_items_json(handle, ids.as_ptr()), 1);
+			assert_eq!(indexer.read().stats().0, 1);
+			search_engine_free(handle);
+		}
+	}
+
+	// Sends through a clone so the channel outlives the send even when the test
+	// returns as soon as it receives.
+	extern "C" fn record_count(results: *mut CSearchResult, count: size_t, user_data: *mut c_void) {
+		let tx = unsafe { &*user_data.cast::<std::sync::mpsc::Sender<usize>>() }.clone();
+		unsafe { search_results_free(results, count) };
+		tx.send(count).unwrap();
+	}
+
+	#[test]
+	fn test_search_async_invokes_callback() {
+		let json =
+			CString::new(r#"[{"id":"a","name":"Safari"},{"id":"b","name":"Safe Notes"},{"id":"c","name":"Mail"}]"#).unwrap();
+		let query = CString::new("saf").unwrap();
+		let (tx, rx) = std::sync::mpsc::channel::<usize>();
+
+		unsafe {
+			let handle = search_engine_new();
+			assert!(search_engine_add_items_json(handle, json.as_ptr(), ptr::null_mut()));
+
+			let user_data = (&raw const tx).cast_mut().cast::<c_void>();
+			let token = search_engine_search_async(handle, query.as_ptr(), 10, record_count, user_data);
+			assert_eq!(token, 1);
+			assert_eq!(rx.recv_timeout(std::time::Duration::from_secs(5)).unwrap(), 2);
+			search_engine_free(handle);
+		}
+	}
+
+	#[test]
+	fn test_search_async_skips_stale_queries() {
+		let json = CString::new(r#"[{"id":"a","name":"Safari"},{"id":"b","name":"Safe Notes"}]"#).unwrap();
+		let query = CString::new("saf").unwrap();
+		let (tx, rx) = std::sync::mpsc::channel::<usize>();
+
+		unsafe {
+			let handle = search_engine_new();
+			assert!(search_engine_add_items_json(handle, json.as_ptr(), ptr::null_mut()));
+			let user_data = (&raw const tx).cast_mut().cast::<c_void>();
+
+			let settings = (*handle).engine.write();
+			let stale = search_engine_search_async(handle, query.as_ptr(), 10, record_count, user_data);
+			let latest = search_engine_search_async(handle, query.as_ptr(), 10, record_count, user_data);
+			assert_eq!((stale, latest), (1, 2));
+			drop(settings);
+
+			let timeout = std::time::Duration::from_secs(5);
+			let mut counts = [rx.recv_timeout(timeout).unwrap(), rx.recv_timeout(timeout).unwrap()];
+			counts.sort_unstable();
+			assert_eq!(counts, [0, 2]);
+			search_engine_free(handle);
+		}
+	}
+
+	#[test]
+	fn test_run_command_result_keeps_args() {
+		let action_type = PatternActionType::RunCommand {
+			cmd:  "/usr/bin/say".to_owned(),
+			args: vec!["-v".to_owned(), "{text}".to_owned()],
+		};
+		let captures = action_manager::pattern::match_pattern("say {text}", "say hello").unwrap();
+		let result = action_manager::pattern::create_results("say", "Say", "say {text}", &action_type, &captures, "icon")
+			.pop()
+			.unwrap();
+
+		unsafe {
+			let items = vec_to_c_array(vec![to_c_action_result(result)]);
+			let item = &*items;
+			assert_eq!(item.kind, 2);
+			assert_eq!(CStr::from_ptr(item.url).to_str().unwrap(), "/usr/bin/say");
+			let args: Vec<_> = (0..item.args.len).map(|i| CStr::from_ptr(*item.args.data.add(i)).to_str().unwrap()).collect();
+			assert_eq!(args, ["-v", "hello"]);
+			action_results_free(items, 1);
+		}
+	}
+
+	#[test]
+	fn test_last_error_reports_parse_failure() {
+		let bad = CString::new("{not json").unwrap();
+		unsafe {
+			let handle = calculator_new();
+			assert!(!calculator_update_rates_json(handle, bad.as_ptr()));
+			let message = summon_last_error();
+			assert!(!message.is_null());
+			assert!(!CStr::from_ptr(message).to_str().unwrap().is_empty());
+			string_free(message);
+			assert!(summon_last_error().is_null());
+
+			assert!(!calculator_update_rates_json(ptr::null_mut(), bad.as_ptr()));
+			let message = summon_last_error();
+			assert_eq!(CStr::from_ptr(message).to_str().unwrap(), NULL_ARGUMENT);
+			string_free(message);
+			calculator_free(handle);
+
+			let missing = CString::new("/nonexistent/summon-backup.tar").unwrap();
+			let dir = CString::new(std::env::temp_dir().to_string_lossy().as_ref()).unwrap();
+			assert!(summon_restore(missing.as_ptr(), dir.as_ptr()).is_null());
+			let message = summon_last_error();
+			assert!(!message.is_null());
+			string_free(message);
+		}
+	}
+
+	#[test]
+	fn test_export_import_round_trip() {
+		let source = tempfile::TempDir::new().unwrap();
+		let target = tempfile::TempDir::new().unwrap();
+		let open = |dir: &tempfile::TempDir| unsafe {
+			let path = |name: &str| CString::new(dir.path().join(name).to_string_lossy().as_ref()).unwrap();
+			(
+				clipboard_storage_new(path("clipboard.bin").as_ptr()),
+				snippet_storage_new(path("snippets.bin").as_ptr()),
+				app_storage_new(path("apps.bin").as_ptr()),
+				settings_storage_new(path("settings.bin").as_ptr()),
+				action_manager_new(path("actions.bin").as_ptr()),
+			)
+		};
+
+		unsafe {
+			let (clipboard, snippets, apps, settings, actions) = open(&source);
+			let text = CString::new("copied").unwrap();
+			assert!(clipboard_storage_add_text(clipboard, text.as_ptr(), 1.0, 6, ptr::null()));
+			assert!((*snippets).inner.add(snippet_storage::Snippet::new("\\sig".to_owned(), "Cheers".to_owned())));
+			let (name, app_path) = (CString::new("Safari").unwrap(), CString::new("/Applications/Safari.app").unwrap());
+			assert!(app_storage_add(apps, name.as_ptr(), app_path.as_ptr()));
+			(*settings).inner.set_extra("theme", Some("dark".to_owned())).unwrap();
+
+			let exported = summon_export_all_json(clipboard, snippets, apps, settings, actions);
+			assert!(!exported.is_null());
+
+			let (clipboard2, snippets2, apps2, settings2, actions2) = open(&target);
+			assert!(summon_import_all_json(clipboard2, snippets2, apps2, settings2, actions2, exported));
+			assert_eq!((*clipboard2).inner.get_all(), (*clipboard).inner.get_all());
+			assert_eq!((*snippets2).inner.get_all(), (*snippets).inner.get_all());
+			let app_paths =
+				|h: *mut AppStorageHandle| (*h).inner.get_all().iter().map(|a| a.path.clone()).collect::<Vec<_>>();
+			assert_eq!(app_paths(apps2), ["/Applications/Safari.app"]);
+			assert_eq!((*settings2).inner.get_extra("theme").as_deref(), Some("dark"));
+			assert_eq!((*actions2).manager.get_all(), (*actions).manager.get_all());
+
+			let blank = CString::new(
+				r#"{"version":1,"clipboard":[],"snippets":[{"id":"x","trigger":" ","content":"","enabled":true,"category":""}]}"#,
+			)
+			.unwrap();
+			assert!(!summon_import_all_json(clipboard2, snippets2, apps2, settings2, actions2, blank.as_ptr()));
+			assert_eq!((*clipboard2).inner.len(), 1);
+			let message = summon_last_error();
+			assert!(CStr::from_ptr(message).to_str().unwrap().contains("blank trigger"));
+			string_free(message);
+
+			string_free(exported);
+			for (c, s, a, st, m) in
+				[(clipboard, snippets, apps, settings, actions), (clipboard2, snippets2, apps2, settings2, actions2)]
+			{
+				clipboard_storage_free(c);
+				snippet_storage_free(s);
+				app_storage_free(a);
+				settings_storage_free(st);
+				action_manager_free(m);
+			}
+		}
+	}
+}
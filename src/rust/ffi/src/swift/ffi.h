@@ -16,6 +16,8 @@ typedef struct ActionManagerHandle ActionManagerHandle;
 
 typedef struct CalculatorHandle CalculatorHandle;
 
+typedef struct FileIndexerHandle FileIndexerHandle;
+
 typedef struct SearchEngineHandle SearchEngineHandle;
 
 typedef struct SnippetMatcherHandle SnippetMatcherHandle;
@@ -25,6 +27,9 @@ typedef struct CSearchResult {
   char *name;
   char *path;
   int64_t score;
+  /* UTF-8 byte offsets into name, one per matched character. */
+  size_t *match_indices;
+  size_t match_count;
 } CSearchResult;
 
 typedef struct CStringArray {
@@ -37,6 +42,13 @@ typedef struct CIndexedApp {
   char *path;
 } CIndexedApp;
 
+typedef struct CFileEntry {
+  char *path;
+  char *name;
+  uint64_t size;
+  uint64_t modified;
+} CFileEntry;
+
 typedef struct CClipboardEntry {
   char *content;
   double timestamp;
@@ -46,11 +58,15 @@ typedef struct CClipboardEntry {
   double image_height;
   int32_t size;
   char *source_app;
+  bool pinned;
+  char *markup;
+  bool truncated;
 } CClipboardEntry;
 
 typedef struct CSnippetMatch {
   char *trigger;
   char *content;
+  size_t cursor;
 } CSnippetMatch;
 
 typedef struct CSnippet {
@@ -59,6 +75,8 @@ typedef struct CSnippet {
   char *content;
   bool enabled;
   char *category;
+  bool is_regex;
+  bool match_case;
 } CSnippet;
 
 typedef struct CAppEntry {
@@ -87,8 +105,16 @@ typedef struct CActionResult {
   char *icon;
   char *url;
   float score;
+  uint8_t kind;
+  struct CStringArray args;
 } CActionResult;
 
+typedef void (*ActionResultsCallback)(struct CActionResult*, size_t, uint64_t, void*);
+
+typedef void (*SearchResultsCallback)(struct CSearchResult*, size_t, void*);
+
+typedef void (*ScanProgressCallback)(size_t, size_t);
+
 #ifdef __cplusplus
 extern "C" {
 #endif // __cplusplus
@@ -133,6 +159,141 @@ bool search_engine_enable_file_search(struct SearchEngineHandle *Handle,
 
 bool search_engine_disable_file_search(struct SearchEngineHandle *Handle);
 
+struct CSearchResult *search_engine_search_with_deadline_ms(struct SearchEngineHandle *Handle,
+                                                            const char *Query,
+                                                            size_t Limit,
+                                                            uint64_t DeadlineMs,
+                                                            size_t *OutCount,
+                                                            bool *OutTruncated);
+
+bool search_engine_set_stable_file_ids(struct SearchEngineHandle *Handle,
+                                       bool Enabled);
+
+bool search_engine_set_fold_diacritics(struct SearchEngineHandle *Handle,
+                                       bool Enabled);
+
+bool search_engine_record_selection(struct SearchEngineHandle *Handle,
+                                    const char *Id);
+
+char *search_engine_get_recent_json(struct SearchEngineHandle *Handle,
+                                    size_t Limit);
+
+bool search_engine_set_recent_capacity(struct SearchEngineHandle *Handle,
+                                       size_t Capacity);
+
+bool search_engine_set_default_limit(struct SearchEngineHandle *Handle,
+                                     size_t Limit);
+
+bool search_engine_set_dedup(struct SearchEngineHandle *Handle,
+                             uint8_t Mode);
+
+bool search_engine_warm_json(struct SearchEngineHandle *Handle,
+                             const char *QueriesJson,
+                             size_t Limit);
+
+struct CSearchResult *search_engine_search_counted(struct SearchEngineHandle *Handle,
+                                                   const char *Query,
+                                                   size_t Limit,
+                                                   size_t *OutCount,
+                                                   size_t *OutTotal);
+
+bool search_engine_set_consecutive_exponent(struct SearchEngineHandle *Handle,
+                                            double Exponent);
+
+bool search_engine_add_item_with_aliases(struct SearchEngineHandle *Handle,
+                                         const char *Id,
+                                         const char *Name,
+                                         const char *Path,
+                                         uint8_t ItemType,
+                                         const char *AliasesJson);
+
+bool search_engine_set_weights_json(struct SearchEngineHandle *Handle,
+                                    const char *Json);
+
+bool search_engine_record_launch(struct SearchEngineHandle *Handle,
+                                 const char *Id);
+
+bool search_engine_set_usage_path(struct SearchEngineHandle *Handle,
+                                  const char *Path);
+
+struct CSearchResult *search_engine_search_page(struct SearchEngineHandle *Handle,
+                                                const char *Query,
+                                                size_t Offset,
+                                                size_t Limit,
+                                                size_t *OutCount);
+
+bool search_engine_add_items_json(struct SearchEngineHandle *Handle,
+                                  const char *Json,
+                                  size_t *CountOut);
+
+bool search_engine_remove_item(struct SearchEngineHandle *Handle,
+                               const char *Id);
+
+size_t search_engine_remove_items_json(struct SearchEngineHandle *Handle,
+                                       const char *JsonIds);
+
+bool search_engine_set_match_paths(struct SearchEngineHandle *Handle,
+                                   bool Enabled);
+
+bool search_engine_cache_stats(struct SearchEngineHandle *Handle,
+                               uint64_t *HitsOut,
+                               uint64_t *MissesOut,
+                               size_t *EntriesOut);
+
+bool search_engine_reset_cache_stats(struct SearchEngineHandle *Handle);
+
+bool search_engine_set_pinned_json(struct SearchEngineHandle *Handle,
+                                   const char *JsonIds);
+
+bool search_engine_set_pinned_path(struct SearchEngineHandle *Handle,
+                                   const char *Path);
+
+uint64_t search_engine_search_async(struct SearchEngineHandle *Handle,
+                                    const char *Query,
+                                    size_t Limit,
+                                    SearchResultsCallback Callback,
+                                    void *UserData);
+
+bool search_engine_set_file_indexer(struct SearchEngineHandle *SearchHandle,
+                                    struct FileIndexerHandle *IndexerHandle);
+
+bool search_engine_clear_file_indexer(struct SearchEngineHandle *Handle);
+
+struct FileIndexerHandle *file_indexer_new(const char *StoragePath,
+                                           const char *ConfigJson);
+
+void file_indexer_free(struct FileIndexerHandle *Handle);
+
+void file_indexer_start_indexing(struct FileIndexerHandle *Handle);
+
+void file_indexer_enable(struct FileIndexerHandle *Handle);
+
+void file_indexer_disable(struct FileIndexerHandle *Handle);
+
+size_t file_indexer_file_count(struct FileIndexerHandle *Handle);
+
+bool file_indexer_is_enabled(struct FileIndexerHandle *Handle);
+
+bool file_indexer_refresh_if_needed(struct FileIndexerHandle *Handle);
+
+struct CFileEntry *file_indexer_get_files(struct FileIndexerHandle *Handle,
+                                          size_t *OutCount);
+
+void file_entries_free(struct CFileEntry *Entries,
+                       size_t Count);
+
+void file_indexer_pause(struct FileIndexerHandle *Handle);
+
+void file_indexer_resume(struct FileIndexerHandle *Handle);
+
+bool file_indexer_is_paused(struct FileIndexerHandle *Handle);
+
+bool file_indexer_contains(struct FileIndexerHandle *Handle,
+                           const char *Path);
+
+bool file_indexer_set_scan_progress_callback(struct FileIndexerHandle *Handle,
+                                             ScanProgressCallback Callback);
+
 struct CalculatorHandle *calculator_new(void);
 
 void calculator_free(struct CalculatorHandle *Handle);
@@ -146,12 +307,30 @@ void calculator_free_string(char *S);
 
 void calculator_clear_history(struct CalculatorHandle *Handle);
 
+void calculator_set_currency_symbols(struct CalculatorHandle *Handle,
+                                     bool Enabled);
+
+bool calculator_update_rates_json(struct CalculatorHandle *Handle,
+                                  const char *Json);
+
+size_t calculator_rate_count(struct CalculatorHandle *Handle);
+
+struct CalculatorHandle *calculator_new_persistent(const char *Path);
+
 bool clipboard_storage_add_text(ClipboardStorageHandle *Handle,
                                 const char *Content,
                                 double Timestamp,
                                 int32_t Size,
                                 const char *SourceApp);
 
+bool clipboard_storage_add_rich(ClipboardStorageHandle *Handle,
+                                const char *Plain,
+                                const char *Markup,
+                                uint8_t Kind,
+                                double Timestamp,
+                                int32_t Size,
+                                const char *SourceApp);
+
 bool clipboard_storage_add_image(ClipboardStorageHandle *Handle,
                                  const char *Content,
                                  double Timestamp,
@@ -174,21 +353,58 @@ size_t clipboard_storage_len(ClipboardStorageHandle *Handle);
 bool clipboard_storage_trim(ClipboardStorageHandle *Handle,
                             size_t Max);
 
+bool clipboard_storage_set_max_text_bytes(ClipboardStorageHandle *Handle,
+                                          size_t Max);
+
+bool clipboard_storage_set_max_image_bytes(ClipboardStorageHandle *Handle,
+                                           size_t Max);
+
+size_t clipboard_storage_prune_older_than(ClipboardStorageHandle *Handle,
+                                         double Cutoff);
+
+bool clipboard_storage_set_pinned(ClipboardStorageHandle *Handle,
+                                  size_t Index,
+                                  bool Pinned);
+
 bool clipboard_storage_clear(ClipboardStorageHandle *Handle);
 
 bool clipboard_storage_remove_at(ClipboardStorageHandle *Handle,
                                  size_t Index);
 
+bool clipboard_storage_move_to_front(ClipboardStorageHandle *Handle,
+                                     size_t Index);
+
+char *clipboard_format_age(double Timestamp,
+                           double Now);
+
+ClipboardStorageHandle *clipboard_storage_new_encrypted(const char *Path,
+                                                        const uint8_t *KeyPtr,
+                                                        size_t KeyLen);
+
 struct SnippetMatcherHandle *snippet_matcher_new(void);
 
+struct SnippetMatcherHandle *snippet_matcher_new_with_usage(const char *UsagePath);
+
+struct SnippetMatcherHandle *snippet_matcher_new_with_policy(uint8_t Policy);
+
 void snippet_matcher_free(struct SnippetMatcherHandle *Handle);
 
+char *snippet_matcher_usage_json(struct SnippetMatcherHandle *Handle);
+
 bool snippet_matcher_update(struct SnippetMatcherHandle *Handle,
                             const char *Json);
 
+bool snippet_matcher_update_from_storage(struct SnippetMatcherHandle *Handle,
+                                         SnippetStorageHandle *StorageHandle);
+
 struct CSnippetMatch *snippet_matcher_find(struct SnippetMatcherHandle *Handle,
                                            const char *Text);
 
+struct CSnippetMatch *snippet_matcher_find_expanded(struct SnippetMatcherHandle *Handle,
+                                                    const char *Text,
+                                                    const char *Clipboard,
+                                                    const char *DateFormat);
+
 void snippet_match_free(struct CSnippetMatch *Result);
 
 bool snippet_storage_add(SnippetStorageHandle *Handle,
@@ -214,11 +430,19 @@ struct CSnippet *snippet_storage_get_all(SnippetStorageHandle *Handle,
 struct CSnippet *snippet_storage_get_enabled(SnippetStorageHandle *Handle,
                                              size_t *OutCount);
 
+struct CSnippet *snippet_storage_get_by_category(SnippetStorageHandle *Handle,
+                                                 const char *Category,
+                                                 size_t *OutCount);
+
+char *snippet_storage_categories_json(SnippetStorageHandle *Handle);
+
 void snippets_free(struct CSnippet *Snippets,
                    size_t Count);
 
 size_t snippet_storage_len(SnippetStorageHandle *Handle);
 
+char *snippet_storage_find_conflicts_json(SnippetStorageHandle *Handle);
+
 bool app_storage_add(AppStorageHandle *Handle,
                      const char *Name,
                      const char *Path);
@@ -259,6 +483,13 @@ char *settings_storage_get_launcher_shortcut(SettingsStorageHandle *Handle);
 
 char *settings_storage_get_clipboard_shortcut(SettingsStorageHandle *Handle);
 
+char *settings_storage_get_extra(SettingsStorageHandle *Handle,
+                                 const char *Key);
+
+bool settings_storage_set_extra(SettingsStorageHandle *Handle,
+                                const char *Key,
+                                const char *Value);
+
 struct ActionManagerHandle *action_manager_new(const char *Path);
 
 void action_manager_free(struct ActionManagerHandle *Handle);
@@ -270,6 +501,13 @@ struct CActionResult *action_manager_search(struct ActionManagerHandle *Handle,
 void action_results_free(struct CActionResult *Results,
                          size_t Count);
 
+struct CActionResult *action_manager_search_async(struct ActionManagerHandle *Handle,
+                                                  const char *Query,
+                                                  size_t *OutCount,
+                                                  uint64_t *OutGeneration,
+                                                  ActionResultsCallback Callback,
+                                                  void *UserData);
+
 bool action_manager_add_json(struct ActionManagerHandle *Handle,
                              const char *Json);
 
@@ -296,10 +534,60 @@ bool action_manager_remove(struct ActionManagerHandle *Handle,
 bool action_manager_toggle(struct ActionManagerHandle *Handle,
                            const char *Id);
 
+bool action_manager_record_use(struct ActionManagerHandle *Handle,
+                               const char *Id);
+
 char *action_manager_get_all_json(struct ActionManagerHandle *Handle);
 
+char *action_manager_get_by_id_json(struct ActionManagerHandle *Handle,
+                                    const char *Id);
+
+size_t action_manager_count(struct ActionManagerHandle *Handle);
+
+bool action_manager_set_fuzzy_keywords(struct ActionManagerHandle *Handle,
+                                       bool Enabled);
+
 bool action_manager_import_defaults(struct ActionManagerHandle *Handle);
 
+int64_t action_manager_import_defaults_json(struct ActionManagerHandle *Handle,
+                                            const char *Json);
+
+bool action_manager_set_min_script_query_len(struct ActionManagerHandle *Handle,
+                                             size_t Len);
+
+char *action_manager_get_triggers_json(struct ActionManagerHandle *Handle);
+
+char *summon_export_all_json(ClipboardStorageHandle *Clipboard,
+                             SnippetStorageHandle *Snippets,
+                             AppStorageHandle *Apps,
+                             SettingsStorageHandle *Settings,
+                             struct ActionManagerHandle *Actions);
+
+bool summon_import_all_json(ClipboardStorageHandle *Clipboard,
+                            SnippetStorageHandle *Snippets,
+                            AppStorageHandle *Apps,
+                            SettingsStorageHandle *Settings,
+                            struct ActionManagerHandle *Actions,
+                            const char *Json);
+
+void storage_set_durability(uint8_t SyncMode,
+                            uint8_t AsyncMode);
+
+bool summon_backup(const char *PathsJson,
+                   const char *OutPath);
+
+/**
+ * Restores a backup into `dir`, returning a JSON report per file. Fails
+ * without writing anything while a storage handle is open on one of the
+ * restored files; free those handles first and reopen them afterwards.
+ */
+char *summon_restore(const char *ArchivePath,
+                     const char *Dir);
+
+bool summon_flush_all(void);
+
+char *summon_last_error(void);
+
 void string_free(char *S);
 
 #ifdef __cplusplus
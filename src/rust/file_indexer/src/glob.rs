@@ -0,0 +1,159 @@
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Token {
+	Literal(u8),
+	AnyChar,
+	Star,
+	AnyPath,
+	AnyDirs,
+}
+
+/// A compiled exclude pattern. `*` and `?` stay within one path segment, `**`
+/// crosses segments and a leading `**/` also matches zero directories.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GlobPattern {
+	tokens: Vec<Token>,
+}
+
+impl GlobPattern {
+	#[must_use]
+	pub fn new(pattern: &str) -> Self {
+		let bytes = pattern.as_bytes();
+		let mut tokens = Vec::with_capacity(bytes.len());
+		let mut i = 0;
+
+		while let Some(&byte) = bytes.get(i) {
+			match byte {
+				b'*' if bytes.get(i + 1) == Some(&b'*') => {
+					if bytes.get(i + 2) == Some(&b'/') {
+						tokens.push(Token::AnyDirs);
+						i += 3;
+					} else {
+						tokens.push(Token::AnyPath);
+						i += 2;
+					}
+					continue;
+				}
+				b'*' => tokens.push(Token::Star),
+				b'?' => tokens.push(Token::AnyChar),
+				b => tokens.push(Token::Literal(b)),
+			}
+			i += 1;
+		}
+
+		Self { tokens }
+	}
+
+	#[must_use]
+	pub fn matches(&self, text: &str) -> bool {
+		Matcher {
+			tokens: &self.tokens,
+			text:   text.as_bytes(),
+			failed: vec![false; (self.tokens.len() + 1) * (text.len() + 1)],
+		}
+		.at(0, 0)
+	}
+}
+
+// Remembers every (token, offset) pair that failed, so wildcards never retry
+// the same suffix and matching stays polynomial in the pattern and path length.
+struct Matcher<'a> {
+	tokens: &'a [Token],
+	text:   &'a [u8],
+	failed: Vec<bool>,
+}
+
+impl Matcher<'_> {
+	fn at(&mut self, token: usize, pos: usize) -> bool {
+		let slot = token * (self.text.len() + 1) + pos;
+		if self.failed.get(slot).copied().unwrap_or(true) {
+			return false;
+		}
+
+		let text = self.text;
+		let matched = match self.tokens.get(token) {
+			None => pos == text.len(),
+			Some(&Token::Literal(byte)) => text.get(pos) == Some(&byte) && self.at(token + 1, pos + 1),
+			Some(Token::AnyChar) => match text.get(pos) {
+				Some(&byte) if byte != b'/' => self.at(token + 1, pos + utf8_len(byte)),
+				_ => false,
+			},
+			Some(Token::Star) => {
+				let segment_end = text.iter().skip(pos).position(|&b| b == b'/').map_or(text.len(), |n| pos + n);
+				(pos..=segment_end).any(|end| is_boundary(text, end) && self.at(token + 1, end))
+			}
+			Some(Token::AnyPath) => (pos..=text.len()).any(|end| is_boundary(text, end) && self.at(token + 1, end)),
+			Some(Token::AnyDirs) => {
+				self.at(token + 1, pos)
+					|| (pos..text.len()).any(|slash| text.get(slash) == Some(&b'/') && self.at(token + 1, slash + 1))
+			}
+		};
+
+		if !matched && let Some(failed) = self.failed.get_mut(slot) {
+			*failed = true;
+		}
+		matched
+	}
+}
+
+const fn utf8_len(lead: u8) -> usize {
+	match lead {
+		0xf0.. => 4,
+		0xe0.. => 3,
+		0xc0.. => 2,
+		_ => 1,
+	}
+}
+
+fn is_boundary(text: &[u8], pos: usize) -> bool { text.get(pos).is_none_or(|&b| b & 0xc0 != 0x80) }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_star_stays_in_segment() {
+		let glob = GlobPattern::new("*.log");
+		assert!(glob.matches("debug.log"));
+		assert!(glob.matches(".log"));
+		assert!(!glob.matches("logs/debug.log"));
+		assert!(!glob.matches("debug.txt"));
+	}
+
+	#[test]
+	fn test_any_path_crosses_segments() {
+		let glob = GlobPattern::new("build/**");
+		assert!(glob.matches("build/"));
+		assert!(glob.matches("build/out/app.o"));
+		assert!(!glob.matches("src/build"));
+
+		let dirs = GlobPattern::new("**/node_modules");
+		assert!(dirs.matches("node_modules"));
+		assert!(dirs.matches("web/app/node_modules"));
+		assert!(!dirs.matches("web/node_modules_old"));
+	}
+
+	#[test]
+	fn test_question_mark_matches_one_char() {
+		let glob = GlobPattern::new("file?.txt");
+		assert!(glob.matches("file1.txt"));
+		assert!(glob.matches("fileé.txt"));
+		assert!(!glob.matches("file.txt"));
+		assert!(!glob.matches("file12.txt"));
+		assert!(!glob.matches("file/.txt"));
+	}
+
+	#[test]
+	fn test_no_match() {
+		assert!(!GlobPattern::new("*.md").matches("README"));
+		assert!(!GlobPattern::new("docs/*").matches("src/docs/a"));
+		assert!(!GlobPattern::new("").matches("a"));
+		assert!(GlobPattern::new("").matches(""));
+	}
+
+	#[test]
+	fn test_many_wildcards_on_long_path() {
+		let glob = GlobPattern::new("**/a*a*a*a*a*a*a*a*b/**");
+		let path = format!("{}/{}", "a".repeat(60), "a".repeat(60));
+		assert!(!glob.matches(&path));
+	}
+}
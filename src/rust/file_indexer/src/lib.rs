@@ -1,19 +1,27 @@
-use std::{fs, io, path::{Path, PathBuf}, sync::{Arc, atomic::{AtomicBool, AtomicUsize, Ordering}}, time::{Duration, SystemTime}};
+mod glob;
+
+use std::{fs, io, path::{Path, PathBuf}, sync::{Arc, atomic::{AtomicBool, AtomicUsize, Ordering}}, time::{Duration, SystemTime, UNIX_EPOCH}};
 
 use bytecheck::CheckBytes;
 use compact_str::CompactString;
 use crossbeam_channel::{Receiver, unbounded};
+use ignore::WalkBuilder;
 use notify::{EventKind, RecommendedWatcher, RecursiveMode};
 use notify_debouncer_full::{DebouncedEvent, Debouncer, new_debouncer};
 use parking_lot::RwLock;
 use rkyv::{Archive, Deserialize, Serialize};
 use rustc_hash::{FxBuildHasher, FxHashMap};
 use storage_utils::{load_from_disk, save_to_disk};
+#[cfg(target_os = "macos")]
 use unicode_normalization::UnicodeNormalization;
 
+use crate::glob::GlobPattern;
+
 const DEFAULT_MAX_FILES: usize = 10_000;
 const DEFAULT_MAX_DEPTH: usize = 5;
 const BATCH_SIZE: usize = 1000;
+const DEFAULT_WATCH_DEBOUNCE_MS: u64 = 300;
+const MIN_WATCH_DEBOUNCE_MS: u64 = 50;
 
 static DEFAULT_EXTENSIONS: &[&str] = &[
 	"txt", "md", "pdf", "doc", "docx", "xls", "xlsx", "ppt", "pptx", "csv", "json", "xml", "html", "css", "js", "ts",
@@ -34,18 +42,57 @@ fn normalize_path(path: &str) -> String {
 	}
 }
 
+/// Resolves symlinks so aliases of one file share an index key. The OS caps
+/// link chains and reports loops as an error, in which case the parent is
+/// resolved instead (the file may already be gone) and finally the raw path.
+fn canonical_path(path: &Path) -> PathBuf {
+	if let Ok(resolved) = fs::canonicalize(path) {
+		return resolved;
+	}
+	match (path.parent().and_then(|parent| fs::canonicalize(parent).ok()), path.file_name()) {
+		(Some(parent), Some(name)) => parent.join(name),
+		_ => path.to_path_buf(),
+	}
+}
+
+fn path_key(path: &Path) -> CompactString {
+	CompactString::new(normalize_path(&canonical_path(path).to_string_lossy()))
+}
+
+fn root_rank(path: &str, directories: &[PathBuf]) -> usize {
+	directories.iter().position(|dir| Path::new(path).starts_with(dir)).unwrap_or(usize::MAX)
+}
+
+fn topmost_directories(dirs: &[PathBuf]) -> Vec<&Path> {
+	let mut roots: Vec<&Path> = Vec::with_capacity(dirs.len());
+	for dir in dirs {
+		if roots.iter().any(|root| dir.starts_with(root)) {
+			continue;
+		}
+		roots.retain(|root| !root.starts_with(dir));
+		roots.push(dir);
+	}
+	roots
+}
+
 #[derive(Archive, Deserialize, Serialize, CheckBytes, Debug, Clone)]
 #[rkyv(derive(Debug))]
 pub struct FileEntry {
-	pub path: String,
-	pub name: String,
+	pub path:     String,
+	pub name:     String,
+	pub size:     u64,
+	pub modified: u64,
 }
 
 impl FileEntry {
 	fn from_path(path: &Path) -> io::Result<Self> {
+		let path = &canonical_path(path);
 		let name = path.file_name().and_then(|n| n.to_str()).ok_or_else(|| io::Error::other("invalid filename"))?;
 
-		Ok(Self { path: path.to_string_lossy().into_owned(), name: name.to_owned() })
+		let metadata = fs::metadata(path)?;
+		let modified = metadata.modified().map_or(0, unix_secs);
+
+		Ok(Self { path: path.to_string_lossy().into_owned(), name: name.to_owned(), size: metadata.len(), modified })
 	}
 
 	#[must_use]
@@ -56,45 +103,130 @@ impl FileEntry {
 
 	#[must_use]
 	pub fn normalized_key(&self) -> CompactString { CompactString::new(normalize_path(&self.path)) }
+
+	#[must_use]
+	pub fn stable_id(&self) -> CompactString {
+		compact_str::format_compact!("file:{:016x}", shared_utils::stable_hash(normalize_path(&self.path).as_str()))
+	}
+}
+
+// Directory mtimes are kept in nanoseconds, so a change within the second of
+// the last scan still counts. Files from before hold seconds, which only
+// costs one extra rescan.
+#[derive(Archive, Deserialize, Serialize, CheckBytes, Debug, Clone)]
+struct ScanTime {
+	path:  String,
+	mtime: u64,
+}
+
+fn unix_secs(time: SystemTime) -> u64 { time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()) }
+
+fn unix_nanos(time: SystemTime) -> u64 {
+	time.duration_since(UNIX_EPOCH).map_or(0, |d| u64::try_from(d.as_nanos()).unwrap_or(u64::MAX))
+}
+
+fn dir_mtime(dir: &Path) -> Option<u64> { fs::metadata(dir).and_then(|m| m.modified()).ok().map(unix_nanos) }
+
+fn record_scan_times(last_scan: &RwLock<FxHashMap<PathBuf, u64>>, directories: &[PathBuf], path: &Path) {
+	let mut times = last_scan.write();
+	for dir in directories {
+		if let Some(mtime) = dir_mtime(dir) {
+			times.insert(dir.clone(), mtime);
+		}
+	}
+	let records: Vec<ScanTime> =
+		times.iter().map(|(dir, &mtime)| ScanTime { path: dir.to_string_lossy().into_owned(), mtime }).collect();
+	drop(times);
+	let _ = save_to_disk(path, &records);
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct FileIndexerConfig {
-	pub enabled:      bool,
-	pub directories:  Vec<PathBuf>,
-	pub extensions:   Vec<String>,
-	pub max_files:    usize,
-	pub max_depth:    usize,
-	pub index_hidden: bool,
-	pub exclude_dirs: Vec<String>,
+	pub enabled:           bool,
+	pub directories:       Vec<PathBuf>,
+	pub extensions:        Vec<String>,
+	pub max_files:         usize,
+	pub max_depth:         usize,
+	pub index_hidden:      bool,
+	pub exclude_dirs:      Vec<String>,
+	#[serde(default)]
+	pub exclude_globs:     Vec<String>,
+	#[serde(default)]
+	pub respect_gitignore: bool,
+	#[serde(default = "default_watch_debounce_ms")]
+	pub watch_debounce_ms: u64,
+	#[serde(skip)]
+	compiled_excludes:     Vec<GlobPattern>,
+	/// `directories` with symlinks resolved, so they compare against the
+	/// canonical paths stored in `FileEntry`.
+	#[serde(skip)]
+	canonical_dirs:        Vec<PathBuf>,
+}
+
+const fn default_watch_debounce_ms() -> u64 { DEFAULT_WATCH_DEBOUNCE_MS }
+
+impl FileIndexerConfig {
+	#[must_use]
+	pub fn watch_debounce(&self) -> Duration { Duration::from_millis(self.watch_debounce_ms.max(MIN_WATCH_DEBOUNCE_MS)) }
+
+	fn compile(&mut self) {
+		self.compiled_excludes = self.exclude_globs.iter().map(|g| GlobPattern::new(g)).collect();
+		self.canonical_dirs = self.directories.iter().map(|dir| canonical_path(dir)).collect();
+	}
+
+	fn is_excluded(&self, name: &str, path: &Path, root: &Path) -> bool {
+		is_excluded(&self.exclude_dirs, &self.compiled_excludes, name, path, root)
+	}
+}
+
+// The root-relative path is only built when there are globs to match it.
+fn is_excluded(exclude_dirs: &[String], globs: &[GlobPattern], name: &str, path: &Path, root: &Path) -> bool {
+	if exclude_dirs.iter().any(|ex| name == ex) {
+		return true;
+	}
+	if globs.is_empty() {
+		return false;
+	}
+	let relative = path.strip_prefix(root).unwrap_or(path).to_string_lossy();
+	globs.iter().any(|glob| glob.matches(name) || glob.matches(&relative))
 }
 
 impl Default for FileIndexerConfig {
 	fn default() -> Self {
 		Self {
-			enabled:      false,
-			directories:  Vec::new(),
-			extensions:   DEFAULT_EXTENSIONS.iter().map(|s| (*s).to_owned()).collect(),
-			max_files:    DEFAULT_MAX_FILES,
-			max_depth:    DEFAULT_MAX_DEPTH,
-			index_hidden: false,
-			exclude_dirs: EXCLUDED_DIRS.iter().map(|s| (*s).to_owned()).collect(),
+			enabled:           false,
+			directories:       Vec::new(),
+			extensions:        DEFAULT_EXTENSIONS.iter().map(|s| (*s).to_owned()).collect(),
+			max_files:         DEFAULT_MAX_FILES,
+			max_depth:         DEFAULT_MAX_DEPTH,
+			index_hidden:      false,
+			exclude_dirs:      EXCLUDED_DIRS.iter().map(|s| (*s).to_owned()).collect(),
+			exclude_globs:     Vec::new(),
+			respect_gitignore: false,
+			watch_debounce_ms: DEFAULT_WATCH_DEBOUNCE_MS,
+			compiled_excludes: Vec::new(),
+			canonical_dirs:    Vec::new(),
 		}
 	}
 }
 
 type GenerationCallback = extern "C" fn(usize);
+type ScanProgressCallback = extern "C" fn(scanned: usize, total_estimate: usize);
 
 pub struct FileIndexer {
 	index:               Arc<RwLock<FxHashMap<CompactString, FileEntry>>>,
 	config:              Arc<RwLock<FileIndexerConfig>>,
 	storage_path:        PathBuf,
+	scan_times_path:     PathBuf,
 	file_count:          Arc<AtomicUsize>,
 	generation:          Arc<AtomicUsize>,
-	last_scan:           Arc<RwLock<FxHashMap<PathBuf, SystemTime>>>,
+	last_scan:           Arc<RwLock<FxHashMap<PathBuf, u64>>>,
 	needs_initial:       Arc<AtomicBool>,
-	watcher:             Arc<RwLock<Option<Debouncer<RecommendedWatcher, notify_debouncer_full::FileIdMap>>>>,
+	paused:              AtomicBool,
+	scan_cancel:         parking_lot::Mutex<Arc<AtomicBool>>,
+	watcher:             Arc<RwLock<Option<Debouncer<RecommendedWatcher, notify_debouncer_full::RecommendedCache>>>>,
 	generation_callback: Arc<parking_lot::Mutex<Option<GenerationCallback>>>,
+	progress_callback:   Arc<parking_lot::Mutex<Option<ScanProgressCallback>>>,
 }
 
 impl FileIndexer {
@@ -102,7 +234,12 @@ impl FileIndexer {
 		*self.generation_callback.lock() = callback;
 	}
 
-	pub fn new(storage_path: impl AsRef<Path>, config: FileIndexerConfig) -> io::Result<Self> {
+	pub fn set_scan_progress_callback(&self, callback: Option<ScanProgressCallback>) {
+		*self.progress_callback.lock() = callback;
+	}
+
+	pub fn new(storage_path: impl AsRef<Path>, mut config: FileIndexerConfig) -> io::Result<Self> {
+		config.compile();
 		let storage_path = storage_path.as_ref().to_path_buf();
 
 		if let Some(parent) = storage_path.parent() {
@@ -110,7 +247,7 @@ impl FileIndexer {
 		}
 
 		let index = if storage_path.exists() {
-			let entries: Vec<FileEntry> = load_from_disk(&storage_path)?;
+			let entries: Vec<FileEntry> = load_from_disk(&storage_path).unwrap_or_default();
 			let mut map = FxHashMap::with_capacity_and_hasher(entries.len(), FxBuildHasher);
 			for e in entries {
 				map.insert(e.normalized_key(), e);
@@ -121,17 +258,30 @@ impl FileIndexer {
 		};
 
 		let file_count = index.len();
+		let needs_initial = !storage_path.exists();
+
+		let scan_times_path = storage_path.with_extension("scan");
+		let last_scan: FxHashMap<PathBuf, u64> = if scan_times_path.exists() {
+			let records: Vec<ScanTime> = load_from_disk(&scan_times_path).unwrap_or_default();
+			records.into_iter().map(|r| (PathBuf::from(r.path), r.mtime)).collect()
+		} else {
+			FxHashMap::default()
+		};
 
 		Ok(Self {
 			index: Arc::new(RwLock::new(index)),
 			config: Arc::new(RwLock::new(config)),
 			storage_path,
+			scan_times_path,
 			file_count: Arc::new(AtomicUsize::new(file_count)),
 			generation: Arc::new(AtomicUsize::new(0)),
-			last_scan: Arc::new(RwLock::new(FxHashMap::default())),
-			needs_initial: Arc::new(AtomicBool::new(true)),
+			last_scan: Arc::new(RwLock::new(last_scan)),
+			needs_initial: Arc::new(AtomicBool::new(needs_initial)),
+			paused: AtomicBool::new(false),
+			scan_cancel: parking_lot::Mutex::new(Arc::new(AtomicBool::new(false))),
 			watcher: Arc::new(RwLock::new(None)),
 			generation_callback: Arc::new(parking_lot::Mutex::new(None)),
+			progress_callback: Arc::new(parking_lot::Mutex::new(None)),
 		})
 	}
 
@@ -143,14 +293,14 @@ impl FileIndexer {
 		let callback = Arc::clone(&self.generation_callback);
 
 		let (tx, rx) = unbounded();
+		let debounce = self.config.read().watch_debounce();
 
-		let debouncer =
-			new_debouncer(Duration::from_millis(300), None, move |result: notify_debouncer_full::DebounceEventResult| {
-				if let Ok(events) = result {
-					let _ = tx.send(events);
-				}
-			})
-			.map_err(|e| io::Error::other(format!("Failed to create debouncer: {e}")))?;
+		let debouncer = new_debouncer(debounce, None, move |result: notify_debouncer_full::DebounceEventResult| {
+			if let Ok(events) = result {
+				let _ = tx.send(events);
+			}
+		})
+		.map_err(|e| io::Error::other(format!("Failed to create debouncer: {e}")))?;
 
 		*self.watcher.write() = Some(debouncer);
 
@@ -166,7 +316,7 @@ impl FileIndexer {
 		let config = self.config.read();
 		let Some(ref mut watcher) = *self.watcher.write() else { return Ok(()) };
 
-		for dir in &config.directories {
+		for dir in topmost_directories(&config.canonical_dirs) {
 			if dir.exists() {
 				watcher
 					.watch(dir, RecursiveMode::Recursive)
@@ -208,8 +358,7 @@ impl FileIndexer {
 		for path in &event.event.paths {
 			match event.event.kind {
 				EventKind::Remove(_) => {
-					let path_str = path.to_string_lossy();
-					let normalized_key = CompactString::new(normalize_path(&path_str));
+					let normalized_key = path_key(path);
 					let mut idx = index.write();
 					if idx.remove(&normalized_key).is_some() {
 						file_count.store(idx.len(), Ordering::Relaxed);
@@ -221,8 +370,7 @@ impl FileIndexer {
 				}
 				EventKind::Create(_) | EventKind::Modify(_) => {
 					if !path.exists() {
-						let path_str = path.to_string_lossy();
-						let normalized_key = CompactString::new(normalize_path(&path_str));
+						let normalized_key = path_key(path);
 						let mut idx = index.write();
 						if idx.remove(&normalized_key).is_some() {
 							file_count.store(idx.len(), Ordering::Relaxed);
@@ -266,7 +414,7 @@ impl FileIndexer {
 	#[allow(clippy::significant_drop_tightening)]
 	pub fn refresh_if_needed(&self) -> bool {
 		let config = self.config.read();
-		if !config.enabled {
+		if !config.enabled || self.is_paused() {
 			return false;
 		}
 
@@ -283,7 +431,7 @@ impl FileIndexer {
 				continue;
 			}
 
-			let current_mtime = fs::metadata(dir).and_then(|m| m.modified()).ok();
+			let current_mtime = dir_mtime(dir);
 
 			match (last_scan.get(dir), current_mtime) {
 				(Some(&last), Some(current)) if current > last => {
@@ -305,37 +453,55 @@ impl FileIndexer {
 
 	#[allow(clippy::significant_drop_tightening)]
 	fn scan_all_directories(&self) -> bool {
+		let cancel = Arc::clone(&self.scan_cancel.lock());
+		let progress = *self.progress_callback.lock();
 		let config = self.config.read();
+		let roots = topmost_directories(&config.canonical_dirs);
 
-		if config.directories.len() > 1 {
+		if roots.len() > 1 {
 			use rayon::prelude::*;
-			config.directories.par_iter().filter(|dir| dir.exists()).for_each(|dir| {
-				Self::scan_directory(dir, &self.index, &config, &self.file_count);
+			roots.par_iter().filter(|dir| dir.exists()).for_each(|dir| {
+				Self::scan_directory(dir, &self.index, &config, &self.file_count, &cancel, progress);
 			});
 		} else {
-			for dir in &config.directories {
+			for dir in roots {
 				if dir.exists() {
-					Self::scan_directory(dir, &self.index, &config, &self.file_count);
+					Self::scan_directory(dir, &self.index, &config, &self.file_count, &cancel, progress);
 				}
 			}
 		}
 
+		if cancel.load(Ordering::Relaxed) {
+			return false;
+		}
+
+		record_scan_times(&self.last_scan, &config.directories, &self.scan_times_path);
 		self.generation.fetch_add(1, Ordering::Relaxed);
 		let _ = self.save();
 		true
 	}
 
 	fn scan_directory(
-		path: &Path,
+		root: &Path,
 		index: &Arc<RwLock<FxHashMap<CompactString, FileEntry>>>,
 		config: &FileIndexerConfig,
 		file_count: &Arc<AtomicUsize>,
+		cancel: &AtomicBool,
+		progress: Option<ScanProgressCallback>,
 	) {
+		if config.respect_gitignore {
+			Self::scan_gitignored(root, index, config, file_count, cancel, progress);
+			return;
+		}
+
 		let mut stack = Vec::with_capacity(256);
-		stack.push((path.to_path_buf(), 0));
+		stack.push((root.to_path_buf(), 0));
 		let mut batch = Vec::with_capacity(BATCH_SIZE);
 
 		while let Some((current, depth)) = stack.pop() {
+			if cancel.load(Ordering::Relaxed) {
+				return;
+			}
 			if depth >= config.max_depth || file_count.load(Ordering::Relaxed) >= config.max_files {
 				break;
 			}
@@ -343,13 +509,17 @@ impl FileIndexer {
 			let Ok(entries) = fs::read_dir(&current) else { continue };
 
 			for entry in entries.filter_map(Result::ok) {
+				if cancel.load(Ordering::Relaxed) {
+					return;
+				}
+
 				let path = entry.path();
 
 				if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
 					if name.starts_with('.') && !config.index_hidden {
 						continue;
 					}
-					if config.exclude_dirs.iter().any(|ex| name == ex) {
+					if config.is_excluded(name, &path, root) {
 						continue;
 					}
 				}
@@ -364,14 +534,65 @@ impl FileIndexer {
 					batch.push(file_entry);
 
 					if batch.len() >= BATCH_SIZE {
-						Self::flush_batch(&mut batch, index, file_count);
+						Self::flush_batch(&mut batch, index, file_count, &config.canonical_dirs, config.max_files, progress);
 					}
 				}
 			}
 		}
 
-		if !batch.is_empty() {
-			Self::flush_batch(&mut batch, index, file_count);
+		if !batch.is_empty() && !cancel.load(Ordering::Relaxed) {
+			Self::flush_batch(&mut batch, index, file_count, &config.canonical_dirs, config.max_files, progress);
+		}
+	}
+
+	fn scan_gitignored(
+		root: &Path,
+		index: &Arc<RwLock<FxHashMap<CompactString, FileEntry>>>,
+		config: &FileIndexerConfig,
+		file_count: &Arc<AtomicUsize>,
+		cancel: &AtomicBool,
+		progress: Option<ScanProgressCallback>,
+	) {
+		let exclude_dirs = config.exclude_dirs.clone();
+		let globs = config.compiled_excludes.clone();
+		let walk_root = root.to_path_buf();
+
+		let walker = WalkBuilder::new(root)
+			.max_depth(Some(config.max_depth))
+			.hidden(!config.index_hidden)
+			.require_git(false)
+			.filter_entry(move |entry| {
+				let Some(name) = entry.file_name().to_str() else { return true };
+				!is_excluded(&exclude_dirs, &globs, name, entry.path(), &walk_root)
+			})
+			.build();
+
+		let mut batch = Vec::with_capacity(BATCH_SIZE);
+
+		for entry in walker.filter_map(Result::ok) {
+			if cancel.load(Ordering::Relaxed) {
+				return;
+			}
+			if file_count.load(Ordering::Relaxed) + batch.len() >= config.max_files {
+				break;
+			}
+
+			let path = entry.path();
+			if entry.file_type().is_some_and(|t| t.is_file())
+				&& let Some(ext) = path.extension().and_then(|e| e.to_str())
+				&& config.extensions.iter().any(|allowed| allowed.eq_ignore_ascii_case(ext))
+				&& let Ok(file_entry) = FileEntry::from_path(path)
+			{
+				batch.push(file_entry);
+
+				if batch.len() >= BATCH_SIZE {
+					Self::flush_batch(&mut batch, index, file_count, &config.canonical_dirs, config.max_files, progress);
+				}
+			}
+		}
+
+		if !batch.is_empty() && !cancel.load(Ordering::Relaxed) {
+			Self::flush_batch(&mut batch, index, file_count, &config.canonical_dirs, config.max_files, progress);
 		}
 	}
 
@@ -379,13 +600,27 @@ impl FileIndexer {
 		batch: &mut Vec<FileEntry>,
 		index: &Arc<RwLock<FxHashMap<CompactString, FileEntry>>>,
 		file_count: &Arc<AtomicUsize>,
+		directories: &[PathBuf],
+		max_files: usize,
+		progress: Option<ScanProgressCallback>,
 	) {
 		let mut idx = index.write();
 		for entry in batch.drain(..) {
 			let key = entry.normalized_key();
-			idx.insert(key, entry);
+			match idx.get(&key) {
+				Some(existing) if root_rank(&existing.path, directories) < root_rank(&entry.path, directories) => {}
+				_ => {
+					idx.insert(key, entry);
+				}
+			}
+		}
+		let scanned = idx.len();
+		drop(idx);
+		file_count.store(scanned, Ordering::Relaxed);
+
+		if let Some(cb) = progress {
+			cb(scanned, max_files);
 		}
-		file_count.store(idx.len(), Ordering::Relaxed);
 	}
 
 	pub fn save(&self) -> io::Result<()> {
@@ -396,30 +631,39 @@ impl FileIndexer {
 	#[allow(clippy::significant_drop_tightening)]
 	pub fn start_indexing(&self) {
 		let config = self.config.read();
-		if !config.enabled {
+		if !config.enabled || self.is_paused() {
 			return;
 		}
 		drop(config);
 
+		let cancel = self.live_scan_token();
 		if self.index.read().is_empty() {
 			let index = Arc::clone(&self.index);
 			let config = Arc::clone(&self.config);
 			let file_count = Arc::clone(&self.file_count);
 			let generation = Arc::clone(&self.generation);
 			let storage_path = self.storage_path.clone();
+			let last_scan = Arc::clone(&self.last_scan);
+			let scan_times_path = self.scan_times_path.clone();
+			let progress = *self.progress_callback.lock();
 
 			std::thread::spawn(move || {
 				let cfg = config.read();
-				for dir in &cfg.directories {
+				for dir in topmost_directories(&cfg.canonical_dirs) {
 					if dir.exists() {
-						Self::scan_directory(dir, &index, &cfg, &file_count);
+						Self::scan_directory(dir, &index, &cfg, &file_count, &cancel, progress);
 					}
 				}
+				if cancel.load(Ordering::Relaxed) {
+					return;
+				}
+				record_scan_times(&last_scan, &cfg.directories, &scan_times_path);
+				drop(cfg);
 				generation.fetch_add(1, Ordering::Relaxed);
 				let entries: Vec<FileEntry> = index.read().values().cloned().collect();
 				let _ = save_to_disk(&storage_path, &entries);
 			});
-		} else {
+		} else if self.last_scan.read().is_empty() {
 			self.needs_initial.store(true, Ordering::Relaxed);
 		}
 
@@ -430,9 +674,28 @@ impl FileIndexer {
 		}
 	}
 
+	#[must_use]
+	pub fn contains(&self, path: &str) -> bool { self.index.read().contains_key(&path_key(Path::new(path))) }
+
 	#[must_use]
 	pub fn get_all_files(&self) -> Vec<FileEntry> { self.index.read().values().cloned().collect() }
 
+	pub fn for_each_file<F>(&self, f: F)
+	where
+		F: FnMut(&FileEntry),
+	{
+		self.index.read().values().for_each(f);
+	}
+
+	pub fn par_map_files<F, T>(&self, f: F) -> Vec<T>
+	where
+		F: Fn(&FileEntry) -> Option<T> + Sync + Send,
+		T: Send,
+	{
+		use rayon::prelude::*;
+		self.index.read().par_iter().filter_map(|(_, entry)| f(entry)).collect()
+	}
+
 	pub fn map_files<F, T>(&self, f: F) -> Vec<T>
 	where
 		F: FnMut(&FileEntry) -> Option<T>,
@@ -443,18 +706,19 @@ impl FileIndexer {
 	#[must_use]
 	pub fn file_count(&self) -> usize { self.file_count.load(Ordering::Relaxed) }
 
-	pub fn update_config(&self, config: FileIndexerConfig) {
+	pub fn update_config(&self, mut config: FileIndexerConfig) {
+		config.compile();
 		let needs_restart = {
 			let old_config = self.config.read();
-			old_config.directories != config.directories
+			old_config.directories != config.directories || old_config.watch_debounce_ms != config.watch_debounce_ms
 		};
 
-		*self.config.write() = config;
-
 		if needs_restart {
 			self.stop_file_watcher();
 		}
 
+		*self.config.write() = config;
+
 		self.start_indexing();
 	}
 
@@ -464,12 +728,44 @@ impl FileIndexer {
 	}
 
 	pub fn disable(&self) {
+		self.cancel_scan();
 		self.config.write().enabled = false;
 		self.stop_file_watcher();
 		let _ = self.save();
 	}
 
-	fn stop_file_watcher(&self) { *self.watcher.write() = None; }
+	pub fn pause(&self) {
+		self.paused.store(true, Ordering::Relaxed);
+		self.stop_file_watcher();
+	}
+
+	pub fn resume(&self) {
+		if self.paused.swap(false, Ordering::Relaxed) {
+			self.start_indexing();
+		}
+	}
+
+	#[must_use]
+	pub fn is_paused(&self) -> bool { self.paused.load(Ordering::Relaxed) }
+
+	fn stop_file_watcher(&self) {
+		self.cancel_scan();
+		*self.watcher.write() = None;
+	}
+
+	fn cancel_scan(&self) { self.scan_cancel.lock().store(true, Ordering::Relaxed); }
+
+	// A cancelled token stays latched until indexing starts again, at which
+	// point it is replaced; a live one is kept so an in-flight scan can still
+	// be cancelled.
+	#[allow(clippy::significant_drop_tightening)]
+	fn live_scan_token(&self) -> Arc<AtomicBool> {
+		let mut token = self.scan_cancel.lock();
+		if token.load(Ordering::Relaxed) {
+			*token = Arc::new(AtomicBool::new(false));
+		}
+		Arc::clone(&token)
+	}
 
 	#[must_use]
 	pub fn is_enabled(&self) -> bool { self.config.read().enabled }
@@ -488,3 +784,464 @@ impl FileIndexer {
 	#[must_use]
 	pub fn generation(&self) -> usize { self.generation.load(Ordering::Relaxed) }
 }
+
+#[cfg(test)]
+mod tests {
+	use tempfile::TempDir;
+
+	use super::*;
+
+	#[test]
+	fn test_topmost_directories() {
+		let dirs = vec![PathBuf::from("/Users/me/Documents"), PathBuf::from("/Users/me"), PathBuf::from("/Volumes/Data")];
+		let roots = topmost_directories(&dirs);
+		assert_eq!(roots, vec![Path::new("/Users/me"), Path::new("/Volumes/Data")]);
+	}
+
+	#[test]
+	fn test_root_rank() {
+		let dirs = vec![PathBuf::from("/Volumes/Data"), PathBuf::from("/Users/me")];
+		assert_eq!(root_rank("/Users/me/a.txt", &dirs), 1);
+		assert_eq!(root_rank("/Volumes/Data/a.txt", &dirs), 0);
+		assert_eq!(root_rank("/tmp/a.txt", &dirs), usize::MAX);
+	}
+
+	#[cfg(target_os = "macos")]
+	#[test]
+	fn test_collision_prefers_earlier_directory() {
+		let dirs = vec![PathBuf::from("/Users/Me/Docs"), PathBuf::from("/users/me/docs")];
+		let first = FileEntry {
+			path:     "/Users/Me/Docs/Plan.txt".to_owned(),
+			name:     "Plan.txt".to_owned(),
+			size:     0,
+			modified: 0,
+		};
+		let second = FileEntry {
+			path:     "/users/me/docs/plan.txt".to_owned(),
+			name:     "plan.txt".to_owned(),
+			size:     0,
+			modified: 0,
+		};
+		assert_eq!(first.normalized_key(), second.normalized_key());
+
+		for order in [[first.clone(), second.clone()], [second.clone(), first.clone()]] {
+			let index = Arc::new(RwLock::new(FxHashMap::default()));
+			let file_count = Arc::new(AtomicUsize::new(0));
+			for entry in order {
+				FileIndexer::flush_batch(&mut vec![entry], &index, &file_count, &dirs, usize::MAX, None);
+			}
+			assert_eq!(index.read().get(&first.normalized_key()).unwrap().path, first.path);
+		}
+	}
+
+	#[test]
+	fn test_stable_id() {
+		let a = FileEntry {
+			path:     "/Users/me/notes.txt".to_owned(),
+			name:     "notes.txt".to_owned(),
+			size:     0,
+			modified: 0,
+		};
+		let b = FileEntry {
+			path:     "/Users/me/todo.txt".to_owned(),
+			name:     "todo.txt".to_owned(),
+			size:     0,
+			modified: 0,
+		};
+
+		assert!(a.stable_id().starts_with("file:"));
+		let same_path = FileEntry { path: a.path.clone(), name: a.name.clone(), size: 0, modified: 0 };
+		assert_eq!(a.stable_id(), same_path.stable_id());
+		assert_ne!(a.stable_id(), b.stable_id());
+	}
+
+	#[test]
+	fn test_nested_directories_indexed_once() {
+		let root = TempDir::new().unwrap();
+		let nested = root.path().join("Documents");
+		fs::create_dir_all(&nested).unwrap();
+		fs::write(root.path().join("top.txt"), "top").unwrap();
+		fs::write(nested.join("inner.md"), "inner").unwrap();
+
+		let config = FileIndexerConfig {
+			enabled: true,
+			directories: vec![root.path().to_path_buf(), nested],
+			..FileIndexerConfig::default()
+		};
+		let indexer = FileIndexer::new(root.path().join("index.bin"), config).unwrap();
+
+		assert!(indexer.refresh_if_needed());
+		assert_eq!(indexer.file_count(), 2);
+		assert!(indexer.contains(&root.path().join("top.txt").to_string_lossy()));
+		assert!(!indexer.contains(&root.path().join("missing.txt").to_string_lossy()));
+
+		let files = indexer.get_all_files();
+		for name in ["top.txt", "inner.md"] {
+			assert_eq!(files.iter().filter(|f| f.name == name).count(), 1);
+		}
+	}
+
+	#[test]
+	fn test_cancelled_scan_leaves_index_untouched() {
+		let root = TempDir::new().unwrap();
+		fs::write(root.path().join("note.txt"), "note").unwrap();
+
+		let config =
+			FileIndexerConfig { enabled: true, directories: vec![root.path().to_path_buf()], ..FileIndexerConfig::default() };
+		let indexer = FileIndexer::new(root.path().join("index.bin"), config).unwrap();
+
+		let cancel = indexer.live_scan_token();
+		indexer.cancel_scan();
+		FileIndexer::scan_directory(
+			root.path(),
+			&indexer.index,
+			&indexer.config.read(),
+			&indexer.file_count,
+			&cancel,
+			None,
+		);
+		assert_eq!(indexer.file_count(), 0);
+		assert!(indexer.get_all_files().is_empty());
+	}
+
+	static SCAN_HALTED: AtomicBool = AtomicBool::new(false);
+	static HALT_TOKEN: parking_lot::Mutex<Option<Arc<AtomicBool>>> = parking_lot::Mutex::new(None);
+
+	// Holds the scan at its first batch until the test has cancelled it.
+	extern "C" fn wait_for_cancel(_scanned: usize, _total_estimate: usize) {
+		SCAN_HALTED.store(true, Ordering::Relaxed);
+		while !HALT_TOKEN.lock().as_ref().is_some_and(|token| token.load(Ordering::Relaxed)) {
+			std::thread::yield_now();
+		}
+	}
+
+	#[test]
+	fn test_disable_halts_initial_scan() {
+		let root = TempDir::new().unwrap();
+		for dir in 0..20 {
+			let sub = root.path().join(format!("dir{dir}"));
+			fs::create_dir_all(&sub).unwrap();
+			for file in 0..100 {
+				fs::write(sub.join(format!("file{file}.txt")), "x").unwrap();
+			}
+		}
+
+		let config =
+			FileIndexerConfig { enabled: true, directories: vec![root.path().to_path_buf()], ..FileIndexerConfig::default() };
+		let indexer = FileIndexer::new(root.path().join("index.bin"), config).unwrap();
+		indexer.set_scan_progress_callback(Some(wait_for_cancel));
+		let generation = indexer.generation();
+
+		indexer.start_indexing();
+		*HALT_TOKEN.lock() = Some(Arc::clone(&indexer.scan_cancel.lock()));
+		while !SCAN_HALTED.load(Ordering::Relaxed) {
+			std::thread::yield_now();
+		}
+		// The scan holds the config lock, so this returns only once it has stopped.
+		indexer.disable();
+
+		assert_eq!(indexer.file_count(), BATCH_SIZE);
+		assert!(indexer.file_count() < 20 * 100);
+		assert_eq!(indexer.generation(), generation);
+	}
+
+	#[test]
+	fn test_watch_debounce_config() {
+		let root = TempDir::new().unwrap();
+		let config = FileIndexerConfig { watch_debounce_ms: 1200, ..FileIndexerConfig::default() };
+		let indexer = FileIndexer::new(root.path().join("index.bin"), config).unwrap();
+		assert_eq!(indexer.config.read().watch_debounce_ms, 1200);
+		assert_eq!(indexer.config.read().watch_debounce(), Duration::from_millis(1200));
+
+		let zero = FileIndexerConfig { watch_debounce_ms: 0, ..FileIndexerConfig::default() };
+		assert_eq!(zero.watch_debounce(), Duration::from_millis(MIN_WATCH_DEBOUNCE_MS));
+
+		let parsed: FileIndexerConfig = serde_json::from_str(
+			r#"{"enabled":false,"directories":[],"extensions":[],"max_files":10,"max_depth":2,"index_hidden":false,"exclude_dirs":[]}"#,
+		)
+		.unwrap();
+		assert_eq!(parsed.watch_debounce_ms, DEFAULT_WATCH_DEBOUNCE_MS);
+	}
+
+	fn scan_with_globs(globs: &[&str]) -> Vec<String> {
+		let root = TempDir::new().unwrap();
+		fs::create_dir_all(root.path().join("src/generated/deep")).unwrap();
+		fs::create_dir_all(root.path().join("docs")).unwrap();
+		fs::write(root.path().join("keep.txt"), "").unwrap();
+		fs::write(root.path().join("draft.tmp.txt"), "").unwrap();
+		fs::write(root.path().join("src/main.rs"), "").unwrap();
+		fs::write(root.path().join("src/generated/deep/out.rs"), "").unwrap();
+		fs::write(root.path().join("docs/guide.md"), "").unwrap();
+
+		let config = FileIndexerConfig {
+			directories: vec![root.path().to_path_buf()],
+			exclude_globs: globs.iter().map(|g| (*g).to_owned()).collect(),
+			..FileIndexerConfig::default()
+		};
+		let indexer = FileIndexer::new(root.path().join("index.bin"), config).unwrap();
+		let cancel = AtomicBool::new(false);
+		FileIndexer::scan_directory(
+			root.path(),
+			&indexer.index,
+			&indexer.config.read(),
+			&indexer.file_count,
+			&cancel,
+			None,
+		);
+
+		let mut names: Vec<String> = indexer.get_all_files().into_iter().map(|f| f.name).collect();
+		names.sort();
+		names
+	}
+
+	#[test]
+	fn test_exclude_glob_by_name() {
+		assert_eq!(scan_with_globs(&["*.tmp.*"]), vec!["guide.md", "keep.txt", "main.rs", "out.rs"]);
+	}
+
+	#[test]
+	fn test_exclude_glob_by_nested_path() {
+		assert_eq!(scan_with_globs(&["src/generated/**"]), vec!["draft.tmp.txt", "guide.md", "keep.txt", "main.rs"]);
+		assert_eq!(scan_with_globs(&["**/deep"]), vec!["draft.tmp.txt", "guide.md", "keep.txt", "main.rs"]);
+		assert_eq!(scan_with_globs(&["d?cs"]), vec!["draft.tmp.txt", "keep.txt", "main.rs", "out.rs"]);
+	}
+
+	#[test]
+	fn test_glob_pattern() {
+		let glob = GlobPattern::new("**/cache/*.bin");
+		assert!(glob.matches("cache/a.bin"));
+		assert!(glob.matches("x/y/cache/a.bin"));
+		assert!(!glob.matches("cache/sub/a.bin"));
+		assert!(!GlobPattern::new("*.log").matches("dir/a.log"));
+		assert!(GlobPattern::new("**.log").matches("dir/a.log"));
+	}
+
+	#[test]
+	fn test_entry_metadata() {
+		let root = TempDir::new().unwrap();
+		let path = root.path().join("report.txt");
+		fs::write(&path, "quarterly").unwrap();
+
+		let entry = FileEntry::from_path(&path).unwrap();
+		assert_eq!(entry.size, 9);
+		assert!(entry.modified > 0);
+		assert!(entry.modified <= SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs());
+	}
+
+	#[test]
+	fn test_unreadable_index_starts_empty() {
+		let root = TempDir::new().unwrap();
+		let storage = root.path().join("index.bin");
+		fs::write(&storage, b"not an archive").unwrap();
+
+		let indexer = FileIndexer::new(&storage, FileIndexerConfig::default()).unwrap();
+		assert_eq!(indexer.file_count(), 0);
+	}
+
+	#[test]
+	fn test_respect_gitignore() {
+		let root = TempDir::new().unwrap();
+		fs::create_dir_all(root.path().join("out")).unwrap();
+		fs::create_dir_all(root.path().join("sub")).unwrap();
+		fs::write(root.path().join(".gitignore"), "secret.txt\nout/\n").unwrap();
+		fs::write(root.path().join("sub/.gitignore"), "local.md\n").unwrap();
+		fs::write(root.path().join("keep.txt"), "").unwrap();
+		fs::write(root.path().join("secret.txt"), "").unwrap();
+		fs::write(root.path().join("out/build.txt"), "").unwrap();
+		fs::write(root.path().join("sub/local.md"), "").unwrap();
+		fs::write(root.path().join("sub/notes.md"), "").unwrap();
+
+		let scan = |respect_gitignore| {
+			let config = FileIndexerConfig {
+				directories: vec![root.path().to_path_buf()],
+				respect_gitignore,
+				..FileIndexerConfig::default()
+			};
+			let indexer = FileIndexer::new(root.path().join("index.bin"), config).unwrap();
+			let cancel = AtomicBool::new(false);
+			FileIndexer::scan_directory(
+				root.path(),
+				&indexer.index,
+				&indexer.config.read(),
+				&indexer.file_count,
+				&cancel,
+				None,
+			);
+			let mut names: Vec<String> = indexer.get_all_files().into_iter().map(|f| f.name).collect();
+			names.sort();
+			names
+		};
+
+		assert_eq!(scan(true), vec!["keep.txt", "notes.md"]);
+		assert_eq!(scan(false), vec!["build.txt", "keep.txt", "local.md", "notes.md", "secret.txt"]);
+	}
+
+	static PROGRESS_CALLS: AtomicUsize = AtomicUsize::new(0);
+	static PROGRESS_SCANNED: AtomicUsize = AtomicUsize::new(0);
+
+	extern "C" fn record_progress(scanned: usize, total_estimate: usize) {
+		assert_eq!(total_estimate, DEFAULT_MAX_FILES);
+		PROGRESS_CALLS.fetch_add(1, Ordering::Relaxed);
+		PROGRESS_SCANNED.fetch_max(scanned, Ordering::Relaxed);
+	}
+
+	#[test]
+	fn test_scan_progress_callback() {
+		let root = TempDir::new().unwrap();
+		for i in 0..=BATCH_SIZE {
+			fs::write(root.path().join(format!("file{i}.txt")), "").unwrap();
+		}
+
+		let config =
+			FileIndexerConfig { enabled: true, directories: vec![root.path().to_path_buf()], ..FileIndexerConfig::default() };
+		let indexer = FileIndexer::new(root.path().join("index.bin"), config).unwrap();
+		indexer.set_scan_progress_callback(Some(record_progress));
+
+		assert!(indexer.refresh_if_needed());
+		assert!(PROGRESS_CALLS.load(Ordering::Relaxed) >= 1);
+		assert_eq!(PROGRESS_SCANNED.load(Ordering::Relaxed), BATCH_SIZE + 1);
+	}
+
+	#[test]
+	fn test_pause_keeps_index_queryable() {
+		let root = TempDir::new().unwrap();
+		let docs = root.path().join("docs");
+		fs::create_dir_all(&docs).unwrap();
+		fs::write(docs.join("note.txt"), "note").unwrap();
+
+		let config = FileIndexerConfig { enabled: true, directories: vec![docs.clone()], ..FileIndexerConfig::default() };
+		let indexer = FileIndexer::new(root.path().join("index.bin"), config).unwrap();
+		assert!(indexer.refresh_if_needed());
+		indexer.start_indexing();
+		assert!(indexer.watcher.read().is_some());
+
+		indexer.pause();
+		assert!(indexer.is_paused());
+		assert!(indexer.is_enabled());
+		assert!(indexer.watcher.read().is_none());
+		assert!(!indexer.refresh_if_needed());
+		assert_eq!(indexer.get_all_files().len(), 1);
+
+		indexer.resume();
+		assert!(!indexer.is_paused());
+		assert!(indexer.watcher.read().is_some());
+		assert_eq!(indexer.get_all_files().len(), 1);
+
+		fs::write(docs.join("later.txt"), "later").unwrap();
+		let later = SystemTime::now() + Duration::from_secs(10);
+		fs::File::open(&docs).unwrap().set_modified(later).unwrap();
+		assert!(indexer.refresh_if_needed());
+		assert!(indexer.contains(&docs.join("later.txt").to_string_lossy()));
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn test_symlinked_directory_indexed_once() {
+		let root = TempDir::new().unwrap();
+		fs::create_dir_all(root.path().join("real")).unwrap();
+		fs::write(root.path().join("real/plan.txt"), "").unwrap();
+		std::os::unix::fs::symlink(root.path().join("real"), root.path().join("alias")).unwrap();
+		std::os::unix::fs::symlink(root.path().join("loop"), root.path().join("loop")).unwrap();
+
+		let config = FileIndexerConfig { directories: vec![root.path().to_path_buf()], ..FileIndexerConfig::default() };
+		let indexer = FileIndexer::new(root.path().join("index.bin"), config).unwrap();
+		let cancel = AtomicBool::new(false);
+		FileIndexer::scan_directory(
+			root.path(),
+			&indexer.index,
+			&indexer.config.read(),
+			&indexer.file_count,
+			&cancel,
+			None,
+		);
+
+		let files = indexer.get_all_files();
+		assert_eq!(files.len(), 1);
+		assert_eq!(files[0].name, "plan.txt");
+		assert!(indexer.contains(&root.path().join("alias/plan.txt").to_string_lossy()));
+		assert!(indexer.contains(&root.path().join("real/plan.txt").to_string_lossy()));
+	}
+
+	#[test]
+	fn test_scan_times_persist_across_restart() {
+		let root = TempDir::new().unwrap();
+		let docs = root.path().join("docs");
+		fs::create_dir_all(&docs).unwrap();
+		fs::write(docs.join("note.txt"), "note").unwrap();
+		let storage = root.path().join("index.bin");
+		let config =
+			|| FileIndexerConfig { enabled: true, directories: vec![docs.clone()], ..FileIndexerConfig::default() };
+
+		let indexer = FileIndexer::new(&storage, config()).unwrap();
+		assert!(indexer.refresh_if_needed());
+		assert!(!indexer.refresh_if_needed());
+		drop(indexer);
+
+		let restarted = FileIndexer::new(&storage, config()).unwrap();
+		assert_eq!(restarted.file_count(), 1);
+		assert!(!restarted.refresh_if_needed());
+
+		let later = SystemTime::now() + Duration::from_secs(10);
+		fs::File::open(&docs).unwrap().set_modified(later).unwrap();
+		assert!(restarted.refresh_if_needed());
+	}
+
+	#[test]
+	fn test_refresh_sees_change_within_the_same_second() {
+		let root = TempDir::new().unwrap();
+		let docs = root.path().join("docs");
+		fs::create_dir_all(&docs).unwrap();
+		let second = UNIX_EPOCH + Duration::from_secs(SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs());
+		fs::File::open(&docs).unwrap().set_modified(second + Duration::from_millis(100)).unwrap();
+
+		let config = FileIndexerConfig { enabled: true, directories: vec![docs.clone()], ..FileIndexerConfig::default() };
+		let indexer = FileIndexer::new(root.path().join("index.bin"), config).unwrap();
+		assert!(indexer.refresh_if_needed());
+		assert!(!indexer.refresh_if_needed());
+
+		fs::write(docs.join("later.txt"), "later").unwrap();
+		fs::File::open(&docs).unwrap().set_modified(second + Duration::from_millis(600)).unwrap();
+		assert!(indexer.refresh_if_needed());
+		assert!(indexer.contains(&docs.join("later.txt").to_string_lossy()));
+	}
+
+	#[test]
+	fn test_refresh_after_disable_and_enable() {
+		let root = TempDir::new().unwrap();
+		let docs = root.path().join("docs");
+		fs::create_dir_all(&docs).unwrap();
+		fs::write(docs.join("note.txt"), "note").unwrap();
+
+		let config = FileIndexerConfig { enabled: true, directories: vec![docs.clone()], ..FileIndexerConfig::default() };
+		let indexer = FileIndexer::new(root.path().join("index.bin"), config).unwrap();
+		assert!(indexer.refresh_if_needed());
+
+		indexer.disable();
+		indexer.enable();
+
+		fs::write(docs.join("later.txt"), "later").unwrap();
+		let later = SystemTime::now() + Duration::from_secs(10);
+		fs::File::open(&docs).unwrap().set_modified(later).unwrap();
+		assert!(indexer.refresh_if_needed());
+		assert!(indexer.contains(&docs.join("later.txt").to_string_lossy()));
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn test_symlinked_root_ranks_by_canonical_path() {
+		let root = TempDir::new().unwrap();
+		let real = root.path().join("real");
+		fs::create_dir_all(real.join("docs")).unwrap();
+		fs::write(real.join("docs/plan.txt"), "").unwrap();
+		let alias = root.path().join("alias");
+		std::os::unix::fs::symlink(real.join("docs"), &alias).unwrap();
+
+		let config = FileIndexerConfig { directories: vec![alias.clone(), real.clone()], ..FileIndexerConfig::default() };
+		let indexer = FileIndexer::new(root.path().join("index.bin"), config).unwrap();
+		let dirs = indexer.config.read().canonical_dirs.clone();
+
+		let entry = FileEntry::from_path(&alias.join("plan.txt")).unwrap();
+		assert_eq!(root_rank(&entry.path, &dirs), 0);
+		assert_eq!(topmost_directories(&dirs), vec![canonical_path(&real).as_path()]);
+	}
+}
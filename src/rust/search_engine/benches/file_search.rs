@@ -0,0 +1,81 @@
+use std::{fs, sync::Arc};
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use file_indexer::{FileEntry, FileIndexer, FileIndexerConfig};
+use search_engine::{fuzzy_matcher::FuzzyMatcher, indexer::{IndexedItem, ItemType}};
+use tempfile::TempDir;
+
+const DIRS: usize = 12;
+const FILES_PER_DIR: usize = 1_000;
+const QUERY: &str = "report 42";
+
+fn build_index(root: &TempDir) -> FileIndexer {
+	for dir in 0..DIRS {
+		let sub = root.path().join(format!("project{dir}"));
+		fs::create_dir_all(&sub).unwrap();
+		for file in 0..FILES_PER_DIR {
+			fs::write(sub.join(format!("report-{file}.txt")), "").unwrap();
+		}
+	}
+
+	let mut config = FileIndexerConfig::default();
+	config.enabled = true;
+	config.directories = vec![root.path().to_path_buf()];
+	config.max_files = DIRS * FILES_PER_DIR * 2;
+	let indexer = FileIndexer::new(root.path().join("index.bin"), config).unwrap();
+	assert!(indexer.refresh_if_needed());
+	assert_eq!(indexer.file_count(), DIRS * FILES_PER_DIR);
+	indexer
+}
+
+fn file_item(entry: &FileEntry) -> IndexedItem {
+	let path = entry.path_compact();
+	IndexedItem {
+		id:        path.clone(),
+		name:      entry.name_compact(),
+		item_type: ItemType::File,
+		path:      Some(path),
+		metadata:  None,
+		aliases:   Vec::new(),
+	}
+}
+
+// `clone_all` is how file search used to work: clone the whole index, then
+// build an item per file before matching. `in_place` matches names under the
+// read lock and builds items only for hits.
+fn bench_file_search(c: &mut Criterion) {
+	let root = TempDir::new().unwrap();
+	let indexer = build_index(&root);
+	let matcher = FuzzyMatcher::new();
+	let pattern = matcher.pattern_for(QUERY);
+
+	let mut group = c.benchmark_group("file_search");
+	group.bench_function("clone_all", |b| {
+		b.iter(|| {
+			let entries = indexer.get_all_files();
+			entries
+				.iter()
+				.filter_map(|entry| {
+					let item = file_item(entry);
+					let (score, _) = matcher.match_with_pattern(&pattern, &item.name, QUERY)?;
+					Some((Arc::new(item), score))
+				})
+				.count()
+		});
+	});
+	group.bench_function("in_place", |b| {
+		b.iter(|| {
+			let mut hits = Vec::new();
+			indexer.for_each_file(|entry| {
+				if let Some((score, _)) = matcher.match_with_pattern(&pattern, &entry.name, QUERY) {
+					hits.push((Arc::new(file_item(entry)), score));
+				}
+			});
+			hits.len()
+		});
+	});
+	group.finish();
+}
+
+criterion_group!(benches, bench_file_search);
+criterion_main!(benches);
@@ -86,6 +86,7 @@ impl FileScanner {
 					item_type: ItemType::File,
 					path:      Some(path_compact),
 					metadata:  Some(metadata),
+					aliases:   Vec::new(),
 				})
 			})
 			.collect()
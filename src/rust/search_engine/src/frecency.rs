@@ -0,0 +1,120 @@
+use std::{io, path::Path, time::{SystemTime, UNIX_EPOCH}};
+
+use bytecheck::CheckBytes;
+use compact_str::CompactString;
+use parking_lot::RwLock;
+use rkyv::{Archive, Deserialize, Serialize};
+use rustc_hash::FxHashMap;
+use storage_utils::RkyvStorage;
+
+/// Seconds after which a launch counts for half as much; a week keeps daily
+/// habits on top without letting an app opened heavily last month crowd out
+/// today's.
+pub const HALF_LIFE_SECS: f64 = 7.0 * 24.0 * 60.0 * 60.0;
+
+const FRECENCY_WEIGHT: f64 = 1000.0;
+
+#[derive(Archive, Deserialize, Serialize, CheckBytes, Clone, Debug)]
+#[rkyv(derive(Debug))]
+pub struct LaunchRecord {
+	pub id:          String,
+	pub count:       u32,
+	pub last_launch: i64,
+}
+
+#[derive(Default)]
+pub struct FrecencyTracker {
+	records: RwLock<FxHashMap<CompactString, (u32, i64)>>,
+	storage: Option<RkyvStorage<LaunchRecord>>,
+}
+
+impl FrecencyTracker {
+	#[must_use]
+	pub fn new() -> Self { Self::default() }
+
+	pub fn with_path(path: impl AsRef<Path>) -> io::Result<Self> {
+		let storage = RkyvStorage::<LaunchRecord>::new(path)?;
+		let records = storage.get_all().iter().map(|r| (CompactString::new(&r.id), (r.count, r.last_launch))).collect();
+		Ok(Self { records: RwLock::new(records), storage: Some(storage) })
+	}
+
+	#[must_use]
+	pub fn now() -> i64 {
+		SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| i64::try_from(d.as_secs()).unwrap_or(i64::MAX))
+	}
+
+	pub fn record(&self, id: &str, now: i64) {
+		let (count, last_launch) = {
+			let mut records = self.records.write();
+			let entry = records.entry(CompactString::new(id)).or_insert((0, now));
+			entry.0 = entry.0.saturating_add(1);
+			entry.1 = now;
+			*entry
+		};
+
+		if let Some(ref storage) = self.storage {
+			let id = id.to_owned();
+			storage.update_async(move |records| {
+				match records.iter_mut().find(|r| r.id == id) {
+					Some(record) => {
+						record.count = count;
+						record.last_launch = last_launch;
+					}
+					None => records.push(LaunchRecord { id: id.clone(), count, last_launch }),
+				}
+				true
+			});
+		}
+	}
+
+	#[must_use]
+	pub fn is_empty(&self) -> bool { self.records.read().is_empty() }
+
+	#[must_use]
+	#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+	pub fn bonus(&self, id: &str, now: i64) -> i64 {
+		let Some(&(count, last_launch)) = self.records.read().get(id) else {
+			return 0;
+		};
+		let age = now.saturating_sub(last_launch).max(0) as f64;
+		let decay = 0.5f64.powf(age / HALF_LIFE_SECS);
+		(FRECENCY_WEIGHT * f64::from(count).ln_1p() * decay).round() as i64
+	}
+
+	pub fn flush(&self) {
+		if let Some(ref storage) = self.storage {
+			storage.flush();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use tempfile::NamedTempFile;
+
+	use super::*;
+
+	#[test]
+	fn test_bonus_decays_with_age() {
+		let tracker = FrecencyTracker::new();
+		tracker.record("safari", 0);
+		tracker.record("safari", 0);
+
+		let fresh = tracker.bonus("safari", 0);
+		assert!(fresh > 0);
+		assert!((tracker.bonus("safari", HALF_LIFE_SECS as i64) - fresh / 2).abs() <= 1);
+		assert_eq!(tracker.bonus("notes", 0), 0);
+	}
+
+	#[test]
+	fn test_records_persist() {
+		let temp = NamedTempFile::new().unwrap();
+		let tracker = FrecencyTracker::with_path(temp.path()).unwrap();
+		tracker.record("safari", 100);
+		tracker.record("safari", 200);
+		tracker.flush();
+
+		let reloaded = FrecencyTracker::with_path(temp.path()).unwrap();
+		assert_eq!(reloaded.bonus("safari", 200), tracker.bonus("safari", 200));
+	}
+}
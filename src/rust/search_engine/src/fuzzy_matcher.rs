@@ -1,27 +1,148 @@
+use std::borrow::Cow;
+
 pub use nucleo_matcher::pattern::Pattern as FuzzyPattern;
 use nucleo_matcher::{Config, Matcher, Utf32String, pattern::{CaseMatching, Normalization, Pattern}};
 use parking_lot::Mutex;
+use serde::Deserialize;
 use smallvec::SmallVec;
+use unicode_normalization::{UnicodeNormalization, char::{decompose_canonical, is_combining_mark}};
+
+#[must_use]
+pub fn fold_diacritics(text: &str) -> Cow<'_, str> {
+	if text.is_ascii() {
+		return Cow::Borrowed(text);
+	}
+	Cow::Owned(text.nfd().filter(|c| !is_combining_mark(*c)).collect())
+}
+
+fn fold_with_char_map(text: &str) -> (String, Vec<usize>) {
+	let mut folded = String::with_capacity(text.len());
+	let mut char_map = Vec::with_capacity(text.len());
+	for (idx, c) in text.chars().enumerate() {
+		decompose_canonical(c, |d| {
+			if !is_combining_mark(d) {
+				folded.push(d);
+				char_map.push(idx);
+			}
+		});
+	}
+	(folded, char_map)
+}
+
+fn eq_ignore_case(a: &str, b: &str) -> bool {
+	if a.is_ascii() && b.is_ascii() {
+		return a.eq_ignore_ascii_case(b);
+	}
+	a.chars().flat_map(char::to_lowercase).eq(b.chars().flat_map(char::to_lowercase))
+}
+
+fn starts_with_ignore_case(text: &str, prefix: &str) -> bool {
+	// Use byte-level ASCII case-insensitive prefix check to avoid allocations
+	if text.is_ascii() && prefix.is_ascii() {
+		return text.len() >= prefix.len()
+			&& text.as_bytes()[..prefix.len()].iter().zip(prefix.as_bytes()).all(|(a, b)| a.eq_ignore_ascii_case(b));
+	}
+	let mut chars = text.chars().flat_map(char::to_lowercase);
+	prefix.chars().flat_map(char::to_lowercase).all(|p| chars.next() == Some(p))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ScoringWeights {
+	pub exact_match:          i64,
+	pub prefix:               i64,
+	pub first_char:           i64,
+	pub consecutive_bonus:    i64,
+	pub consecutive_exponent: f64,
+	pub length_penalty:       i64,
+}
+
+impl Default for ScoringWeights {
+	fn default() -> Self {
+		Self {
+			exact_match:          10000,
+			prefix:               5000,
+			first_char:           2000,
+			consecutive_bonus:    100,
+			consecutive_exponent: 1.0,
+			length_penalty:       10,
+		}
+	}
+}
+
+impl ScoringWeights {
+	#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+	fn run_bonus(&self, run: u32) -> i64 {
+		if run == 0 {
+			return 0;
+		}
+		(self.consecutive_bonus as f64 * f64::from(run).powf(self.consecutive_exponent)).round() as i64
+	}
+}
 
 pub struct FuzzyMatcher {
-	matcher:     Mutex<Matcher>,
-	indices_buf: Mutex<Vec<u32>>,
+	matcher:         Mutex<Matcher>,
+	indices_buf:     Mutex<Vec<u32>>,
+	fold_diacritics: bool,
+	weights:         ScoringWeights,
 }
 
 impl FuzzyMatcher {
 	#[inline]
 	#[must_use]
-	pub fn new() -> Self {
-		Self { matcher: Mutex::new(Matcher::new(Config::DEFAULT)), indices_buf: Mutex::new(Vec::with_capacity(64)) }
+	pub fn new() -> Self { Self::with_fold_diacritics(false) }
+
+	#[inline]
+	#[must_use]
+	pub fn with_fold_diacritics(fold_diacritics: bool) -> Self {
+		Self {
+			matcher: Mutex::new(Matcher::new(Config::DEFAULT)),
+			indices_buf: Mutex::new(Vec::with_capacity(64)),
+			fold_diacritics,
+			weights: ScoringWeights::default(),
+		}
+	}
+
+	#[inline]
+	#[must_use]
+	pub fn with_weights(weights: ScoringWeights) -> Self {
+		let mut matcher = Self::new();
+		matcher.weights = weights;
+		matcher
 	}
 
+	#[must_use]
+	pub fn fork(&self) -> Self {
+		let mut matcher = Self::with_fold_diacritics(self.fold_diacritics);
+		matcher.weights = self.weights;
+		matcher
+	}
+
+	pub const fn set_weights(&mut self, weights: ScoringWeights) { self.weights = weights; }
+
+	#[inline]
+	#[must_use]
+	pub const fn weights(&self) -> ScoringWeights { self.weights }
+
+	pub fn set_fold_diacritics(&mut self, enabled: bool) { self.fold_diacritics = enabled; }
+
+	#[inline]
+	#[must_use]
+	pub const fn folds_diacritics(&self) -> bool { self.fold_diacritics }
+
 	#[inline]
 	#[must_use]
 	pub fn parse_pattern(query: &str) -> Pattern { Pattern::parse(query, CaseMatching::Smart, Normalization::Smart) }
 
+	#[inline]
+	#[must_use]
+	pub fn pattern_for(&self, query: &str) -> Pattern {
+		if self.fold_diacritics { Self::parse_pattern(&fold_diacritics(query)) } else { Self::parse_pattern(query) }
+	}
+
 	#[inline]
 	pub fn match_with_indices(&self, candidate: &str, query: &str) -> Option<(i64, SmallVec<[usize; 8]>)> {
-		let pattern = Self::parse_pattern(query);
+		let pattern = self.pattern_for(query);
 		self.match_with_pattern(&pattern, candidate, query)
 	}
 
@@ -32,17 +153,27 @@ impl FuzzyMatcher {
 		candidate: &str,
 		query: &str,
 	) -> Option<(i64, SmallVec<[usize; 8]>)> {
-		let haystack = Utf32String::from(candidate);
+		let (candidate, query, char_map) = if self.fold_diacritics && !candidate.is_ascii() {
+			let (folded, char_map) = fold_with_char_map(candidate);
+			(Cow::Owned(folded), fold_diacritics(query), Some(char_map))
+		} else if self.fold_diacritics {
+			(Cow::Borrowed(candidate), fold_diacritics(query), None)
+		} else {
+			(Cow::Borrowed(candidate), Cow::Borrowed(query), None)
+		};
+
+		let haystack = Utf32String::from(candidate.as_ref());
 		let mut indices_buf = self.indices_buf.lock();
 		indices_buf.clear();
 
 		let score = pattern.indices(haystack.slice(..), &mut self.matcher.lock(), &mut indices_buf)?;
 
-		let bonus_score = Self::calculate_bonus(candidate, query, i64::from(score), &indices_buf);
+		let bonus_score = self.calculate_bonus(&candidate, &query, i64::from(score), &indices_buf);
 
 		let mut result_indices = SmallVec::with_capacity(indices_buf.len());
 		for &idx in indices_buf.iter() {
-			result_indices.push(idx as usize);
+			let idx = idx as usize;
+			result_indices.push(char_map.as_ref().and_then(|map| map.get(idx).copied()).unwrap_or(idx));
 		}
 
 		Some((bonus_score, result_indices))
@@ -50,35 +181,36 @@ impl FuzzyMatcher {
 
 	#[inline]
 	#[allow(clippy::cast_possible_wrap)]
-	fn calculate_bonus(candidate: &str, query: &str, base_score: i64, indices: &[u32]) -> i64 {
+	fn calculate_bonus(&self, candidate: &str, query: &str, base_score: i64, indices: &[u32]) -> i64 {
 		let mut bonus = 0i64;
 
-		if candidate.eq_ignore_ascii_case(query) {
-			bonus += 10000;
+		let (folded_candidate, folded_query) = (fold_diacritics(candidate), fold_diacritics(query));
+		if eq_ignore_case(&folded_candidate, &folded_query) {
+			bonus += self.weights.exact_match;
 		}
 
-		// Use byte-level ASCII case-insensitive prefix check to avoid allocations
-		if candidate.len() >= query.len()
-			&& candidate.as_bytes()[..query.len()].iter().zip(query.as_bytes()).all(|(a, b)| a.eq_ignore_ascii_case(b))
-		{
-			bonus += 5000;
+		if starts_with_ignore_case(&folded_candidate, &folded_query) {
+			bonus += self.weights.prefix;
 		}
 
 		if let Some(&first_idx) = indices.first()
 			&& first_idx == 0
 		{
-			bonus += 2000;
+			bonus += self.weights.first_char;
 		}
 
-		let mut consecutive = 0;
+		let mut run = 0;
 		for window in indices.windows(2) {
 			if window[1] == window[0] + 1 {
-				consecutive += 1;
+				run += 1;
+			} else {
+				bonus += self.weights.run_bonus(run);
+				run = 0;
 			}
 		}
-		bonus += consecutive * 100;
+		bonus += self.weights.run_bonus(run);
 
-		let length_penalty = (candidate.len() as i64).saturating_sub(query.len() as i64) * 10;
+		let length_penalty = (candidate.len() as i64).saturating_sub(query.len() as i64) * self.weights.length_penalty;
 
 		base_score + bonus - length_penalty
 	}
@@ -138,4 +270,74 @@ mod tests {
 		assert!(score2.is_some());
 		assert!(score3.is_some());
 	}
+
+	#[test]
+	fn test_fold_diacritics() {
+		assert_eq!(fold_diacritics("Café"), "Cafe");
+		assert_eq!(fold_diacritics("Résumé.pdf"), "Resume.pdf");
+		assert!(matches!(fold_diacritics("Safari"), Cow::Borrowed(_)));
+	}
+
+	#[test]
+	fn test_ascii_query_matches_accented_candidate() {
+		let matcher = FuzzyMatcher::with_fold_diacritics(true);
+		assert!(matcher.fuzzy_match("Café", "cafe").is_some());
+		assert!(matcher.fuzzy_match("Résumé.pdf", "resume").is_some());
+		assert!(matcher.fuzzy_match("Ångström Viewer", "angstrom").is_some());
+	}
+
+	#[test]
+	fn test_consecutive_exponent_rewards_long_runs() {
+		let mut matcher = FuzzyMatcher::new();
+		let contiguous = [0, 1, 2, 3, 4, 5, 6];
+		let scattered = [0, 1, 3, 4, 6, 7, 9, 10, 12, 13, 15];
+
+		let long = matcher.calculate_bonus("abcdefghijklmnop", "abcdefg", 50, &contiguous);
+		let split = matcher.calculate_bonus("abcdefghijklmnop", "abcdefg", 50, &scattered);
+		assert_eq!(long, split + 100);
+
+		matcher.set_weights(ScoringWeights { consecutive_exponent: 1.5, ..ScoringWeights::default() });
+		let long = matcher.calculate_bonus("abcdefghijklmnop", "abcdefg", 50, &contiguous);
+		let split = matcher.calculate_bonus("abcdefghijklmnop", "abcdefg", 50, &scattered);
+		assert!(long > split + 500);
+	}
+
+	#[test]
+	fn test_prefix_weight_reorders_candidates() {
+		let baseline = ScoringWeights { prefix: 0, first_char: 0, ..ScoringWeights::default() };
+		let matcher = FuzzyMatcher::with_weights(baseline);
+		assert!(matcher.fuzzy_match("Google Chrome", "chrome") > matcher.fuzzy_match("Chrome Remote Desktop", "chrome"));
+
+		let matcher = FuzzyMatcher::with_weights(ScoringWeights { prefix: 5000, ..baseline });
+		assert!(matcher.fuzzy_match("Chrome Remote Desktop", "chrome") > matcher.fuzzy_match("Google Chrome", "chrome"));
+	}
+
+	#[test]
+	fn test_accented_candidates_get_prefix_and_exact_bonus() {
+		let matcher = FuzzyMatcher::new();
+		let exact = matcher.fuzzy_match("Café", "cafe").unwrap();
+		let prefix = matcher.fuzzy_match("Café Menu", "cafe").unwrap();
+		assert!(exact > 10000);
+		assert!(prefix > 5000 && prefix < exact);
+
+		let (score, indices) = matcher.match_with_indices("Привет Мир", "прив").unwrap();
+		assert!(score > 5000);
+		assert_eq!(indices.as_slice(), [0, 1, 2, 3]);
+	}
+
+	#[test]
+	fn test_folded_indices_point_into_original() {
+		let matcher = FuzzyMatcher::with_fold_diacritics(true);
+		let decomposed = "U\u{308}ber Mu\u{308}nchen";
+
+		let (score, indices) = matcher.match_with_indices(decomposed, "uber").unwrap();
+		assert!(score > 5000);
+		assert_eq!(indices.as_slice(), [0, 2, 3, 4]);
+
+		let (_, indices) = matcher.match_with_indices(decomposed, "munchen").unwrap();
+		let chars: Vec<char> = decomposed.chars().collect();
+		let matched: String = indices.iter().map(|&i| chars[i]).collect();
+		assert_eq!(matched, "Munchen");
+		assert_eq!(indices.as_slice(), [6, 7, 9, 10, 11, 12, 13]);
+	}
 }
@@ -20,6 +20,8 @@ pub struct IndexedItem {
 	pub item_type: ItemType,
 	pub path:      Option<CompactString>,
 	pub metadata:  Option<FxHashMap<CompactString, CompactString>>,
+	#[serde(default)]
+	pub aliases:   Vec<CompactString>,
 }
 
 pub struct Indexer {
@@ -118,6 +120,9 @@ impl Indexer {
 	#[inline]
 	pub fn items_iter(&self) -> impl Iterator<Item = &Arc<IndexedItem>> + '_ { self.items.values() }
 
+	#[must_use]
+	pub fn snapshot(&self) -> Vec<Arc<IndexedItem>> { self.items.values().cloned().collect() }
+
 	#[inline]
 	#[must_use]
 	pub fn get_item(&self, id: &str) -> Option<IndexedItem> { self.items.get(id).map(|arc| (**arc).clone()) }
@@ -157,6 +162,7 @@ mod tests {
 			item_type: ItemType::Application,
 			path:      Some("/Applications/Test.app".into()),
 			metadata:  None,
+			aliases:   Vec::new(),
 		};
 
 		indexer.add_item(item);
@@ -172,6 +178,7 @@ mod tests {
 			item_type: ItemType::Application,
 			path:      None,
 			metadata:  None,
+			aliases:   Vec::new(),
 		};
 
 		indexer.add_item(item);
@@ -189,6 +196,7 @@ mod tests {
 			item_type: ItemType::Application,
 			path:      None,
 			metadata:  None,
+			aliases:   Vec::new(),
 		});
 
 		indexer.add_item(IndexedItem {
@@ -197,6 +205,7 @@ mod tests {
 			item_type: ItemType::File,
 			path:      None,
 			metadata:  None,
+			aliases:   Vec::new(),
 		});
 
 		let (total, apps, files, _) = indexer.stats();
@@ -1,13 +1,15 @@
 pub mod file_scanner;
+pub mod frecency;
 pub mod fuzzy_matcher;
 pub mod indexer;
 
-use std::{cmp::Reverse, collections::BinaryHeap, fmt, io, num::NonZeroUsize, sync::{Arc, atomic::{AtomicUsize, Ordering}}};
+use std::{cmp::Reverse, collections::{BinaryHeap, VecDeque}, fmt, io, num::NonZeroUsize, sync::{Arc, atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering}}, time::{Duration, Instant}};
 
 use compact_str::CompactString;
 use lru::LruCache;
 use parking_lot::RwLock;
 use rayon::prelude::*;
+use rustc_hash::{FxHashMap, FxHashSet};
 use smallvec::SmallVec;
 
 #[derive(Debug)]
@@ -40,12 +42,23 @@ const CACHE_SIZE_NZ: NonZeroUsize = NonZeroUsize::new(CACHE_SIZE).unwrap();
 const PARALLEL_THRESHOLD: usize = 500;
 const SMALL_VEC_SIZE: usize = 64;
 const HEAP_THRESHOLD: usize = 100;
+const DEADLINE_CHECK_INTERVAL: usize = 256;
+const CACHE_INVALIDATION_DEBOUNCE: Duration = Duration::from_millis(100);
+const DEFAULT_RECENT_CAPACITY: usize = 20;
+const DEFAULT_RESULT_LIMIT: usize = 7;
+const PINNED_BONUS: i64 = 1_000_000;
 
-type ResultCache = Arc<RwLock<LruCache<CompactString, Arc<Vec<SearchResult>>>>>;
+type ResultCache = Arc<RwLock<LruCache<CompactString, Arc<CachedSearch>>>>;
 type IndicesVec = SmallVec<[usize; 8]>;
 type MatchTuple = (Arc<indexer::IndexedItem>, i64, IndicesVec);
 type MatchVec = SmallVec<[MatchTuple; SMALL_VEC_SIZE]>;
 
+struct CachedSearch {
+	results:  Vec<SearchResult>,
+	total:    usize,
+	complete: bool,
+}
+
 struct HeapItem(Arc<indexer::IndexedItem>, i64, IndicesVec);
 
 impl Eq for HeapItem {}
@@ -64,6 +77,154 @@ impl PartialOrd for HeapItem {
 	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> { Some(self.cmp(other)) }
 }
 
+struct Deadline {
+	at:      Option<Instant>,
+	polls:   AtomicUsize,
+	expired: AtomicBool,
+}
+
+impl Deadline {
+	fn new(budget: Option<Duration>) -> Self {
+		Self { at: budget.map(|b| Instant::now() + b), polls: AtomicUsize::new(0), expired: AtomicBool::new(false) }
+	}
+
+	#[inline]
+	fn poll(&self) -> bool {
+		let Some(at) = self.at else { return false };
+		if self.expired.load(Ordering::Relaxed) {
+			return true;
+		}
+		if self.polls.fetch_add(1, Ordering::Relaxed).is_multiple_of(DEADLINE_CHECK_INTERVAL) && Instant::now() >= at {
+			self.expired.store(true, Ordering::Relaxed);
+			return true;
+		}
+		false
+	}
+
+	fn is_expired(&self) -> bool { self.expired.load(Ordering::Relaxed) }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeFilter {
+	All,
+	Application,
+	File,
+	Snippet,
+	Command,
+}
+
+impl TypeFilter {
+	fn from_kind(kind: &str) -> Option<Self> {
+		match kind.to_ascii_lowercase().as_str() {
+			"all" => Some(Self::All),
+			"app" | "apps" | "application" | "applications" => Some(Self::Application),
+			"file" | "files" => Some(Self::File),
+			"snippet" | "snippets" => Some(Self::Snippet),
+			"command" | "commands" | "cmd" => Some(Self::Command),
+			_ => None,
+		}
+	}
+
+	#[must_use]
+	pub fn parse(query: &str) -> (Option<Self>, &str) {
+		let mut filter = None;
+		let mut rest = query.trim_start();
+		loop {
+			let (token, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+			let Some(kind) = token.get(..5).filter(|p| p.eq_ignore_ascii_case("type:")).and_then(|_| token.get(5..)) else {
+				break;
+			};
+			let Some(parsed) = Self::from_kind(kind) else { break };
+			filter = Some(parsed);
+			rest = tail.trim_start();
+		}
+		(filter, rest)
+	}
+
+	#[must_use]
+	pub fn matches(self, item_type: &indexer::ItemType) -> bool {
+		match self {
+			Self::All => true,
+			Self::Application => *item_type == indexer::ItemType::Application,
+			Self::File => *item_type == indexer::ItemType::File,
+			Self::Snippet => *item_type == indexer::ItemType::Snippet,
+			Self::Command => matches!(item_type, indexer::ItemType::Custom(_)),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DedupKey {
+	#[default]
+	Path,
+	NameAndPath,
+}
+
+#[derive(Debug, Clone)]
+pub struct DedupConfig {
+	pub key:             DedupKey,
+	pub type_preference: Vec<indexer::ItemType>,
+}
+
+impl Default for DedupConfig {
+	fn default() -> Self {
+		Self {
+			key:             DedupKey::default(),
+			type_preference: vec![indexer::ItemType::Application, indexer::ItemType::File],
+		}
+	}
+}
+
+impl DedupConfig {
+	fn type_rank(&self, item_type: &indexer::ItemType) -> usize {
+		self.type_preference.iter().position(|t| t == item_type).unwrap_or(self.type_preference.len())
+	}
+
+	fn key_for(&self, item: &indexer::IndexedItem) -> Option<(Option<CompactString>, CompactString)> {
+		let path = item.path.clone()?;
+		match self.key {
+			DedupKey::Path => Some((None, path)),
+			DedupKey::NameAndPath => Some((Some(item.name.clone()), path)),
+		}
+	}
+
+	fn apply(&self, results: Vec<SearchResult>) -> Vec<SearchResult> {
+		let mut seen: FxHashMap<(Option<CompactString>, CompactString), usize> = FxHashMap::default();
+		let mut kept: Vec<SearchResult> = Vec::with_capacity(results.len());
+		let mut replaced = false;
+
+		for result in results {
+			let Some(key) = self.key_for(&result.item) else {
+				kept.push(result);
+				continue;
+			};
+			match seen.get(&key) {
+				Some(&idx) => {
+					let existing = &kept[idx];
+					let better = self
+						.type_rank(&result.item.item_type)
+						.cmp(&self.type_rank(&existing.item.item_type))
+						.then_with(|| existing.score.cmp(&result.score))
+						.is_lt();
+					if better {
+						kept[idx] = result;
+						replaced = true;
+					}
+				}
+				None => {
+					seen.insert(key, kept.len());
+					kept.push(result);
+				}
+			}
+		}
+
+		if replaced {
+			kept.sort_unstable_by(|a, b| b.score.cmp(&a.score).then_with(|| a.item.name.cmp(&b.item.name)));
+		}
+		kept
+	}
+}
+
 pub struct SearchEngine {
 	indexer:                 Arc<RwLock<indexer::Indexer>>,
 	matcher:                 fuzzy_matcher::FuzzyMatcher,
@@ -71,6 +232,18 @@ pub struct SearchEngine {
 	file_scanner:            Option<Arc<RwLock<file_scanner::FileScanner>>>,
 	file_indexer:            Option<Arc<file_indexer::FileIndexer>>,
 	file_indexer_generation: AtomicUsize,
+	last_invalidation:       parking_lot::Mutex<Option<Instant>>,
+	stable_file_ids:         bool,
+	recent:                  parking_lot::Mutex<VecDeque<CompactString>>,
+	recent_capacity:         usize,
+	default_limit:           usize,
+	dedup:                   Option<DedupConfig>,
+	frecency:                frecency::FrecencyTracker,
+	match_paths:             bool,
+	cache_hits:              AtomicU64,
+	cache_misses:            AtomicU64,
+	pinned:                  RwLock<FxHashSet<CompactString>>,
+	pinned_storage:          Option<storage_utils::RkyvStorage<String>>,
 }
 
 impl SearchEngine {
@@ -83,9 +256,28 @@ impl SearchEngine {
 			file_scanner:            None,
 			file_indexer:            None,
 			file_indexer_generation: AtomicUsize::new(0),
+			last_invalidation:       parking_lot::Mutex::new(None),
+			stable_file_ids:         false,
+			recent:                  parking_lot::Mutex::new(VecDeque::with_capacity(DEFAULT_RECENT_CAPACITY)),
+			recent_capacity:         DEFAULT_RECENT_CAPACITY,
+			default_limit:           DEFAULT_RESULT_LIMIT,
+			dedup:                   None,
+			frecency:                frecency::FrecencyTracker::new(),
+			match_paths:             false,
+			cache_hits:              AtomicU64::new(0),
+			cache_misses:            AtomicU64::new(0),
+			pinned:                  RwLock::new(FxHashSet::default()),
+			pinned_storage:          None,
 		}
 	}
 
+	#[must_use]
+	pub fn with_weights(weights: fuzzy_matcher::ScoringWeights) -> Self {
+		let mut engine = Self::new();
+		engine.matcher.set_weights(weights);
+		engine
+	}
+
 	pub fn enable_file_search(&mut self, directories: Vec<std::path::PathBuf>, extensions: Option<Vec<String>>) {
 		self.file_scanner = Some(Arc::new(RwLock::new(file_scanner::FileScanner::new(directories, extensions))));
 	}
@@ -96,42 +288,221 @@ impl SearchEngine {
 
 	pub fn clear_file_indexer(&mut self) { self.file_indexer = None; }
 
+	pub fn set_fold_diacritics(&mut self, enabled: bool) {
+		if self.matcher.folds_diacritics() != enabled {
+			self.matcher.set_fold_diacritics(enabled);
+			self.clear_cache();
+		}
+	}
+
+	pub fn set_weights(&mut self, weights: fuzzy_matcher::ScoringWeights) {
+		if self.matcher.weights() != weights {
+			self.matcher.set_weights(weights);
+			self.clear_cache();
+		}
+	}
+
+	#[must_use]
+	pub const fn weights(&self) -> fuzzy_matcher::ScoringWeights { self.matcher.weights() }
+
+	pub fn set_stable_file_ids(&mut self, enabled: bool) {
+		if self.stable_file_ids != enabled {
+			self.stable_file_ids = enabled;
+			self.clear_cache();
+		}
+	}
+
+	pub fn set_match_paths(&mut self, enabled: bool) {
+		if self.match_paths != enabled {
+			self.match_paths = enabled;
+			self.clear_cache();
+		}
+	}
+
+	pub fn set_default_limit(&mut self, limit: usize) {
+		if limit > 0 {
+			self.default_limit = limit;
+		}
+	}
+
+	pub fn set_dedup(&mut self, dedup: Option<DedupConfig>) {
+		self.dedup = dedup;
+		self.clear_cache();
+	}
+
+	#[must_use]
+	pub const fn default_limit(&self) -> usize { self.default_limit }
+
+	pub fn set_recent_capacity(&mut self, capacity: usize) {
+		self.recent_capacity = capacity;
+		self.recent.lock().truncate(capacity);
+	}
+
+	pub fn record_selection(&self, id: &str) {
+		if self.recent_capacity == 0 {
+			return;
+		}
+		let mut recent = self.recent.lock();
+		if let Some(pos) = recent.iter().position(|r| r == id) {
+			recent.remove(pos);
+		}
+		recent.push_front(CompactString::new(id));
+		recent.truncate(self.recent_capacity);
+	}
+
+	pub fn set_usage_path(&mut self, path: impl AsRef<std::path::Path>) -> io::Result<()> {
+		self.frecency = frecency::FrecencyTracker::with_path(path)?;
+		self.clear_cache();
+		Ok(())
+	}
+
+	pub fn record_launch(&self, id: &str) {
+		self.frecency.record(id, frecency::FrecencyTracker::now());
+		self.clear_cache();
+	}
+
+	pub fn flush_usage(&self) {
+		self.frecency.flush();
+		if let Some(ref storage) = self.pinned_storage {
+			storage.flush();
+		}
+	}
+
+	pub fn set_pinned_path(&mut self, path: impl AsRef<std::path::Path>) -> io::Result<()> {
+		let storage = storage_utils::RkyvStorage::<String>::new(path)?;
+		*self.pinned.write() = storage.get_all().iter().map(CompactString::new).collect();
+		self.pinned_storage = Some(storage);
+		self.clear_cache();
+		Ok(())
+	}
+
+	pub fn set_pinned(&self, ids: &[&str]) {
+		*self.pinned.write() = ids.iter().copied().map(CompactString::new).collect();
+		if let Some(ref storage) = self.pinned_storage {
+			let ids: Vec<String> = ids.iter().copied().map(str::to_owned).collect();
+			storage.update_async(move |stored| {
+				stored.clone_from(&ids);
+				true
+			});
+		}
+		self.clear_cache();
+	}
+
+	#[must_use]
+	pub fn is_pinned(&self, id: &str) -> bool { self.pinned.read().contains(id) }
+
+	fn boost(&self, id: &str, now: i64) -> i64 {
+		let pinned = if self.is_pinned(id) { PINNED_BONUS } else { 0 };
+		pinned + self.frecency.bonus(id, now)
+	}
+
+	#[must_use]
+	pub fn recent_ids(&self, limit: usize) -> Vec<CompactString> {
+		self.recent.lock().iter().take(limit).cloned().collect()
+	}
+
 	#[inline]
-	#[allow(clippy::significant_drop_tightening)]
 	pub fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>> {
+		self.search_with_deadline(query, limit, None).map(|(results, _)| results)
+	}
+
+	pub fn search_with_deadline(
+		&self,
+		query: &str,
+		limit: usize,
+		budget: Option<Duration>,
+	) -> Result<(Vec<SearchResult>, bool)> {
+		self.run_search(query, limit, budget, false).map(|(results, _, truncated)| (results, truncated))
+	}
+
+	pub fn search_counted(&self, query: &str, limit: usize) -> Result<(Vec<SearchResult>, usize)> {
+		self.run_search(query, limit, None, false).map(|(results, total, _)| (results, total))
+	}
+
+	// Every page, the first included, scans the full match list so later pages
+	// are sliced from the cache.
+	pub fn search_page(&self, query: &str, offset: usize, limit: usize) -> Result<Vec<SearchResult>> {
+		let limit = if limit == 0 { self.default_limit } else { limit };
+		self
+			.run_search(query, offset.saturating_add(limit), None, true)
+			.map(|(results, ..)| results.into_iter().skip(offset).take(limit).collect())
+	}
+
+	#[allow(clippy::significant_drop_tightening)]
+	fn run_search(
+		&self,
+		query: &str,
+		limit: usize,
+		budget: Option<Duration>,
+		paged: bool,
+	) -> Result<(Vec<SearchResult>, usize, bool)> {
+		let normalized = shared_utils::normalize_query(query);
+		let (type_filter, query) = TypeFilter::parse(&normalized);
+		let limit = if limit == 0 { self.default_limit } else { limit };
+
 		if query.is_empty() {
-			return Err(SearchError::QueryTooShort);
+			return match type_filter {
+				Some(filter) => {
+					let (results, total) = self.list_by_type(filter, limit);
+					Ok((results, total, false))
+				}
+				None => Err(SearchError::QueryTooShort),
+			};
 		}
 
 		self.check_and_invalidate_cache();
 
-		let cache_key = CompactString::new(query);
+		let cache_key = CompactString::new(&normalized);
 		{
 			let mut cache = self.cache.write();
-			if let Some(cached) = cache.get(&cache_key) {
-				return Ok(cached.iter().take(limit).cloned().collect());
+			if let Some(cached) = cache.get(&cache_key).filter(|c| c.complete || c.results.len() >= limit) {
+				self.cache_hits.fetch_add(1, Ordering::Relaxed);
+				return Ok((cached.results.iter().take(limit).cloned().collect(), cached.total, false));
 			}
 		}
+		self.cache_misses.fetch_add(1, Ordering::Relaxed);
+
+		let scan_limit = match (paged, self.dedup.is_some()) {
+			(true, _) => usize::MAX,
+			(false, true) => limit.saturating_mul(2),
+			(false, false) => limit,
+		};
+		let deadline = Deadline::new(budget);
+
+		let now = frecency::FrecencyTracker::now();
+		let pattern = self.matcher.pattern_for(query);
+		let items = self.indexer.read().snapshot();
+		let items_count = items.len();
 
-		let pattern = fuzzy_matcher::FuzzyMatcher::parse_pattern(query);
-		let indexer = self.indexer.read();
-		let items_count = indexer.items_iter().size_hint().0;
+		let accepts = |item: &indexer::IndexedItem| type_filter.is_none_or(|f| f.matches(&item.item_type));
+		let include_files = type_filter.is_none_or(|f| f.matches(&indexer::ItemType::File));
 
-		let use_heap = limit < HEAP_THRESHOLD;
+		let use_heap = !paged && scan_limit < HEAP_THRESHOLD;
+		let mut total = 0;
 
 		let results: Vec<SearchResult> = if use_heap {
-			let mut heap: BinaryHeap<Reverse<HeapItem>> = BinaryHeap::with_capacity(limit + 1);
+			let mut heap: BinaryHeap<Reverse<HeapItem>> = BinaryHeap::with_capacity(scan_limit + 1);
 
-			for item in indexer.items_iter() {
-				if let Some((score, indices)) = self.matcher.match_with_pattern(&pattern, &item.name, query) {
+			for item in &items {
+				if deadline.poll() {
+					break;
+				}
+				if !accepts(item) {
+					continue;
+				}
+				if let Some((score, indices)) = Self::match_item(&self.matcher, &pattern, item, query, self.match_paths) {
+					total += 1;
+					let score = score + self.boost(&item.id, now);
 					heap.push(Reverse(HeapItem(Arc::clone(item), score, indices)));
-					if heap.len() > limit {
+					if heap.len() > scan_limit {
 						heap.pop();
 					}
 				}
 			}
 
-			self.search_files_heap(&pattern, query, &mut heap, limit);
+			if include_files {
+				total += self.search_files_heap(&pattern, query, &mut heap, scan_limit, &deadline, now);
+			}
 
 			let mut results: Vec<_> = heap
 				.into_iter()
@@ -141,31 +512,42 @@ impl SearchEngine {
 			results
 		} else {
 			let mut matches: MatchVec = if items_count >= PARALLEL_THRESHOLD {
-				let vec: Vec<_> = indexer
-					.items_iter()
-					.par_bridge()
+				let vec: Vec<_> = items
+					.par_iter()
 					.filter_map(|item| {
-						let matcher = fuzzy_matcher::FuzzyMatcher::new();
-						let (score, indices) = matcher.match_with_pattern(&pattern, &item.name, query)?;
-						Some((Arc::clone(item), score, indices))
+						if deadline.poll() || !accepts(item) {
+							return None;
+						}
+						let matcher = self.matcher.fork();
+						let (score, indices) = Self::match_item(&matcher, &pattern, item, query, self.match_paths)?;
+						Some((Arc::clone(item), score + self.boost(&item.id, now), indices))
 					})
 					.collect();
 				SmallVec::from_vec(vec)
 			} else {
 				let mut m = SmallVec::with_capacity(items_count.min(SMALL_VEC_SIZE));
-				for item in indexer.items_iter() {
-					if let Some((score, indices)) = self.matcher.match_with_pattern(&pattern, &item.name, query) {
-						m.push((Arc::clone(item), score, indices));
+				for item in &items {
+					if deadline.poll() {
+						break;
+					}
+					if !accepts(item) {
+						continue;
+					}
+					if let Some((score, indices)) = Self::match_item(&self.matcher, &pattern, item, query, self.match_paths) {
+						m.push((Arc::clone(item), score + self.boost(&item.id, now), indices));
 					}
 				}
 				m
 			};
 
-			self.search_files_vec(&pattern, query, &mut matches);
+			if include_files {
+				self.search_files_vec(&pattern, query, &mut matches, &deadline, now);
+			}
+			total = matches.len();
 
-			if limit < matches.len() {
-				matches.select_nth_unstable_by(limit, |a, b| b.1.cmp(&a.1).then_with(|| a.0.name.cmp(&b.0.name)));
-				matches.truncate(limit);
+			if scan_limit < matches.len() {
+				matches.select_nth_unstable_by(scan_limit, |a, b| b.1.cmp(&a.1).then_with(|| a.0.name.cmp(&b.0.name)));
+				matches.truncate(scan_limit);
 			}
 
 			matches.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.name.cmp(&b.0.name)));
@@ -173,24 +555,90 @@ impl SearchEngine {
 			matches.into_iter().map(|(item, score, match_indices)| SearchResult { item, score, match_indices }).collect()
 		};
 
-		let results = Arc::new(results);
-		self.cache.write().put(cache_key, results.clone());
-		Ok(Arc::unwrap_or_clone(results))
+		// The cache keeps every deduplicated result so `complete` describes what
+		// is stored; only the caller's copy is cut to `limit`.
+		let complete = total <= scan_limit;
+		let mut results = match self.dedup {
+			Some(ref dedup) => dedup.apply(results),
+			None => results,
+		};
+
+		if deadline.is_expired() {
+			results.truncate(limit);
+			return Ok((results, total, true));
+		}
+
+		self.cache.write().put(cache_key, Arc::new(CachedSearch { results: results.clone(), total, complete }));
+		results.truncate(limit);
+		Ok((results, total, false))
+	}
+
+	fn list_by_type(&self, filter: TypeFilter, limit: usize) -> (Vec<SearchResult>, usize) {
+		let mut listed: Vec<Arc<indexer::IndexedItem>> =
+			self.indexer.read().snapshot().into_iter().filter(|item| filter.matches(&item.item_type)).collect();
+
+		if filter.matches(&indexer::ItemType::File) {
+			if let Some(ref file_idx) = self.file_indexer {
+				file_idx.for_each_file(|file_entry| listed.push(Arc::new(Self::file_item(file_entry, self.stable_file_ids))));
+			} else if let Some(ref scanner) = self.file_scanner {
+				listed.extend(scanner.write().scan().iter().cloned().map(Arc::new));
+			}
+		}
+
+		let total = listed.len();
+		if limit < listed.len() {
+			listed.select_nth_unstable_by(limit, |a, b| a.name.cmp(&b.name));
+			listed.truncate(limit);
+		}
+		listed.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+
+		let results =
+			listed.into_iter().map(|item| SearchResult { item, score: 0, match_indices: IndicesVec::new() }).collect();
+		(results, total)
+	}
+
+	pub fn warm(&self, queries: &[&str], limit: usize) {
+		for query in queries.iter().filter(|q| !q.is_empty()) {
+			let _ = self.search_with_deadline(query, limit, None);
+		}
 	}
 
 	pub fn clear_cache(&self) { self.cache.write().clear(); }
 
+	#[must_use]
+	pub fn cache_stats(&self) -> (u64, u64, usize) {
+		(self.cache_hits.load(Ordering::Relaxed), self.cache_misses.load(Ordering::Relaxed), self.cache.read().len())
+	}
+
+	pub fn reset_stats(&self) {
+		self.cache_hits.store(0, Ordering::Relaxed);
+		self.cache_misses.store(0, Ordering::Relaxed);
+	}
+
 	pub fn indexer(&self) -> &Arc<RwLock<indexer::Indexer>> { &self.indexer }
 
 	fn check_and_invalidate_cache(&self) {
 		if let Some(ref indexer) = self.file_indexer {
-			let current_gen = indexer.generation();
-			let last_gen = self.file_indexer_generation.load(Ordering::Relaxed);
-			if current_gen != last_gen {
-				self.invalidate_file_cache();
-				self.file_indexer_generation.store(current_gen, Ordering::Relaxed);
-			}
+			self.invalidate_for_generation(indexer.generation(), Instant::now());
+		}
+	}
+
+	fn invalidate_for_generation(&self, current_gen: usize, now: Instant) {
+		if current_gen == self.file_indexer_generation.load(Ordering::Relaxed) {
+			return;
+		}
+
+		// Bursts of file events bump the generation repeatedly; clear at most once per
+		// window and leave the stored generation stale so a later search still clears.
+		let mut last = self.last_invalidation.lock();
+		if last.is_some_and(|at| now.saturating_duration_since(at) < CACHE_INVALIDATION_DEBOUNCE) {
+			return;
 		}
+		*last = Some(now);
+		drop(last);
+
+		self.invalidate_file_cache();
+		self.file_indexer_generation.store(current_gen, Ordering::Relaxed);
 	}
 
 	fn invalidate_file_cache(&self) {
@@ -198,35 +646,96 @@ impl SearchEngine {
 		cache.clear();
 	}
 
+	fn match_item(
+		matcher: &fuzzy_matcher::FuzzyMatcher,
+		pattern: &fuzzy_matcher::FuzzyPattern,
+		item: &indexer::IndexedItem,
+		query: &str,
+		match_paths: bool,
+	) -> Option<(i64, IndicesVec)> {
+		let by_name = matcher.match_with_pattern(pattern, &item.name, query);
+		let path = item
+			.path
+			.as_deref()
+			.filter(|path| match_paths && item.item_type == indexer::ItemType::File && *path != item.name);
+		let alternate = item
+			.aliases
+			.iter()
+			.map(CompactString::as_str)
+			.chain(path)
+			.filter_map(|alt| matcher.match_with_pattern(pattern, alt, query).map(|(score, _)| score))
+			.max();
+		Self::prefer_alternate(by_name, alternate)
+	}
+
+	fn match_file(
+		matcher: &fuzzy_matcher::FuzzyMatcher,
+		pattern: &fuzzy_matcher::FuzzyPattern,
+		file_entry: &file_indexer::FileEntry,
+		query: &str,
+		match_paths: bool,
+	) -> Option<(i64, IndicesVec)> {
+		let by_name = matcher.match_with_pattern(pattern, &file_entry.name, query);
+		if !match_paths || file_entry.path == file_entry.name {
+			return by_name;
+		}
+		let by_path = matcher.match_with_pattern(pattern, &file_entry.path, query).map(|(score, _)| score);
+		Self::prefer_alternate(by_name, by_path)
+	}
+
+	fn prefer_alternate(by_name: Option<(i64, IndicesVec)>, alternate: Option<i64>) -> Option<(i64, IndicesVec)> {
+		alternate
+			.filter(|&alt_score| by_name.as_ref().is_none_or(|(name_score, _)| alt_score > *name_score))
+			.map(|alt_score| (alt_score, IndicesVec::new()))
+			.or(by_name)
+	}
+
+	fn file_item(file_entry: &file_indexer::FileEntry, stable_id: bool) -> indexer::IndexedItem {
+		let path = file_entry.path_compact();
+		indexer::IndexedItem {
+			id:        if stable_id { file_entry.stable_id() } else { path.clone() },
+			name:      file_entry.name_compact(),
+			item_type: indexer::ItemType::File,
+			path:      Some(path),
+			metadata:  None,
+			aliases:   Vec::new(),
+		}
+	}
+
 	fn search_files_heap(
 		&self,
 		pattern: &fuzzy_matcher::FuzzyPattern,
 		query: &str,
 		heap: &mut BinaryHeap<Reverse<HeapItem>>,
 		limit: usize,
-	) {
+		deadline: &Deadline,
+		now: i64,
+	) -> usize {
+		let mut matched = 0;
 		if let Some(ref file_idx) = self.file_indexer {
-			let file_entries = file_idx.get_all_files();
-
-			for file_entry in &file_entries {
-				let item = indexer::IndexedItem {
-					id:        CompactString::new(&file_entry.path),
-					name:      file_entry.name_compact(),
-					item_type: indexer::ItemType::File,
-					path:      Some(file_entry.path_compact()),
-					metadata:  None,
-				};
-				if let Some((score, indices)) = self.matcher.match_with_pattern(pattern, &item.name, query) {
+			file_idx.for_each_file(|file_entry| {
+				if deadline.poll() {
+					return;
+				}
+				if let Some((score, indices)) = Self::match_file(&self.matcher, pattern, file_entry, query, self.match_paths) {
+					matched += 1;
+					let item = Self::file_item(file_entry, self.stable_file_ids);
+					let score = score + self.boost(&item.id, now);
 					heap.push(Reverse(HeapItem(Arc::new(item), score, indices)));
 					if heap.len() > limit {
 						heap.pop();
 					}
 				}
-			}
+			});
 		} else if let Some(ref scanner) = self.file_scanner {
 			let file_items = scanner.write().scan();
 			for item in file_items.iter() {
-				if let Some((score, indices)) = self.matcher.match_with_pattern(pattern, &item.name, query) {
+				if deadline.poll() {
+					break;
+				}
+				if let Some((score, indices)) = Self::match_item(&self.matcher, pattern, item, query, self.match_paths) {
+					matched += 1;
+					let score = score + self.boost(&item.id, now);
 					heap.push(Reverse(HeapItem(Arc::new(item.clone()), score, indices)));
 					if heap.len() > limit {
 						heap.pop();
@@ -234,49 +743,55 @@ impl SearchEngine {
 				}
 			}
 		}
+		matched
 	}
 
-	fn search_files_vec(&self, pattern: &fuzzy_matcher::FuzzyPattern, query: &str, matches: &mut MatchVec) {
+	fn search_files_vec(
+		&self,
+		pattern: &fuzzy_matcher::FuzzyPattern,
+		query: &str,
+		matches: &mut MatchVec,
+		deadline: &Deadline,
+		now: i64,
+	) {
 		if let Some(ref file_idx) = self.file_indexer {
-			let file_entries = file_idx.get_all_files();
-			matches.reserve(file_entries.len().min(1000));
+			let file_count = file_idx.file_count();
+			matches.reserve(file_count.min(1000));
 
-			if file_entries.len() >= PARALLEL_THRESHOLD {
-				let parallel_matches: Vec<_> = file_entries
-					.par_iter()
-					.filter_map(|file_entry| {
-						let matcher = fuzzy_matcher::FuzzyMatcher::new();
-						let item = indexer::IndexedItem {
-							id:        CompactString::new(&file_entry.path),
-							name:      file_entry.name_compact(),
-							item_type: indexer::ItemType::File,
-							path:      Some(file_entry.path_compact()),
-							metadata:  None,
-						};
-						let (score, indices) = matcher.match_with_pattern(pattern, &item.name, query)?;
-						Some((Arc::new(item), score, indices))
-					})
-					.collect();
+			if file_count >= PARALLEL_THRESHOLD {
+				let parallel_matches = file_idx.par_map_files(|file_entry| {
+					if deadline.poll() {
+						return None;
+					}
+					let matcher = self.matcher.fork();
+					let (score, indices) = Self::match_file(&matcher, pattern, file_entry, query, self.match_paths)?;
+					let item = Self::file_item(file_entry, self.stable_file_ids);
+					let score = score + self.boost(&item.id, now);
+					Some((Arc::new(item), score, indices))
+				});
 				matches.extend(parallel_matches);
 			} else {
-				for file_entry in &file_entries {
-					let item = indexer::IndexedItem {
-						id:        CompactString::new(&file_entry.path),
-						name:      file_entry.name_compact(),
-						item_type: indexer::ItemType::File,
-						path:      Some(file_entry.path_compact()),
-						metadata:  None,
-					};
-					if let Some((score, indices)) = self.matcher.match_with_pattern(pattern, &item.name, query) {
+				file_idx.for_each_file(|file_entry| {
+					if deadline.poll() {
+						return;
+					}
+					if let Some((score, indices)) = Self::match_file(&self.matcher, pattern, file_entry, query, self.match_paths)
+					{
+						let item = Self::file_item(file_entry, self.stable_file_ids);
+						let score = score + self.boost(&item.id, now);
 						matches.push((Arc::new(item), score, indices));
 					}
-				}
+				});
 			}
 		} else if let Some(ref scanner) = self.file_scanner {
 			let file_items = scanner.write().scan();
 			matches.reserve(file_items.len());
 			for item in file_items.iter() {
-				if let Some((score, indices)) = self.matcher.match_with_pattern(pattern, &item.name, query) {
+				if deadline.poll() {
+					return;
+				}
+				if let Some((score, indices)) = Self::match_item(&self.matcher, pattern, item, query, self.match_paths) {
+					let score = score + self.boost(&item.id, now);
 					matches.push((Arc::new(item.clone()), score, indices));
 				}
 			}
@@ -346,6 +861,7 @@ mod tests {
 				item_type: indexer::ItemType::Application,
 				path:      Some("/Applications/Visual Studio Code.app".into()),
 				metadata:  None,
+				aliases:   Vec::new(),
 			});
 			indexer.add_item(indexer::IndexedItem {
 				id:        "2".into(),
@@ -353,6 +869,7 @@ mod tests {
 				item_type: indexer::ItemType::Application,
 				path:      Some("/Applications/Safari.app".into()),
 				metadata:  None,
+				aliases:   Vec::new(),
 			});
 		}
 
@@ -360,4 +877,479 @@ mod tests {
 		assert!(!results.is_empty());
 		assert_eq!(results[0].item.name.as_str(), "Visual Studio Code");
 	}
+
+	#[test]
+	fn test_search_deadline_truncates() {
+		let engine = SearchEngine::new();
+
+		{
+			let mut indexer = engine.indexer.write();
+			for i in 0..10 {
+				indexer.add_item(indexer::IndexedItem {
+					id:        format!("{i}").into(),
+					name:      format!("Safari {i}").into(),
+					item_type: indexer::ItemType::Application,
+					path:      None,
+					metadata:  None,
+					aliases:   Vec::new(),
+				});
+			}
+		}
+
+		let (results, truncated) = engine.search_with_deadline("saf", 10, Some(Duration::ZERO)).unwrap();
+		assert!(truncated);
+		assert!(results.is_empty());
+
+		let (results, truncated) = engine.search_with_deadline("saf", 10, Some(Duration::from_secs(5))).unwrap();
+		assert!(!truncated);
+		assert_eq!(results.len(), 10);
+	}
+
+	#[test]
+	fn test_generation_invalidation_debounced() {
+		let engine = SearchEngine::new();
+		engine.indexer.write().add_item(indexer::IndexedItem {
+			id:        "1".into(),
+			name:      "Safari".into(),
+			item_type: indexer::ItemType::Application,
+			path:      None,
+			metadata:  None,
+			aliases:   Vec::new(),
+		});
+
+		let start = Instant::now();
+		engine.search("saf", 10).unwrap();
+		engine.invalidate_for_generation(1, start);
+		assert_eq!(engine.cache.read().len(), 0);
+
+		engine.search("saf", 10).unwrap();
+		for (generation, offset_ms) in [(2, 10), (3, 20), (4, 30)] {
+			engine.invalidate_for_generation(generation, start + Duration::from_millis(offset_ms));
+			assert_eq!(engine.cache.read().len(), 1);
+		}
+
+		engine.invalidate_for_generation(4, start + CACHE_INVALIDATION_DEBOUNCE + Duration::from_millis(1));
+		assert_eq!(engine.cache.read().len(), 0);
+		assert_eq!(engine.file_indexer_generation.load(Ordering::Relaxed), 4);
+	}
+
+	#[test]
+	fn test_search_folds_diacritics() {
+		let mut engine = SearchEngine::new();
+		engine.set_fold_diacritics(true);
+
+		{
+			let mut indexer = engine.indexer.write();
+			indexer.add_item(indexer::IndexedItem {
+				id:        "1".into(),
+				name:      "Café Menu".into(),
+				item_type: indexer::ItemType::Application,
+				path:      Some("/Applications/Café Menu.app".into()),
+				metadata:  None,
+				aliases:   Vec::new(),
+			});
+			indexer.add_item(indexer::IndexedItem {
+				id:        "2".into(),
+				name:      "Résumé.pdf".into(),
+				item_type: indexer::ItemType::File,
+				path:      Some("/Users/me/Résumé.pdf".into()),
+				metadata:  None,
+				aliases:   Vec::new(),
+			});
+		}
+
+		let results = engine.search("cafe", 10).unwrap();
+		assert_eq!(results[0].item.name.as_str(), "Café Menu");
+
+		let results = engine.search("resume", 10).unwrap();
+		assert_eq!(results[0].item.name.as_str(), "Résumé.pdf");
+	}
+
+	#[test]
+	fn test_recent_selections() {
+		let mut engine = SearchEngine::new();
+		engine.set_recent_capacity(3);
+
+		for id in ["a", "b", "c", "a", "d"] {
+			engine.record_selection(id);
+		}
+
+		assert_eq!(engine.recent_ids(10), vec!["d", "a", "c"]);
+		assert_eq!(engine.recent_ids(2), vec!["d", "a"]);
+	}
+
+	#[test]
+	fn test_zero_limit_uses_default() {
+		let mut engine = SearchEngine::new();
+		engine.set_default_limit(3);
+
+		{
+			let mut indexer = engine.indexer.write();
+			for i in 0..10 {
+				indexer.add_item(indexer::IndexedItem {
+					id:        format!("{i}").into(),
+					name:      format!("Notes {i}").into(),
+					item_type: indexer::ItemType::Application,
+					path:      None,
+					metadata:  None,
+					aliases:   Vec::new(),
+				});
+			}
+		}
+
+		assert_eq!(engine.search("notes", 0).unwrap().len(), 3);
+		engine.clear_cache();
+		assert_eq!(engine.search("notes", 5).unwrap().len(), 5);
+	}
+
+	#[test]
+	fn test_concurrent_search_and_add() {
+		let engine = SearchEngine::new();
+
+		std::thread::scope(|scope| {
+			scope.spawn(|| {
+				for i in 0..200 {
+					engine.indexer.write().add_items(vec![indexer::IndexedItem {
+						id:        format!("{i}").into(),
+						name:      format!("Terminal {i}").into(),
+						item_type: indexer::ItemType::Application,
+						path:      None,
+						metadata:  None,
+						aliases:   Vec::new(),
+					}]);
+				}
+			});
+
+			for _ in 0..50 {
+				engine.clear_cache();
+				let results = engine.search("term", 10).unwrap();
+				assert!(results.len() <= 10);
+			}
+		});
+
+		engine.clear_cache();
+		assert_eq!(engine.search("term", 500).unwrap().len(), 200);
+	}
+
+	#[test]
+	fn test_dedup_prefers_application() {
+		let mut engine = SearchEngine::new();
+
+		{
+			let mut indexer = engine.indexer.write();
+			indexer.add_item(indexer::IndexedItem {
+				id:        "file:/Applications/Safari.app".into(),
+				name:      "Safari.app".into(),
+				item_type: indexer::ItemType::File,
+				path:      Some("/Applications/Safari.app".into()),
+				metadata:  None,
+				aliases:   Vec::new(),
+			});
+			indexer.add_item(indexer::IndexedItem {
+				id:        "com.apple.Safari".into(),
+				name:      "Safari".into(),
+				item_type: indexer::ItemType::Application,
+				path:      Some("/Applications/Safari.app".into()),
+				metadata:  None,
+				aliases:   Vec::new(),
+			});
+		}
+
+		assert_eq!(engine.search("safari", 10).unwrap().len(), 2);
+
+		engine.set_dedup(Some(DedupConfig::default()));
+		let results = engine.search("safari", 10).unwrap();
+		assert_eq!(results.len(), 1);
+		assert_eq!(results[0].item.item_type, indexer::ItemType::Application);
+
+		engine.set_dedup(Some(DedupConfig { key: DedupKey::NameAndPath, ..DedupConfig::default() }));
+		assert_eq!(engine.search("safari", 10).unwrap().len(), 2);
+	}
+
+	#[test]
+	fn test_dedup_cache_serves_larger_limits() {
+		let mut engine = SearchEngine::new();
+		{
+			let mut indexer = engine.indexer.write();
+			for i in 0..3 {
+				indexer.add_item(indexer::IndexedItem {
+					id:        format!("{i}").into(),
+					name:      format!("Safari {i}").into(),
+					item_type: indexer::ItemType::Application,
+					path:      Some(format!("/Applications/Safari {i}.app").into()),
+					metadata:  None,
+					aliases:   Vec::new(),
+				});
+			}
+		}
+		engine.set_dedup(Some(DedupConfig::default()));
+
+		assert_eq!(engine.search("safari", 2).unwrap().len(), 2);
+		assert_eq!(engine.search("safari", 3).unwrap().len(), 3);
+
+		engine.clear_cache();
+		assert_eq!(engine.search("safari", 2).unwrap().len(), 2);
+		assert_eq!(engine.search_page("safari", 2, 2).unwrap().len(), 1);
+	}
+
+	#[test]
+	fn test_warm_populates_cache() {
+		let engine = SearchEngine::new();
+		engine.indexer.write().add_item(indexer::IndexedItem {
+			id:        "1".into(),
+			name:      "Mail".into(),
+			item_type: indexer::ItemType::Application,
+			path:      None,
+			metadata:  None,
+			aliases:   Vec::new(),
+		});
+
+		engine.warm(&["m", "", "mail"], 5);
+
+		let mut cache = engine.cache.write();
+		assert!(cache.get("m").is_some());
+		assert!(cache.get("mail").is_some());
+		assert_eq!(cache.len(), 2);
+	}
+
+	#[test]
+	fn test_search_counted_reports_total() {
+		let engine = SearchEngine::new();
+
+		{
+			let mut indexer = engine.indexer.write();
+			for i in 0..42 {
+				indexer.add_item(indexer::IndexedItem {
+					id:        format!("{i}").into(),
+					name:      format!("Preview {i}").into(),
+					item_type: indexer::ItemType::Application,
+					path:      None,
+					metadata:  None,
+					aliases:   Vec::new(),
+				});
+			}
+		}
+
+		let (results, total) = engine.search_counted("prev", 7).unwrap();
+		assert_eq!((results.len(), total), (7, 42));
+
+		let (results, total) = engine.search_counted("prev", 3).unwrap();
+		assert_eq!((results.len(), total), (3, 42));
+
+		engine.clear_cache();
+		let (results, total) = engine.search_counted("prev", 200).unwrap();
+		assert_eq!((results.len(), total), (42, 42));
+	}
+
+	#[test]
+	fn test_alias_match() {
+		let engine = SearchEngine::new();
+		engine.indexer.write().add_item(indexer::IndexedItem {
+			id:        "com.microsoft.Word".into(),
+			name:      "Microsoft Word".into(),
+			item_type: indexer::ItemType::Application,
+			path:      None,
+			metadata:  None,
+			aliases:   vec!["winword".into()],
+		});
+
+		let results = engine.search("winword", 5).unwrap();
+		assert_eq!(results.len(), 1);
+		assert!(results[0].match_indices.is_empty());
+
+		let results = engine.search("microsoft", 5).unwrap();
+		assert_eq!(results.len(), 1);
+		assert!(!results[0].match_indices.is_empty());
+	}
+
+	#[test]
+	fn test_launched_item_overtakes_equal_match() {
+		let engine = SearchEngine::new();
+		{
+			let mut indexer = engine.indexer.write();
+			for i in 1..=2 {
+				indexer.add_item(indexer::IndexedItem {
+					id:        format!("{i}").into(),
+					name:      format!("Safari {i}").into(),
+					item_type: indexer::ItemType::Application,
+					path:      None,
+					metadata:  None,
+					aliases:   Vec::new(),
+				});
+			}
+		}
+
+		let results = engine.search("saf", 10).unwrap();
+		assert_eq!(results[0].score, results[1].score);
+		assert_eq!(results[0].item.id.as_str(), "1");
+
+		engine.record_launch("2");
+		let results = engine.search("saf", 10).unwrap();
+		assert_eq!(results[0].item.id.as_str(), "2");
+		assert!(results[0].score > results[1].score);
+	}
+
+	#[test]
+	fn test_type_filter() {
+		let engine = SearchEngine::new();
+		{
+			let mut indexer = engine.indexer.write();
+			for (id, name, item_type) in [
+				("1", "README.md", indexer::ItemType::File),
+				("2", "Readme Viewer", indexer::ItemType::Application),
+				("3", "notes.txt", indexer::ItemType::File),
+				("4", "Reload", indexer::ItemType::Custom("command".to_owned())),
+			] {
+				indexer.add_item(indexer::IndexedItem {
+					id:        id.into(),
+					name:      name.into(),
+					item_type: item_type.clone(),
+					path:      None,
+					metadata:  None,
+					aliases:   Vec::new(),
+				});
+			}
+		}
+
+		let results = engine.search("type:file readme", 10).unwrap();
+		assert_eq!(results.len(), 1);
+		assert_eq!(results[0].item.id.as_str(), "1");
+
+		let results = engine.search("readme", 10).unwrap();
+		assert_eq!(results.len(), 2);
+
+		let results = engine.search("TYPE:cmd re", 10).unwrap();
+		assert_eq!(results.len(), 1);
+		assert_eq!(results[0].item.id.as_str(), "4");
+
+		let results = engine.search("type:all readme", 10).unwrap();
+		assert_eq!(results.len(), 2);
+
+		let names: Vec<_> = engine.search("type:file", 10).unwrap().iter().map(|r| r.item.name.to_string()).collect();
+		assert_eq!(names, ["README.md", "notes.txt"]);
+
+		assert!(engine.search("type:bogus", 10).unwrap().is_empty());
+		assert_eq!(TypeFilter::parse("type:app safari"), (Some(TypeFilter::Application), "safari"));
+	}
+
+	#[test]
+	fn test_search_page_continues_first_page() {
+		let engine = SearchEngine::new();
+		{
+			let mut indexer = engine.indexer.write();
+			for i in 0..20 {
+				indexer.add_item(indexer::IndexedItem {
+					id:        format!("{i}").into(),
+					name:      format!("Safari {i:02}").into(),
+					item_type: indexer::ItemType::Application,
+					path:      None,
+					metadata:  None,
+					aliases:   Vec::new(),
+				});
+			}
+		}
+
+		let all = engine.search("saf", 20).unwrap();
+		engine.clear_cache();
+		engine.reset_stats();
+
+		let first = engine.search_page("saf", 0, 7).unwrap();
+		let second = engine.search_page("saf", 7, 7).unwrap();
+		let third = engine.search_page("saf", 14, 7).unwrap();
+		assert_eq!(engine.cache_stats().0, 2);
+		assert_eq!(engine.cache_stats().1, 1);
+		assert_eq!(first.len(), 7);
+		assert_eq!(second.len(), 7);
+		assert_eq!(third.len(), 6);
+
+		let paged: Vec<_> = first.iter().chain(&second).chain(&third).map(|r| r.item.id.clone()).collect();
+		let expected: Vec<_> = all.iter().map(|r| r.item.id.clone()).collect();
+		assert_eq!(paged, expected);
+		assert!(engine.search_page("saf", 20, 7).unwrap().is_empty());
+	}
+
+	#[test]
+	fn test_match_paths() {
+		let mut engine = SearchEngine::new();
+		{
+			let mut indexer = engine.indexer.write();
+			indexer.add_item(indexer::IndexedItem {
+				id:        "1".into(),
+				name:      "main.rs".into(),
+				item_type: indexer::ItemType::File,
+				path:      Some("/home/user/project/src/main.rs".into()),
+				metadata:  None,
+				aliases:   Vec::new(),
+			});
+			indexer.add_item(indexer::IndexedItem {
+				id:        "2".into(),
+				name:      "Project Manager".into(),
+				item_type: indexer::ItemType::Application,
+				path:      Some("/Applications/Project Manager.app".into()),
+				metadata:  None,
+				aliases:   Vec::new(),
+			});
+		}
+
+		assert!(engine.search("project/src", 10).unwrap().is_empty());
+
+		engine.set_match_paths(true);
+		let results = engine.search("project/src", 10).unwrap();
+		assert_eq!(results.len(), 1);
+		assert_eq!(results[0].item.id.as_str(), "1");
+		assert!(results[0].match_indices.is_empty());
+
+		let results = engine.search("main", 10).unwrap();
+		assert_eq!(results[0].match_indices.as_slice(), [0, 1, 2, 3]);
+	}
+
+	#[test]
+	fn test_cache_stats() {
+		let engine = SearchEngine::new();
+		engine.indexer.write().add_item(indexer::IndexedItem {
+			id:        "1".into(),
+			name:      "Safari".into(),
+			item_type: indexer::ItemType::Application,
+			path:      None,
+			metadata:  None,
+			aliases:   Vec::new(),
+		});
+
+		engine.search("saf", 10).unwrap();
+		engine.search("saf", 10).unwrap();
+		assert_eq!(engine.cache_stats(), (1, 1, 1));
+
+		engine.clear_cache();
+		assert_eq!(engine.cache_stats(), (1, 1, 0));
+
+		engine.reset_stats();
+		assert_eq!(engine.cache_stats(), (0, 0, 0));
+	}
+
+	#[test]
+	fn test_pinned_item_ranks_first() {
+		let engine = SearchEngine::new();
+		{
+			let mut indexer = engine.indexer.write();
+			for (id, name) in [("1", "Terminal"), ("2", "Remote Terminal Helper Utility"), ("3", "Notes")] {
+				indexer.add_item(indexer::IndexedItem {
+					id:        id.into(),
+					name:      name.into(),
+					item_type: indexer::ItemType::Application,
+					path:      None,
+					metadata:  None,
+					aliases:   Vec::new(),
+				});
+			}
+		}
+
+		let results = engine.search("terminal", 10).unwrap();
+		assert_eq!(results[0].item.id.as_str(), "1");
+
+		engine.set_pinned(&["2", "3"]);
+		let results = engine.search("terminal", 10).unwrap();
+		assert_eq!(results.len(), 2);
+		assert_eq!(results[0].item.id.as_str(), "2");
+		assert_eq!(results[1].item.id.as_str(), "1");
+	}
 }
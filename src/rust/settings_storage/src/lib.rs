@@ -2,11 +2,13 @@ use std::{io, path::Path};
 
 use bytecheck::CheckBytes;
 use rkyv::{Archive, Deserialize, Serialize};
-use storage_utils::RkyvStorage;
+use rustc_hash::FxHashMap;
+use storage_utils::{RkyvStorage, load_from_disk};
 
 #[allow(clippy::struct_excessive_bools)]
 #[derive(Archive, Deserialize, Serialize, CheckBytes, Debug, Clone)]
 #[rkyv(derive(Debug))]
+#[derive(serde::Serialize, serde::Deserialize)]
 pub struct AppSettings {
 	pub theme:                    String,
 	pub custom_font_name:         String,
@@ -24,6 +26,8 @@ pub struct AppSettings {
 	pub clipboard_shortcut_key:   String,
 	pub clipboard_shortcut_mods:  Vec<String>,
 	pub search_folders:           Vec<String>,
+	#[serde(default)]
+	pub extra:                    FxHashMap<String, String>,
 }
 
 impl Default for AppSettings {
@@ -49,6 +53,52 @@ impl Default for AppSettings {
 				"/System/Applications".to_owned(),
 				"/System/Applications/Utilities".to_owned(),
 			],
+			extra:                    FxHashMap::default(),
+		}
+	}
+}
+
+#[derive(Archive, Deserialize, Serialize, CheckBytes, Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
+struct LegacyAppSettings {
+	theme:                    String,
+	custom_font_name:         String,
+	font_size:                String,
+	max_results:              i32,
+	max_clipboard_items:      i32,
+	clipboard_retention_days: i32,
+	quick_select_modifier:    String,
+	enable_commands:          bool,
+	show_tray_icon:           bool,
+	show_dock_icon:           bool,
+	hide_traffic_lights:      bool,
+	launcher_shortcut_key:    String,
+	launcher_shortcut_mods:   Vec<String>,
+	clipboard_shortcut_key:   String,
+	clipboard_shortcut_mods:  Vec<String>,
+	search_folders:           Vec<String>,
+}
+
+impl From<LegacyAppSettings> for AppSettings {
+	fn from(legacy: LegacyAppSettings) -> Self {
+		Self {
+			theme:                    legacy.theme,
+			custom_font_name:         legacy.custom_font_name,
+			font_size:                legacy.font_size,
+			max_results:              legacy.max_results,
+			max_clipboard_items:      legacy.max_clipboard_items,
+			clipboard_retention_days: legacy.clipboard_retention_days,
+			quick_select_modifier:    legacy.quick_select_modifier,
+			enable_commands:          legacy.enable_commands,
+			show_tray_icon:           legacy.show_tray_icon,
+			show_dock_icon:           legacy.show_dock_icon,
+			hide_traffic_lights:      legacy.hide_traffic_lights,
+			launcher_shortcut_key:    legacy.launcher_shortcut_key,
+			launcher_shortcut_mods:   legacy.launcher_shortcut_mods,
+			clipboard_shortcut_key:   legacy.clipboard_shortcut_key,
+			clipboard_shortcut_mods:  legacy.clipboard_shortcut_mods,
+			search_folders:           legacy.search_folders,
+			extra:                    FxHashMap::default(),
 		}
 	}
 }
@@ -61,7 +111,11 @@ impl SettingsStorage {
 	pub fn new<P: AsRef<Path>>(path: P) -> io::Result<Self> {
 		let storage = RkyvStorage::new(path)?;
 		if storage.is_empty() {
-			storage.add(AppSettings::default())?;
+			let legacy = load_from_disk::<LegacyAppSettings>(storage.path()).unwrap_or_default();
+			match legacy.into_iter().next() {
+				Some(settings) => storage.replace_all(vec![settings.into()])?,
+				None => storage.add(AppSettings::default())?,
+			}
 		}
 		Ok(Self { storage })
 	}
@@ -69,14 +123,39 @@ impl SettingsStorage {
 	#[must_use]
 	pub fn get(&self) -> AppSettings { self.storage.get_all().first().cloned().unwrap_or_default() }
 
-	pub fn save(&self, settings: AppSettings) -> io::Result<()> {
+	pub fn save(&self, settings: AppSettings) -> io::Result<()> { self.storage.replace_all(vec![settings]) }
+
+	/// Saves every field except `extra`, which keeps the stored entries so a
+	/// `set_extra` made since `settings` was read is not lost.
+	pub fn save_keeping_extra(&self, mut settings: AppSettings) -> io::Result<()> {
 		self.storage.update(|entries| {
-			entries.clear();
-			entries.push(settings);
+			if let Some(current) = entries.first_mut() {
+				settings.extra = std::mem::take(&mut current.extra);
+			}
+			*entries = vec![settings];
 			true
 		})?;
 		Ok(())
 	}
+
+	#[must_use]
+	pub fn get_extra(&self, key: &str) -> Option<String> {
+		self.storage.get_all().first().and_then(|settings| settings.extra.get(key).cloned())
+	}
+
+	pub fn set_extra(&self, key: &str, value: Option<String>) -> io::Result<()> {
+		self.storage.update(|entries| {
+			if entries.is_empty() {
+				entries.push(AppSettings::default());
+			}
+			let extra = &mut entries[0].extra;
+			match value {
+				Some(value) => extra.insert(key.to_owned(), value.clone()).as_ref() != Some(&value),
+				None => extra.remove(key).is_some(),
+			}
+		})?;
+		Ok(())
+	}
 }
 
 #[cfg(test)]
@@ -109,4 +188,86 @@ mod tests {
 		assert_eq!(loaded.theme, "light");
 		assert_eq!(loaded.max_results, 10);
 	}
+
+	#[test]
+	fn test_extra_round_trip() {
+		let temp = NamedTempFile::new().unwrap();
+		let storage = SettingsStorage::new(temp.path()).unwrap();
+
+		storage.set_extra("accent", Some("blue".to_owned())).unwrap();
+		assert_eq!(storage.get_extra("accent").as_deref(), Some("blue"));
+
+		let storage2 = SettingsStorage::new(temp.path()).unwrap();
+		assert_eq!(storage2.get_extra("accent").as_deref(), Some("blue"));
+
+		let json = serde_json::to_string(&storage2.get()).unwrap();
+		let parsed: AppSettings = serde_json::from_str(&json).unwrap();
+		assert_eq!(parsed.extra.get("accent").map(String::as_str), Some("blue"));
+
+		storage2.set_extra("accent", None).unwrap();
+		assert_eq!(storage2.get_extra("accent"), None);
+	}
+
+	#[test]
+	fn test_save_keeping_extra_preserves_concurrent_extra() {
+		let temp = NamedTempFile::new().unwrap();
+		let window = SettingsStorage::new(temp.path()).unwrap();
+		let other = SettingsStorage::new(temp.path()).unwrap();
+
+		let mut settings = window.get();
+		other.set_extra("accent", Some("blue".to_owned())).unwrap();
+		settings.theme = "light".to_owned();
+		window.save_keeping_extra(settings).unwrap();
+
+		let loaded = SettingsStorage::new(temp.path()).unwrap();
+		assert_eq!(loaded.get().theme, "light");
+		assert_eq!(loaded.get_extra("accent").as_deref(), Some("blue"));
+	}
+
+	#[test]
+	fn test_legacy_settings_migrate() {
+		let temp = NamedTempFile::new().unwrap();
+		let AppSettings {
+			custom_font_name,
+			font_size,
+			max_clipboard_items,
+			clipboard_retention_days,
+			quick_select_modifier,
+			enable_commands,
+			show_tray_icon,
+			show_dock_icon,
+			hide_traffic_lights,
+			launcher_shortcut_key,
+			launcher_shortcut_mods,
+			clipboard_shortcut_key,
+			clipboard_shortcut_mods,
+			search_folders,
+			..
+		} = AppSettings::default();
+		let legacy = LegacyAppSettings {
+			theme: "light".to_owned(),
+			max_results: 12,
+			custom_font_name,
+			font_size,
+			max_clipboard_items,
+			clipboard_retention_days,
+			quick_select_modifier,
+			enable_commands,
+			show_tray_icon,
+			show_dock_icon,
+			hide_traffic_lights,
+			launcher_shortcut_key,
+			launcher_shortcut_mods,
+			clipboard_shortcut_key,
+			clipboard_shortcut_mods,
+			search_folders,
+		};
+		storage_utils::save_to_disk(temp.path(), &vec![legacy]).unwrap();
+
+		let storage = SettingsStorage::new(temp.path()).unwrap();
+		let settings = storage.get();
+		assert_eq!(settings.theme, "light");
+		assert_eq!(settings.max_results, 12);
+		assert!(settings.extra.is_empty());
+	}
 }
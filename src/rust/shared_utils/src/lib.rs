@@ -1,12 +1,19 @@
+use std::{borrow::Cow, hash::{Hash, Hasher}};
+
 use aho_corasick::{AhoCorasick, AhoCorasickBuilder, MatchKind};
+use chrono::{DateTime, Local};
 use parking_lot::RwLock;
 
 pub fn build_automaton_leftmost_longest<P: AsRef<[u8]>>(patterns: &[P]) -> Option<AhoCorasick> {
+	build_automaton(patterns, MatchKind::LeftmostLongest)
+}
+
+pub fn build_automaton<P: AsRef<[u8]>>(patterns: &[P], kind: MatchKind) -> Option<AhoCorasick> {
 	if patterns.is_empty() {
 		return None;
 	}
 
-	AhoCorasickBuilder::new().match_kind(MatchKind::LeftmostLongest).build(patterns).ok()
+	AhoCorasickBuilder::new().match_kind(kind).build(patterns).ok()
 }
 
 pub struct KeywordMatcherCache {
@@ -46,3 +53,133 @@ impl KeywordMatcherCache {
 impl Default for KeywordMatcherCache {
 	fn default() -> Self { Self::new() }
 }
+
+/// 64-bit FNV-1a. Unlike `FxHasher`, its output is fixed by the algorithm, so
+/// hashes persisted to disk stay valid across dependency upgrades.
+pub struct FnvHasher(u64);
+
+impl Default for FnvHasher {
+	fn default() -> Self { Self(0xcbf2_9ce4_8422_2325) }
+}
+
+impl Hasher for FnvHasher {
+	fn finish(&self) -> u64 { self.0 }
+
+	fn write(&mut self, bytes: &[u8]) {
+		self.0 = bytes.iter().fold(self.0, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3));
+	}
+
+	fn write_usize(&mut self, value: usize) { self.write(&(value as u64).to_le_bytes()); }
+}
+
+#[must_use]
+pub fn fixed_hasher() -> FnvHasher { FnvHasher::default() }
+
+#[must_use]
+pub fn stable_hash<T: Hash + ?Sized>(value: &T) -> u64 {
+	let mut hasher = fixed_hasher();
+	value.hash(&mut hasher);
+	hasher.finish()
+}
+
+#[must_use]
+pub fn normalize_query(query: &str) -> Cow<'_, str> {
+	let trimmed = query.trim();
+	let mut prev_whitespace = false;
+	let collapsed = trimmed.chars().all(|c| {
+		let clean = !c.is_whitespace() || (c == ' ' && !prev_whitespace);
+		prev_whitespace = c.is_whitespace();
+		clean
+	});
+	if collapsed { Cow::Borrowed(trimmed) } else { Cow::Owned(trimmed.split_whitespace().collect::<Vec<_>>().join(" ")) }
+}
+
+#[must_use]
+pub fn normalize_command(query: &str) -> Cow<'_, str> {
+	let trimmed = query.trim();
+	let Some((head, rest)) = trimmed.split_once(char::is_whitespace) else {
+		return Cow::Borrowed(trimmed);
+	};
+	let args = rest.trim_start();
+	if args.len() == rest.len() && trimmed.as_bytes()[head.len()] == b' ' {
+		Cow::Borrowed(trimmed)
+	} else {
+		Cow::Owned(format!("{head} {args}"))
+	}
+}
+
+const MINUTE: f64 = 60.0;
+const HOUR: f64 = 60.0 * MINUTE;
+const DAY: f64 = 24.0 * HOUR;
+const WEEK: f64 = 7.0 * DAY;
+
+#[must_use]
+#[allow(clippy::cast_possible_truncation)]
+pub fn format_age(timestamp: f64, now: f64) -> String {
+	let age = now - timestamp;
+	if age.is_nan() || age < MINUTE {
+		return "just now".to_owned();
+	}
+	if age < HOUR {
+		return format!("{}m ago", (age / MINUTE) as u64);
+	}
+	if age < DAY {
+		return format!("{}h ago", (age / HOUR) as u64);
+	}
+	if age < 2.0 * DAY {
+		return "yesterday".to_owned();
+	}
+	if age < WEEK {
+		return format!("{}d ago", (age / DAY) as u64);
+	}
+	DateTime::from_timestamp(timestamp as i64, 0)
+		.map_or_else(String::new, |utc| utc.with_timezone(&Local).format("%b %-d, %Y").to_string())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_stable_hash() {
+		assert_eq!(stable_hash("/Users/me/notes.txt"), stable_hash("/Users/me/notes.txt"));
+		assert_ne!(stable_hash("/Users/me/notes.txt"), stable_hash("/Users/me/todo.txt"));
+		assert_eq!(stable_hash(&("script.sh", "query")), stable_hash(&("script.sh", "query")));
+	}
+
+	#[test]
+	fn test_stable_hash_known_values() {
+		assert_eq!(fixed_hasher().finish(), 0xcbf2_9ce4_8422_2325);
+		assert_eq!(stable_hash("/Users/me/notes.txt"), 0xc5c7_55b3_5beb_fec6);
+		assert_eq!(stable_hash(&("script.sh", "query")), 0x6b7f_fc82_d185_78e1);
+	}
+
+	#[test]
+	fn test_normalize_query() {
+		assert_eq!(normalize_query("  g  "), "g");
+		assert_eq!(normalize_query("visual   studio\tcode"), "visual studio code");
+		assert!(matches!(normalize_query("safari"), Cow::Borrowed("safari")));
+		assert!(matches!(normalize_query(" safari web "), Cow::Borrowed("safari web")));
+		assert_eq!(normalize_query("   "), "");
+	}
+
+	#[test]
+	fn test_normalize_command() {
+		assert_eq!(normalize_command("  g  "), "g");
+		assert_eq!(normalize_command("g    rust  lang "), "g rust  lang");
+		assert_eq!(normalize_command("g\trust"), "g rust");
+		assert!(matches!(normalize_command("g a  b"), Cow::Borrowed("g a  b")));
+	}
+
+	#[test]
+	fn test_format_age() {
+		let now = 1_700_000_000.0;
+		assert_eq!(format_age(now + 30.0, now), "just now");
+		assert_eq!(format_age(now - 5.0, now), "just now");
+		assert_eq!(format_age(now - 120.0, now), "2m ago");
+		assert_eq!(format_age(now - 10_800.0, now), "3h ago");
+		assert_eq!(format_age(now - 108_000.0, now), "yesterday");
+		assert_eq!(format_age(now - 345_600.0, now), "4d ago");
+		assert!(format_age(now - 2_592_000.0, now).ends_with("2023"));
+	}
+}
@@ -1,68 +1,479 @@
-use std::sync::Arc;
+use std::{fmt::Write, path::PathBuf, sync::Arc};
 
-use aho_corasick::AhoCorasick;
-use parking_lot::RwLock;
+use aho_corasick::{AhoCorasick, MatchKind};
+use bytecheck::CheckBytes;
+use chrono::{Local, NaiveDateTime};
+use parking_lot::{Mutex, RwLock};
+use regex::{Regex, RegexBuilder, RegexSet};
+use rustc_hash::FxHashMap;
 use serde::{Deserialize, Serialize};
+use storage_utils::{load_from_disk, save_to_disk_async};
 
 #[derive(Clone, Debug)]
 pub struct Snippet {
-	pub id:      String,
-	pub trigger: Arc<str>,
-	pub content: Arc<str>,
-	pub enabled: bool,
+	pub id:         String,
+	pub trigger:    Arc<str>,
+	pub content:    Arc<str>,
+	pub enabled:    bool,
+	pub match_case: bool,
+	pub is_regex:   bool,
 }
 
-#[derive(Deserialize, Serialize)]
-struct SnippetDTO {
-	id:      String,
-	trigger: String,
-	content: String,
-	enabled: bool,
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SnippetDTO {
+	pub id:         String,
+	pub trigger:    String,
+	pub content:    String,
+	pub enabled:    bool,
+	#[serde(default = "default_category")]
+	pub category:   String,
+	#[serde(default)]
+	pub match_case: bool,
+	#[serde(default)]
+	pub is_regex:   bool,
 }
 
+fn default_category() -> String { "General".to_owned() }
+
 impl From<SnippetDTO> for Snippet {
 	fn from(dto: SnippetDTO) -> Self {
-		Self { id: dto.id, trigger: dto.trigger.into(), content: dto.content.into(), enabled: dto.enabled }
+		Self {
+			id:         dto.id,
+			trigger:    dto.trigger.into(),
+			content:    dto.content.into(),
+			enabled:    dto.enabled,
+			match_case: dto.match_case,
+			is_regex:   dto.is_regex,
+		}
+	}
+}
+
+impl From<SnippetDTO> for snippet_storage::Snippet {
+	fn from(dto: SnippetDTO) -> Self {
+		Self::with_all(dto.id, dto.trigger, dto.content, dto.enabled, dto.category)
+			.regex(dto.is_regex)
+			.match_case(dto.match_case)
+	}
+}
+
+impl From<snippet_storage::Snippet> for SnippetDTO {
+	fn from(snippet: snippet_storage::Snippet) -> Self {
+		Self {
+			id:         snippet.id,
+			trigger:    snippet.trigger,
+			content:    snippet.content,
+			enabled:    snippet.enabled,
+			category:   snippet.category,
+			match_case: snippet.match_case,
+			is_regex:   snippet.is_regex,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CasePattern {
+	Lower,
+	Upper,
+	Capitalized,
+}
+
+impl CasePattern {
+	fn detect(typed: &str) -> Self {
+		let mut letters = typed.chars().filter(|c| c.is_alphabetic());
+		let Some(first) = letters.next() else {
+			return Self::Lower;
+		};
+		if !first.is_uppercase() {
+			return Self::Lower;
+		}
+		let rest: Vec<char> = letters.collect();
+		if !rest.is_empty() && rest.iter().all(|c| !c.is_lowercase()) { Self::Upper } else { Self::Capitalized }
+	}
+
+	fn apply(self, text: &str) -> String {
+		match self {
+			Self::Lower => text.to_owned(),
+			Self::Upper => text.to_uppercase(),
+			Self::Capitalized => capitalize(text),
+		}
+	}
+}
+
+fn capitalize(text: &str) -> String {
+	let Some(pos) = text.find(char::is_alphabetic) else {
+		return text.to_owned();
+	};
+	let first = text[pos..].chars().next().unwrap_or_default();
+	let mut out = String::with_capacity(text.len());
+	out.push_str(&text[..pos]);
+	out.extend(first.to_uppercase());
+	out.push_str(&text[pos + first.len_utf8()..]);
+	out
+}
+
+pub const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+pub const DEFAULT_TIME_FORMAT: &str = "%H:%M";
+const CURSOR_MARKER: &str = "{cursor}";
+
+/// Trigger, content, match end and cursor offset within the content.
+pub type CursorMatch = (Arc<str>, Arc<str>, usize, usize);
+
+/// Values substituted for placeholders when a snippet is pasted.
+#[derive(Clone, Debug)]
+pub struct ExpandContext {
+	pub now:         NaiveDateTime,
+	pub clipboard:   String,
+	pub date_format: String,
+	pub time_format: String,
+}
+
+impl ExpandContext {
+	#[must_use]
+	pub fn new(clipboard: impl Into<String>) -> Self {
+		Self {
+			now:         Local::now().naive_local(),
+			clipboard:   clipboard.into(),
+			date_format: DEFAULT_DATE_FORMAT.to_owned(),
+			time_format: DEFAULT_TIME_FORMAT.to_owned(),
+		}
+	}
+
+	#[must_use]
+	pub fn with_date_format(mut self, format: impl Into<String>) -> Self {
+		self.date_format = format.into();
+		self
+	}
+
+	fn format_now(&self, format: &str) -> Option<String> {
+		let mut out = String::new();
+		write!(out, "{}", self.now.format(format)).ok()?;
+		Some(out)
+	}
+
+	fn resolve(&self, name: &str) -> Option<String> {
+		match name {
+			"date" => self.format_now(&self.date_format),
+			"time" => self.format_now(&self.time_format),
+			"datetime" => self.format_now(&format!("{} {}", self.date_format, self.time_format)),
+			"clipboard" => Some(self.clipboard.clone()),
+			_ => None,
+		}
+	}
+}
+
+fn title_case(text: &str) -> String {
+	let mut out = String::with_capacity(text.len());
+	let mut word_start = true;
+	for c in text.chars() {
+		if word_start {
+			out.extend(c.to_uppercase());
+		} else {
+			out.extend(c.to_lowercase());
+		}
+		word_start = !c.is_alphanumeric();
+	}
+	out
+}
+
+fn transform(directive: &str, text: &str) -> Option<String> {
+	match directive {
+		"upper" => Some(text.to_uppercase()),
+		"lower" => Some(text.to_lowercase()),
+		"title" => Some(title_case(text)),
+		_ => None,
+	}
+}
+
+// Byte offset of the `}` that closes the `{` at the start of `text`.
+fn closing_brace(text: &str) -> Option<usize> {
+	let mut depth = 0usize;
+	for (idx, c) in text.char_indices() {
+		match c {
+			'{' => depth += 1,
+			'}' => {
+				depth -= 1;
+				if depth == 0 {
+					return Some(idx);
+				}
+			}
+			_ => {}
+		}
+	}
+	None
+}
+
+/// Replaces `{date}`, `{time}`, `{datetime}` and `{clipboard}` in `content`.
+///
+/// `{upper:...}`, `{lower:...}` and `{title:...}` apply to their expanded
+/// body, so directives and placeholders nest. Unknown placeholders, and ones
+/// whose format string is invalid, are left as typed.
+#[must_use]
+pub fn expand_content(content: &str, ctx: &ExpandContext) -> String {
+	let mut out = String::with_capacity(content.len());
+	let mut rest = content;
+
+	while let Some(start) = rest.find('{') {
+		out.push_str(&rest[..start]);
+		rest = &rest[start..];
+		let Some(end) = closing_brace(rest) else {
+			out.push('{');
+			rest = &rest[1..];
+			continue;
+		};
+		let inner = &rest[1..end];
+
+		let expanded = match inner.split_once(':') {
+			Some((directive, body)) => transform(directive, &expand_content(body, ctx)),
+			None if !inner.contains('{') => ctx.resolve(inner),
+			None => None,
+		};
+		if let Some(value) = expanded {
+			out.push_str(&value);
+			rest = &rest[end + 1..];
+		} else {
+			out.push('{');
+			rest = &rest[1..];
+		}
+	}
+
+	out.push_str(rest);
+	out
+}
+
+const MAX_REGEX_TRIGGERS: usize = 256;
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+struct RegexTriggers {
+	set:     RegexSet,
+	regexes: Vec<Regex>,
+	owners:  Vec<usize>,
+}
+
+impl RegexTriggers {
+	// Invalid or oversized patterns are skipped, as is anything past the cap.
+	fn build(snippets: &[Snippet]) -> Option<Self> {
+		let (owners, regexes): (Vec<usize>, Vec<Regex>) = snippets
+			.iter()
+			.enumerate()
+			.filter(|(_, s)| s.is_regex)
+			.filter_map(|(idx, s)| RegexBuilder::new(&s.trigger).size_limit(REGEX_SIZE_LIMIT).build().ok().map(|r| (idx, r)))
+			.take(MAX_REGEX_TRIGGERS)
+			.unzip();
+		if regexes.is_empty() {
+			return None;
+		}
+		let set = RegexSet::new(regexes.iter().map(Regex::as_str)).ok()?;
+		Some(Self { set, regexes, owners })
+	}
+
+	fn find(&self, text: &str, policy: MatchPolicy) -> Option<(usize, usize, usize)> {
+		let found = self.set.matches(text).into_iter().filter_map(|idx| {
+			let mut matches = self.regexes[idx].find_iter(text).filter(|m| !m.is_empty());
+			let found = if policy.picks_first() { matches.next() } else { matches.last() }?;
+			Some((found.start(), found.end(), self.owners[idx]))
+		});
+		if policy.picks_first() {
+			found.min_by_key(|&(start, end, _)| (start, std::cmp::Reverse(end)))
+		} else {
+			found.max_by_key(|&(start, end, _)| (end, end - start))
+		}
+	}
+}
+
+/// Which trigger `find_match` expands when several occur in the text.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MatchPolicy {
+	/// The match closest to the end of the text wins. Overlapping triggers
+	/// are resolved left to right first, preferring the longest, so in
+	/// `"abcd"` with triggers `abc` and `bcd` only `abc` is seen.
+	#[default]
+	LeftmostLongest,
+	/// The match closest to the start of the text wins. Of triggers starting
+	/// at the same position, the one listed first is used.
+	LeftmostFirst,
+	/// Every match is considered, including overlapping ones, and the one
+	/// ending last wins: `bcd` in the example above.
+	Standard,
+}
+
+impl MatchPolicy {
+	const fn match_kind(self) -> MatchKind {
+		match self {
+			Self::LeftmostLongest => MatchKind::LeftmostLongest,
+			Self::LeftmostFirst => MatchKind::LeftmostFirst,
+			Self::Standard => MatchKind::Standard,
+		}
 	}
+
+	const fn picks_first(self) -> bool { matches!(self, Self::LeftmostFirst) }
+}
+
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, CheckBytes)]
+struct UsageCount {
+	id:    String,
+	count: u64,
 }
 
 pub struct SnippetMatcher {
-	snippets:  RwLock<Vec<Snippet>>,
-	automaton: RwLock<Option<AhoCorasick>>,
+	snippets:       RwLock<Vec<Snippet>>,
+	pattern_owners: RwLock<Vec<usize>>,
+	automaton:      RwLock<Option<AhoCorasick>>,
+	regexes:        RwLock<Option<RegexTriggers>>,
+	usage:          Mutex<FxHashMap<String, u64>>,
+	usage_path:     Option<PathBuf>,
+	match_policy:   MatchPolicy,
 }
 
 impl SnippetMatcher {
 	#[must_use]
 	#[allow(clippy::missing_const_for_fn)]
-	pub fn new() -> Self { Self { snippets: RwLock::new(Vec::new()), automaton: RwLock::new(None) } }
+	pub fn new() -> Self {
+		Self {
+			snippets:       RwLock::new(Vec::new()),
+			pattern_owners: RwLock::new(Vec::new()),
+			automaton:      RwLock::new(None),
+			regexes:        RwLock::new(None),
+			usage:          Mutex::new(FxHashMap::default()),
+			usage_path:     None,
+			match_policy:   MatchPolicy::LeftmostLongest,
+		}
+	}
+
+	#[must_use]
+	pub const fn with_match_policy(mut self, policy: MatchPolicy) -> Self {
+		self.match_policy = policy;
+		self
+	}
+
+	#[must_use]
+	pub const fn match_policy(&self) -> MatchPolicy { self.match_policy }
+
+	/// Creates a matcher whose usage counts are loaded from and saved to
+	/// `path`.
+	#[must_use]
+	pub fn with_usage_path(path: impl Into<PathBuf>) -> Self {
+		let path = path.into();
+		let counts: Vec<UsageCount> = if path.exists() { load_from_disk(&path).unwrap_or_default() } else { Vec::new() };
+		Self {
+			usage: Mutex::new(counts.into_iter().map(|u| (u.id, u.count)).collect()),
+			usage_path: Some(path),
+			..Self::new()
+		}
+	}
 
 	pub fn update_snippets(&self, snippets: Vec<Snippet>) {
-		let enabled_snippets: Vec<Snippet> = snippets.into_iter().filter(|s| s.enabled).collect();
+		let enabled_snippets: Vec<Snippet> =
+			snippets.into_iter().filter(|s| s.enabled && !s.trigger.trim().is_empty()).collect();
 
-		let patterns: Vec<&str> = enabled_snippets.iter().map(|s| &*s.trigger).collect();
-		let automaton = shared_utils::build_automaton_leftmost_longest(&patterns);
+		let mut patterns: Vec<String> = Vec::with_capacity(enabled_snippets.len());
+		let mut owners = Vec::with_capacity(enabled_snippets.len());
+		for (idx, snippet) in enabled_snippets.iter().enumerate().filter(|(_, s)| !s.is_regex) {
+			let mut variants = vec![snippet.trigger.to_string()];
+			if snippet.match_case {
+				let lower = snippet.trigger.to_lowercase();
+				for variant in [snippet.trigger.to_uppercase(), capitalize(&lower), lower] {
+					if !variants.contains(&variant) {
+						variants.push(variant);
+					}
+				}
+			}
+			owners.extend(std::iter::repeat_n(idx, variants.len()));
+			patterns.extend(variants);
+		}
+		let automaton = shared_utils::build_automaton(&patterns, self.match_policy.match_kind());
+		let regexes = RegexTriggers::build(&enabled_snippets);
 
 		*self.snippets.write() = enabled_snippets;
+		*self.pattern_owners.write() = owners;
 		*self.automaton.write() = automaton;
+		*self.regexes.write() = regexes;
+	}
+
+	pub fn find_match(&self, text: &str) -> Option<(Arc<str>, Arc<str>, usize)> { self.find_match_with(text, None) }
+
+	/// Like [`Self::find_match`], but expands placeholders in the returned
+	/// content when `ctx` is given. The stored snippet keeps its raw form.
+	pub fn find_match_with(&self, text: &str, ctx: Option<&ExpandContext>) -> Option<(Arc<str>, Arc<str>, usize)> {
+		let (trigger, content, end) = self.find_raw(text)?;
+		match ctx {
+			Some(ctx) => Some((trigger, Arc::from(expand_content(&content, ctx)), end)),
+			None => Some((trigger, content, end)),
+		}
 	}
 
+	/// Like [`Self::find_match_with`], but strips the first `{cursor}` marker
+	/// from the content and returns its byte offset as the last element. Later
+	/// markers are dropped; without one the offset is the content length.
+	pub fn find_match_with_cursor(&self, text: &str, ctx: Option<&ExpandContext>) -> Option<CursorMatch> {
+		let (trigger, content, end) = self.find_raw(text)?;
+		let expand = |part: &str| ctx.map_or_else(|| part.to_owned(), |ctx| expand_content(part, ctx));
+
+		let (before, after) = content.split_once(CURSOR_MARKER).unwrap_or((&content, ""));
+		let mut expanded = expand(before);
+		let cursor = expanded.len();
+		expanded.push_str(&expand(&after.replace(CURSOR_MARKER, "")));
+		Some((trigger, Arc::from(expanded), end, cursor))
+	}
+
+	// Literal and regex triggers compete on the rightmost end, then on length,
+	// or on the leftmost start under `MatchPolicy::LeftmostFirst`; a literal
+	// wins a tie.
 	#[allow(clippy::significant_drop_tightening)]
-	pub fn find_match(&self, text: &str) -> Option<(Arc<str>, Arc<str>, usize)> {
-		let automaton_guard = self.automaton.read();
-		let automaton = automaton_guard.as_ref()?;
-		let last_match = automaton.find_iter(text).last()?;
-		let pattern_idx = last_match.pattern().as_usize();
-		let match_end = last_match.end();
-		drop(automaton_guard);
+	fn find_raw(&self, text: &str) -> Option<(Arc<str>, Arc<str>, usize)> {
+		let policy = self.match_policy;
+		let literal = self.automaton.read().as_ref().and_then(|automaton| {
+			let found = match policy {
+				MatchPolicy::LeftmostLongest => automaton.find_iter(text).last(),
+				MatchPolicy::LeftmostFirst => automaton.find(text),
+				MatchPolicy::Standard => automaton.find_overlapping_iter(text).max_by_key(|m| (m.end(), m.len())),
+			}?;
+			let owner = *self.pattern_owners.read().get(found.pattern().as_usize())?;
+			Some((found.start(), found.end(), owner))
+		});
+		let regex = self.regexes.read().as_ref().and_then(|regexes| regexes.find(text, policy));
+		let (start, match_end, snippet_idx) = if policy.picks_first() {
+			literal.into_iter().chain(regex).min_by_key(|&(start, ..)| start)?
+		} else {
+			regex.into_iter().chain(literal).max_by_key(|&(start, end, _)| (end, end - start))?
+		};
+		let typed = &text[start..match_end];
 
 		let snippets = self.snippets.read();
-		let snippet = snippets.get(pattern_idx)?;
-		let result = (Arc::clone(&snippet.trigger), Arc::clone(&snippet.content), match_end);
+		let snippet = snippets.get(snippet_idx)?;
+		let result = if snippet.is_regex {
+			(Arc::from(typed), Arc::clone(&snippet.content), match_end)
+		} else if snippet.match_case && typed != &*snippet.trigger {
+			let content = CasePattern::detect(typed).apply(&snippet.content);
+			(Arc::from(typed), Arc::from(content), match_end)
+		} else {
+			(Arc::clone(&snippet.trigger), Arc::clone(&snippet.content), match_end)
+		};
+		let id = snippet.id.clone();
 		drop(snippets);
 
+		self.record_use(id);
 		Some(result)
 	}
 
+	/// Snippet ids with how often each has matched, most used first.
+	pub fn usage_stats(&self) -> Vec<(String, u64)> {
+		let mut stats: Vec<(String, u64)> = self.usage.lock().iter().map(|(id, &count)| (id.clone(), count)).collect();
+		stats.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+		stats
+	}
+
+	// Queued while the counts are locked so snapshots reach the writer in order.
+	#[allow(clippy::significant_drop_tightening)]
+	fn record_use(&self, id: String) {
+		let mut usage = self.usage.lock();
+		*usage.entry(id).or_default() += 1;
+		let Some(path) = &self.usage_path else {
+			return;
+		};
+		let counts: Vec<UsageCount> = usage.iter().map(|(id, &count)| UsageCount { id: id.clone(), count }).collect();
+		save_to_disk_async(path, counts);
+	}
+
 	pub fn stats(&self) -> (usize, usize) {
 		let snippets = self.snippets.read();
 		let total = snippets.len();
@@ -85,8 +496,22 @@ mod tests {
 		let matcher = SnippetMatcher::new();
 
 		let snippets = vec![
-			Snippet { id: "1".to_owned(), trigger: "\\email".into(), content: "test@example.com".into(), enabled: true },
-			Snippet { id: "2".to_owned(), trigger: "\\phone".into(), content: "123-456-7890".into(), enabled: true },
+			Snippet {
+				id:         "1".to_owned(),
+				trigger:    "\\email".into(),
+				content:    "test@example.com".into(),
+				enabled:    true,
+				match_case: false,
+				is_regex:   false,
+			},
+			Snippet {
+				id:         "2".to_owned(),
+				trigger:    "\\phone".into(),
+				content:    "123-456-7890".into(),
+				enabled:    true,
+				match_case: false,
+				is_regex:   false,
+			},
 		];
 
 		matcher.update_snippets(snippets);
@@ -102,8 +527,14 @@ mod tests {
 	fn test_rightmost_match() {
 		let matcher = SnippetMatcher::new();
 
-		let snippets =
-			vec![Snippet { id: "1".to_owned(), trigger: "\\test".into(), content: "replacement".into(), enabled: true }];
+		let snippets = vec![Snippet {
+			id:         "1".to_owned(),
+			trigger:    "\\test".into(),
+			content:    "replacement".into(),
+			enabled:    true,
+			match_case: false,
+			is_regex:   false,
+		}];
 
 		matcher.update_snippets(snippets);
 
@@ -117,8 +548,22 @@ mod tests {
 		let matcher = SnippetMatcher::new();
 
 		let snippets = vec![
-			Snippet { id: "1".to_owned(), trigger: "\\enabled".into(), content: "yes".into(), enabled: true },
-			Snippet { id: "2".to_owned(), trigger: "\\disabled".into(), content: "no".into(), enabled: false },
+			Snippet {
+				id:         "1".to_owned(),
+				trigger:    "\\enabled".into(),
+				content:    "yes".into(),
+				enabled:    true,
+				match_case: false,
+				is_regex:   false,
+			},
+			Snippet {
+				id:         "2".to_owned(),
+				trigger:    "\\disabled".into(),
+				content:    "no".into(),
+				enabled:    false,
+				match_case: false,
+				is_regex:   false,
+			},
 		];
 
 		matcher.update_snippets(snippets);
@@ -126,4 +571,277 @@ mod tests {
 		assert!(matcher.find_match("\\enabled").is_some());
 		assert!(matcher.find_match("\\disabled").is_none());
 	}
+
+	#[test]
+	fn test_blank_triggers_ignored() {
+		let matcher = SnippetMatcher::new();
+		let snippet = |id: &str, trigger: &str| Snippet {
+			id:         id.to_owned(),
+			trigger:    trigger.into(),
+			content:    "x".into(),
+			enabled:    true,
+			match_case: false,
+			is_regex:   false,
+		};
+
+		matcher.update_snippets(vec![snippet("1", ""), snippet("2", "  "), snippet("3", "\\ok")]);
+
+		assert!(matcher.find_match("hello ").is_none());
+		assert!(matcher.find_match("\\ok").is_some());
+	}
+
+	#[test]
+	fn test_dto_round_trip() {
+		let stored = snippet_storage::Snippet::with_all(
+			"1".to_owned(),
+			"\\sig".to_owned(),
+			"Cheers".to_owned(),
+			true,
+			"Mail".to_owned(),
+		)
+		.match_case(true);
+
+		let json = serde_json::to_string(&SnippetDTO::from(stored.clone())).unwrap();
+		let dto: SnippetDTO = serde_json::from_str(&json).unwrap();
+		assert_eq!(snippet_storage::Snippet::from(dto.clone()), stored);
+
+		let snippet = Snippet::from(dto);
+		assert_eq!(snippet.id, stored.id);
+		assert_eq!(&*snippet.trigger, stored.trigger);
+		assert_eq!(&*snippet.content, stored.content);
+		assert!(snippet.enabled);
+		assert!(snippet.match_case);
+
+		let legacy: SnippetDTO =
+			serde_json::from_str(r#"{"id":"2","trigger":"\\x","content":"y","enabled":false}"#).unwrap();
+		assert_eq!(legacy.category, "General");
+	}
+
+	#[test]
+	fn test_match_case_expansion() {
+		let matcher = SnippetMatcher::new();
+
+		let snippets = vec![
+			Snippet {
+				id:         "1".to_owned(),
+				trigger:    "btw".into(),
+				content:    "by the way".into(),
+				enabled:    true,
+				match_case: true,
+				is_regex:   false,
+			},
+			Snippet {
+				id:         "2".to_owned(),
+				trigger:    "ty".into(),
+				content:    "thank you".into(),
+				enabled:    true,
+				match_case: false,
+				is_regex:   false,
+			},
+		];
+
+		matcher.update_snippets(snippets);
+
+		let (trigger, content, _) = matcher.find_match("oh btw").unwrap();
+		assert_eq!((&*trigger, &*content), ("btw", "by the way"));
+
+		let (trigger, content, _) = matcher.find_match("oh BTW").unwrap();
+		assert_eq!((&*trigger, &*content), ("BTW", "BY THE WAY"));
+
+		let (trigger, content, _) = matcher.find_match("Btw").unwrap();
+		assert_eq!((&*trigger, &*content), ("Btw", "By the way"));
+
+		assert!(matcher.find_match("TY").is_none());
+	}
+
+	fn expand_ctx() -> ExpandContext {
+		let mut ctx = ExpandContext::new("copied text");
+		ctx.now = chrono::NaiveDate::from_ymd_opt(2024, 3, 9).unwrap().and_hms_opt(14, 5, 0).unwrap();
+		ctx
+	}
+
+	#[test]
+	fn test_expand_date_placeholders() {
+		let ctx = expand_ctx();
+		assert_eq!(expand_content("Sent on {date} at {time}", &ctx), "Sent on 2024-03-09 at 14:05");
+		assert_eq!(expand_content("{datetime}", &ctx), "2024-03-09 14:05");
+		assert_eq!(expand_content("{{clipboard}}", &ctx), "{copied text}");
+
+		let ctx = ctx.with_date_format("%d/%m/%Y");
+		assert_eq!(expand_content("{date}", &ctx), "09/03/2024");
+	}
+
+	#[test]
+	fn test_unknown_placeholder_passes_through() {
+		let ctx = expand_ctx().with_date_format("%Q");
+		assert_eq!(expand_content("{name} {date} {unclosed", &ctx), "{name} {date} {unclosed");
+	}
+
+	#[test]
+	fn test_find_match_expands_with_context() {
+		let matcher = SnippetMatcher::new();
+		matcher.update_snippets(vec![Snippet {
+			id:         "1".to_owned(),
+			trigger:    "\\paste".into(),
+			content:    "> {clipboard}".into(),
+			enabled:    true,
+			match_case: false,
+			is_regex:   false,
+		}]);
+
+		let (_, raw, _) = matcher.find_match("\\paste").unwrap();
+		assert_eq!(&*raw, "> {clipboard}");
+		let (_, expanded, _) = matcher.find_match_with("\\paste", Some(&expand_ctx())).unwrap();
+		assert_eq!(&*expanded, "> copied text");
+	}
+
+	fn cursor_matcher(content: &str) -> SnippetMatcher {
+		let matcher = SnippetMatcher::new();
+		matcher.update_snippets(vec![Snippet {
+			id:         "1".to_owned(),
+			trigger:    "\\tag".into(),
+			content:    content.into(),
+			enabled:    true,
+			match_case: false,
+			is_regex:   false,
+		}]);
+		matcher
+	}
+
+	#[test]
+	fn test_cursor_marker_mid_string() {
+		let matcher = cursor_matcher("<b>{cursor}</b>{cursor}");
+		let (_, content, _, caret) = matcher.find_match_with_cursor("\\tag", None).unwrap();
+		assert_eq!(&*content, "<b></b>");
+		assert_eq!(caret, 3);
+
+		let matcher = cursor_matcher("{clipboard} {cursor}!");
+		let (_, content, _, caret) = matcher.find_match_with_cursor("\\tag", Some(&expand_ctx())).unwrap();
+		assert_eq!(&*content, "copied text !");
+		assert_eq!(caret, "copied text ".len());
+	}
+
+	#[test]
+	fn test_cursor_defaults_to_content_end() {
+		let matcher = cursor_matcher("no marker");
+		let (_, content, _, caret) = matcher.find_match_with_cursor("\\tag", None).unwrap();
+		assert_eq!(&*content, "no marker");
+		assert_eq!(caret, content.len());
+	}
+
+	#[test]
+	fn test_regex_trigger() {
+		let matcher = SnippetMatcher::new();
+		matcher.update_snippets(vec![
+			Snippet {
+				id:         "1".to_owned(),
+				trigger:    r"#\d{4}".into(),
+				content:    "ticket".into(),
+				enabled:    true,
+				match_case: false,
+				is_regex:   true,
+			},
+			Snippet {
+				id:         "2".to_owned(),
+				trigger:    "\\sig".into(),
+				content:    "Cheers".into(),
+				enabled:    true,
+				match_case: false,
+				is_regex:   false,
+			},
+			Snippet {
+				id:         "3".to_owned(),
+				trigger:    "(unclosed".into(),
+				content:    "never".into(),
+				enabled:    true,
+				match_case: false,
+				is_regex:   true,
+			},
+		]);
+
+		let (trigger, content, end) = matcher.find_match("see #1234").unwrap();
+		assert_eq!((&*trigger, &*content, end), ("#1234", "ticket", 9));
+		assert!(matcher.find_match("see #12").is_none());
+
+		let (trigger, content, _) = matcher.find_match("#1234 \\sig").unwrap();
+		assert_eq!((&*trigger, &*content), ("\\sig", "Cheers"));
+	}
+
+	#[test]
+	fn test_usage_counts() {
+		let temp = tempfile::TempDir::new().unwrap();
+		let path = temp.path().join("usage.bin");
+		let snippets = || {
+			vec![
+				Snippet {
+					id:         "sig".to_owned(),
+					trigger:    "\\sig".into(),
+					content:    "Cheers".into(),
+					enabled:    true,
+					match_case: false,
+					is_regex:   false,
+				},
+				Snippet {
+					id:         "addr".to_owned(),
+					trigger:    "\\addr".into(),
+					content:    "1 Main St".into(),
+					enabled:    true,
+					match_case: false,
+					is_regex:   false,
+				},
+			]
+		};
+
+		let matcher = SnippetMatcher::with_usage_path(&path);
+		matcher.update_snippets(snippets());
+		matcher.find_match("\\sig").unwrap();
+		matcher.find_match("\\addr").unwrap();
+		matcher.find_match("again \\sig").unwrap();
+		assert!(matcher.find_match("nothing").is_none());
+		assert_eq!(matcher.usage_stats(), [("sig".to_owned(), 2), ("addr".to_owned(), 1)]);
+
+		assert!(storage_utils::flush_all());
+		let reopened = SnippetMatcher::with_usage_path(&path);
+		assert_eq!(reopened.usage_stats(), matcher.usage_stats());
+	}
+
+	#[test]
+	fn test_case_transforms() {
+		let ctx = ExpandContext::new("hello WORLD");
+		assert_eq!(expand_content("{upper:all done} {lower:SO LOUD}", &ctx), "ALL DONE so loud");
+		assert_eq!(expand_content("{title:the quick-brown fox}", &ctx), "The Quick-Brown Fox");
+		assert_eq!(expand_content("> {upper:{clipboard}}!", &ctx), "> HELLO WORLD!");
+		assert_eq!(expand_content("{title:{lower:{clipboard}} again}", &ctx), "Hello World Again");
+		assert_eq!(expand_content("{shout:{clipboard}} {upper:open", &ctx), "{shout:hello WORLD} {upper:open");
+	}
+
+	#[test]
+	fn test_match_policies() {
+		let snippet = |id: &str, trigger: &str| Snippet {
+			id:         id.to_owned(),
+			trigger:    trigger.into(),
+			content:    id.into(),
+			enabled:    true,
+			match_case: false,
+			is_regex:   false,
+		};
+		let content = |policy: MatchPolicy, text: &str| {
+			let matcher = SnippetMatcher::new().with_match_policy(policy);
+			matcher.update_snippets(vec![
+				snippet("one", "\\one"),
+				snippet("two", "\\two"),
+				snippet("abc", "abc"),
+				snippet("bcd", "bcd"),
+			]);
+			matcher.find_match(text).map(|(_, content, _)| content.to_string())
+		};
+
+		assert_eq!(content(MatchPolicy::default(), "\\one then \\two").as_deref(), Some("two"));
+		assert_eq!(content(MatchPolicy::LeftmostFirst, "\\one then \\two").as_deref(), Some("one"));
+		assert_eq!(content(MatchPolicy::Standard, "\\one then \\two").as_deref(), Some("two"));
+
+		assert_eq!(content(MatchPolicy::LeftmostLongest, "abcd").as_deref(), Some("abc"));
+		assert_eq!(content(MatchPolicy::LeftmostFirst, "abcd").as_deref(), Some("abc"));
+		assert_eq!(content(MatchPolicy::Standard, "abcd").as_deref(), Some("bcd"));
+	}
 }
@@ -1,34 +1,117 @@
 use std::{io, path::Path, sync::Arc};
 
 use bytecheck::CheckBytes;
-use rkyv::{Archive, Deserialize, Serialize};
+use rkyv::{Archive, Deserialize, Serialize, rancor::Error};
 use storage_utils::RkyvStorage;
 
+const SCHEMA_VERSION: u8 = 3;
+pub const UNCATEGORIZED: &str = "Uncategorized";
+
+fn category_name(category: &str) -> &str { if category.trim().is_empty() { UNCATEGORIZED } else { category } }
+
 #[derive(Archive, Deserialize, Serialize, CheckBytes, Debug, Clone, PartialEq, Eq)]
 #[rkyv(derive(Debug))]
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct Snippet {
-	pub id:       String,
-	pub trigger:  String,
-	pub content:  String,
-	pub enabled:  bool,
-	pub category: String,
+	pub id:         String,
+	pub trigger:    String,
+	pub content:    String,
+	pub enabled:    bool,
+	pub category:   String,
+	#[serde(default)]
+	pub is_regex:   bool,
+	#[serde(default)]
+	pub match_case: bool,
 }
 
 impl Snippet {
 	#[must_use]
 	pub fn new(trigger: String, content: String) -> Self {
-		Self { id: uuid::Uuid::new_v4().to_string(), trigger, content, enabled: true, category: "General".into() }
+		Self {
+			id: uuid::Uuid::new_v4().to_string(),
+			trigger,
+			content,
+			enabled: true,
+			category: "General".into(),
+			is_regex: false,
+			match_case: false,
+		}
 	}
 
 	#[must_use]
 	pub fn with_category(trigger: String, content: String, category: String) -> Self {
-		Self { id: uuid::Uuid::new_v4().to_string(), trigger, content, enabled: true, category }
+		Self {
+			id: uuid::Uuid::new_v4().to_string(),
+			trigger,
+			content,
+			enabled: true,
+			category,
+			is_regex: false,
+			match_case: false,
+		}
 	}
 
 	#[must_use]
 	pub const fn with_all(id: String, trigger: String, content: String, enabled: bool, category: String) -> Self {
-		Self { id, trigger, content, enabled, category }
+		Self { id, trigger, content, enabled, category, is_regex: false, match_case: false }
+	}
+
+	#[must_use]
+	pub const fn regex(mut self, is_regex: bool) -> Self {
+		self.is_regex = is_regex;
+		self
+	}
+
+	#[must_use]
+	pub const fn match_case(mut self, match_case: bool) -> Self {
+		self.match_case = match_case;
+		self
+	}
+
+	/// Whether the trigger has any non-whitespace character. Blank triggers
+	/// would match unpredictably, so storage refuses them.
+	#[must_use]
+	pub fn has_valid_trigger(&self) -> bool { !self.trigger.trim().is_empty() }
+}
+
+// Layout before `is_regex` was added, kept to read schema 1 and unversioned
+// files.
+#[derive(Archive, Deserialize, Serialize, CheckBytes)]
+struct SnippetV1 {
+	id:       String,
+	trigger:  String,
+	content:  String,
+	enabled:  bool,
+	category: String,
+}
+
+// Layout before `match_case` was added, written as schema 2.
+#[derive(Archive, Deserialize, Serialize, CheckBytes)]
+struct SnippetV2 {
+	id:       String,
+	trigger:  String,
+	content:  String,
+	enabled:  bool,
+	category: String,
+	is_regex: bool,
+}
+
+fn migrate(version: u8, bytes: &[u8]) -> Option<Vec<Snippet>> {
+	match version {
+		2 => {
+			let old = rkyv::from_bytes::<Vec<SnippetV2>, Error>(bytes).ok()?;
+			Some(
+				old
+					.into_iter()
+					.map(|s| Snippet::with_all(s.id, s.trigger, s.content, s.enabled, s.category).regex(s.is_regex))
+					.collect(),
+			)
+		}
+		0 | 1 => {
+			let old = rkyv::from_bytes::<Vec<SnippetV1>, Error>(bytes).ok()?;
+			Some(old.into_iter().map(|s| Snippet::with_all(s.id, s.trigger, s.content, s.enabled, s.category)).collect())
+		}
+		_ => None,
 	}
 }
 
@@ -37,7 +120,9 @@ pub struct SnippetStorage {
 }
 
 impl SnippetStorage {
-	pub fn new<P: AsRef<Path>>(path: P) -> io::Result<Self> { Ok(Self { storage: RkyvStorage::new(path)? }) }
+	pub fn new<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+		Ok(Self { storage: RkyvStorage::new_versioned(path, SCHEMA_VERSION, migrate)? })
+	}
 
 	#[must_use]
 	pub fn get_all(&self) -> Arc<Vec<Snippet>> { self.storage.get_all() }
@@ -45,25 +130,80 @@ impl SnippetStorage {
 	#[must_use]
 	pub fn get_enabled(&self) -> Vec<Snippet> { self.storage.get_filtered(|s| s.enabled) }
 
-	pub fn add(&self, snippet: Snippet) { self.storage.add_async(snippet); }
+	/// Distinct categories in sorted order, with blank ones listed as
+	/// [`UNCATEGORIZED`].
+	#[must_use]
+	pub fn categories(&self) -> Vec<String> {
+		let mut categories: Vec<String> =
+			self.storage.get_all().iter().map(|s| category_name(&s.category).to_owned()).collect();
+		categories.sort_unstable();
+		categories.dedup();
+		categories
+	}
+
+	#[must_use]
+	pub fn get_by_category(&self, category: &str) -> Vec<Snippet> {
+		self.storage.get_filtered(|s| category_name(&s.category) == category)
+	}
 
+	/// Adds `snippet`, or returns false if its trigger is blank.
+	#[must_use]
+	pub fn add(&self, snippet: Snippet) -> bool {
+		if !snippet.has_valid_trigger() {
+			return false;
+		}
+		self.storage.add_async(snippet);
+		true
+	}
+
+	/// Replaces the snippet with the same id. Returns false if there is none or
+	/// the new trigger is blank.
+	#[must_use]
 	pub fn update(&self, snippet: Snippet) -> bool {
-		self.storage.update_async(|snippets| {
+		if !snippet.has_valid_trigger() {
+			return false;
+		}
+		self.storage.update_async(move |snippets| {
 			snippets.iter_mut().find(|s| s.id == snippet.id).is_some_and(|s| {
-				*s = snippet;
+				*s = snippet.clone();
 				true
 			})
 		})
 	}
 
+	#[must_use]
 	pub fn delete(&self, id: &str) -> bool {
-		self.storage.update_async(|snippets| {
+		let id = id.to_owned();
+		self.storage.update_async(move |snippets| {
 			let before_len = snippets.len();
 			snippets.retain(|s| s.id != id);
 			snippets.len() != before_len
 		})
 	}
 
+	/// Triggers that fire ambiguously among enabled literal snippets: each
+	/// trigger shared by several snippets or a prefix of a longer trigger, with
+	/// the ids of every snippet whose trigger starts with it. Triggers are
+	/// compared as written, without the case variants `match_case` adds.
+	#[must_use]
+	pub fn find_conflicts(&self) -> Vec<(String, Vec<String>)> {
+		let mut snippets: Vec<Snippet> = self.storage.get_filtered(|s| s.enabled && !s.is_regex);
+		snippets.sort_by(|a, b| a.trigger.cmp(&b.trigger).then_with(|| a.id.cmp(&b.id)));
+
+		let mut conflicts: Vec<(String, Vec<String>)> = Vec::new();
+		for (idx, snippet) in snippets.iter().enumerate() {
+			if conflicts.last().is_some_and(|(trigger, _)| *trigger == snippet.trigger) {
+				continue;
+			}
+			let ids: Vec<String> =
+				snippets[idx..].iter().take_while(|s| s.trigger.starts_with(&snippet.trigger)).map(|s| s.id.clone()).collect();
+			if ids.len() > 1 {
+				conflicts.push((snippet.trigger.clone(), ids));
+			}
+		}
+		conflicts
+	}
+
 	#[must_use]
 	pub fn len(&self) -> usize { self.storage.len() }
 
@@ -79,17 +219,20 @@ impl SnippetStorage {
 
 	pub fn import_from_json(&self, json: &str, merge: bool) -> Result<usize, String> {
 		let imported: Vec<Snippet> = serde_json::from_str(json).map_err(|e| format!("Failed to parse JSON: {e}"))?;
+		self.import(imported, merge)
+	}
 
-		if !merge {
-			self.storage.clear().map_err(|e| format!("Failed to clear existing snippets: {e}"))?;
-		}
-
-		let mut count = 0;
-		for snippet in imported {
-			if self.storage.add(snippet).is_ok() {
-				count += 1;
-			}
+	pub fn import(&self, imported: Vec<Snippet>, merge: bool) -> Result<usize, String> {
+		let count = imported.len();
+		if merge {
+			self.storage.update(|snippets| {
+				snippets.extend(imported);
+				count > 0
+			})
+		} else {
+			self.storage.replace_all(imported).map(|()| true)
 		}
+		.map_err(|e| format!("Failed to import snippets: {e}"))?;
 
 		Ok(count)
 	}
@@ -116,7 +259,7 @@ mod tests {
 		let storage = SnippetStorage::new(temp.path()).unwrap();
 
 		let snippet = Snippet::new("\\email".to_owned(), "test@example.com".to_owned());
-		storage.add(snippet);
+		assert!(storage.add(snippet));
 
 		assert_eq!(storage.len(), 1);
 
@@ -134,7 +277,7 @@ mod tests {
 		{
 			let storage = SnippetStorage::new(&path).unwrap();
 			let snippet = Snippet::new("\\test".to_owned(), "Test content".to_owned());
-			storage.add(snippet);
+			assert!(storage.add(snippet));
 			storage.flush();
 		}
 
@@ -150,7 +293,7 @@ mod tests {
 		let storage = SnippetStorage::new(temp.path()).unwrap();
 
 		let snippet = Snippet::new("\\test".to_owned(), "Original".to_owned());
-		storage.add(snippet.clone());
+		assert!(storage.add(snippet.clone()));
 
 		let mut updated = snippet;
 		updated.content = "Updated".to_owned();
@@ -168,7 +311,7 @@ mod tests {
 
 		let snippet = Snippet::new("\\test".to_owned(), "Content".to_owned());
 		let id = snippet.id.clone();
-		storage.add(snippet);
+		assert!(storage.add(snippet));
 
 		assert_eq!(storage.len(), 1);
 		let deleted = storage.delete(&id);
@@ -181,8 +324,8 @@ mod tests {
 		let temp = NamedTempFile::new().unwrap();
 		let storage = SnippetStorage::new(temp.path()).unwrap();
 
-		storage.add(Snippet::new("\\email".to_owned(), "test@example.com".to_owned()));
-		storage.add(Snippet::with_category("\\phone".to_owned(), "123-456-7890".to_owned(), "Contact".to_owned()));
+		assert!(storage.add(Snippet::new("\\email".to_owned(), "test@example.com".to_owned())));
+		assert!(storage.add(Snippet::with_category("\\phone".to_owned(), "123-456-7890".to_owned(), "Contact".to_owned())));
 
 		let json = storage.export_to_json().unwrap();
 		assert!(json.contains("\\email"));
@@ -205,7 +348,7 @@ mod tests {
 		let temp = NamedTempFile::new().unwrap();
 		let storage = SnippetStorage::new(temp.path()).unwrap();
 
-		storage.add(Snippet::new("\\existing".to_owned(), "Existing".to_owned()));
+		assert!(storage.add(Snippet::new("\\existing".to_owned(), "Existing".to_owned())));
 
 		let json = r#"[{"id":"test-id","trigger":"\\new","content":"New","enabled":true,"category":"General"}]"#;
 
@@ -213,4 +356,140 @@ mod tests {
 		assert_eq!(count, 1);
 		assert_eq!(storage.len(), 2);
 	}
+
+	#[test]
+	fn test_import_is_atomic_for_readers() {
+		let temp = NamedTempFile::new().unwrap();
+		let storage = SnippetStorage::new(temp.path()).unwrap();
+		storage.import((0..5).map(|i| Snippet::new(format!("\\old{i}"), "x".to_owned())).collect(), false).unwrap();
+
+		let done = std::sync::atomic::AtomicBool::new(false);
+		std::thread::scope(|scope| {
+			scope.spawn(|| {
+				while !done.load(std::sync::atomic::Ordering::Relaxed) {
+					let len = storage.get_all().len();
+					assert!(len == 5 || len == 20, "observed partial import of {len} snippets");
+				}
+			});
+
+			for round in 0..20 {
+				let count = if round % 2 == 0 { 20 } else { 5 };
+				let snippets = (0..count).map(|i| Snippet::new(format!("\\new{i}"), "y".to_owned())).collect();
+				assert_eq!(storage.import(snippets, false).unwrap(), count);
+			}
+			done.store(true, std::sync::atomic::Ordering::Relaxed);
+		});
+
+		assert_eq!(storage.len(), 5);
+	}
+
+	#[test]
+	fn test_schema_1_file_migrates() {
+		let temp = NamedTempFile::new().unwrap();
+		let old = SnippetV1 {
+			id:       "1".to_owned(),
+			trigger:  "\\sig".to_owned(),
+			content:  "Cheers".to_owned(),
+			enabled:  true,
+			category: "Mail".to_owned(),
+		};
+		storage_utils::save_to_disk_versioned(temp.path(), &vec![old], 1).unwrap();
+
+		let storage = SnippetStorage::new(temp.path()).unwrap();
+		let snippets = storage.get_all();
+		assert_eq!(snippets[0], Snippet::with_all("1".into(), "\\sig".into(), "Cheers".into(), true, "Mail".into()));
+		assert!(!snippets[0].is_regex);
+	}
+
+	#[test]
+	fn test_schema_2_file_migrates() {
+		let temp = NamedTempFile::new().unwrap();
+		let old = SnippetV2 {
+			id:       "1".to_owned(),
+			trigger:  "^\\d+$".to_owned(),
+			content:  "number".to_owned(),
+			enabled:  true,
+			category: "General".to_owned(),
+			is_regex: true,
+		};
+		storage_utils::save_to_disk_versioned(temp.path(), &vec![old], 2).unwrap();
+
+		let storage = SnippetStorage::new(temp.path()).unwrap();
+		let snippet = &storage.get_all()[0];
+		assert!(snippet.is_regex);
+		assert!(!snippet.match_case);
+
+		assert!(storage.update(snippet.clone().match_case(true)));
+		storage.flush();
+		assert!(SnippetStorage::new(temp.path()).unwrap().get_all()[0].match_case);
+	}
+
+	#[test]
+	fn test_find_conflicts() {
+		let temp = NamedTempFile::new().unwrap();
+		let storage = SnippetStorage::new(temp.path()).unwrap();
+		let snippet = |id: &str, trigger: &str, enabled: bool| {
+			Snippet::with_all(id.into(), trigger.into(), "x".into(), enabled, "General".into())
+		};
+		storage
+			.import(
+				vec![
+					snippet("a", "\\email", true),
+					snippet("b", "\\email", true),
+					snippet("c", "\\addr", true),
+					snippet("d", "\\addrwork", true),
+					snippet("e", "\\phone", true),
+					snippet("f", "\\phone", false),
+					snippet("g", "\\Phone", true),
+				],
+				false,
+			)
+			.unwrap();
+
+		assert_eq!(storage.find_conflicts(), [
+			("\\addr".to_owned(), vec!["c".to_owned(), "d".to_owned()]),
+			("\\email".to_owned(), vec!["a".to_owned(), "b".to_owned()]),
+		]);
+	}
+
+	#[test]
+	fn test_categories() {
+		let temp = NamedTempFile::new().unwrap();
+		let storage = SnippetStorage::new(temp.path()).unwrap();
+		let snippet =
+			|trigger: &str, category: &str| Snippet::with_category(trigger.to_owned(), "x".to_owned(), category.to_owned());
+		storage
+			.import(
+				vec![
+					snippet("\\a", "Work"),
+					snippet("\\b", "Mail"),
+					snippet("\\c", "Work"),
+					snippet("\\d", ""),
+					snippet("\\e", "General"),
+				],
+				false,
+			)
+			.unwrap();
+
+		assert_eq!(storage.categories(), ["General", "Mail", UNCATEGORIZED, "Work"]);
+		let work: Vec<String> = storage.get_by_category("Work").into_iter().map(|s| s.trigger).collect();
+		assert_eq!(work, ["\\a", "\\c"]);
+		assert_eq!(storage.get_by_category(UNCATEGORIZED)[0].trigger, "\\d");
+		assert!(storage.get_by_category("Missing").is_empty());
+	}
+
+	#[test]
+	fn test_blank_triggers_rejected() {
+		let temp = NamedTempFile::new().unwrap();
+		let storage = SnippetStorage::new(temp.path()).unwrap();
+
+		assert!(!storage.add(Snippet::new(String::new(), "x".to_owned())));
+		assert!(!storage.add(Snippet::new(" \t".to_owned(), "x".to_owned())));
+		assert!(storage.is_empty());
+
+		let snippet = Snippet::with_all("1".into(), "\\sig".into(), "Cheers".into(), true, "Mail".into());
+		assert!(storage.add(snippet));
+		assert!(!storage.update(Snippet::with_all("1".into(), " ".into(), "Cheers".into(), true, "Mail".into())));
+		assert_eq!(storage.get_all()[0].trigger, "\\sig");
+	}
 }
@@ -0,0 +1,201 @@
+use std::{ffi::OsStr, fs, io, path::Path, sync::atomic::Ordering};
+
+use crate::{Durability, OsSyncer, SYNC_DURABILITY, async_writer, is_open, write_atomic};
+
+// Archive layout: magic, then per member a u32 name length, the name, a u64
+// data length, a u64 checksum of the data and the data itself, all
+// little-endian.
+const BACKUP_MAGIC: &[u8; 4] = b"SMB\x01";
+
+pub struct RestoredFile {
+	pub name:   String,
+	pub result: io::Result<()>,
+}
+
+fn invalid(message: &str) -> io::Error { io::Error::new(io::ErrorKind::InvalidData, message.to_owned()) }
+
+fn checksum(data: &[u8]) -> u64 {
+	data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3))
+}
+
+fn is_plain_name(name: &str) -> bool { Path::new(name).file_name() == Some(OsStr::new(name)) }
+
+pub fn backup_all(paths: &[&Path], out: &Path) -> io::Result<Vec<String>> {
+	async_writer().flush();
+
+	let mut archive = BACKUP_MAGIC.to_vec();
+	let mut names: Vec<String> = Vec::with_capacity(paths.len());
+
+	for path in paths.iter().filter(|p| p.exists()) {
+		let name = path
+			.file_name()
+			.and_then(OsStr::to_str)
+			.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid file name"))?;
+		if names.iter().any(|n| n == name) {
+			return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("duplicate file name {name}")));
+		}
+
+		let data = fs::read(path)?;
+		let name_len =
+			u32::try_from(name.len()).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "name too long"))?;
+		archive.extend_from_slice(&name_len.to_le_bytes());
+		archive.extend_from_slice(name.as_bytes());
+		archive.extend_from_slice(&(data.len() as u64).to_le_bytes());
+		archive.extend_from_slice(&checksum(&data).to_le_bytes());
+		archive.extend_from_slice(&data);
+		names.push(name.to_owned());
+	}
+
+	write_atomic(out, &archive, Durability::from_u8(SYNC_DURABILITY.load(Ordering::Relaxed)), &OsSyncer)?;
+	Ok(names)
+}
+
+fn read_u32(bytes: &[u8]) -> Option<(u32, &[u8])> {
+	let (head, rest) = bytes.split_first_chunk::<4>()?;
+	Some((u32::from_le_bytes(*head), rest))
+}
+
+fn read_u64(bytes: &[u8]) -> Option<(u64, &[u8])> {
+	let (head, rest) = bytes.split_first_chunk::<8>()?;
+	Some((u64::from_le_bytes(*head), rest))
+}
+
+type Member<'a> = (&'a [u8], u64, &'a [u8], &'a [u8]);
+
+fn read_member(bytes: &[u8]) -> Option<Member<'_>> {
+	let (name_len, rest) = read_u32(bytes)?;
+	let (name, rest) = rest.split_at_checked(usize::try_from(name_len).ok()?)?;
+	let (data_len, rest) = read_u64(rest)?;
+	let (expected, rest) = read_u64(rest)?;
+	let (data, rest) = rest.split_at_checked(usize::try_from(data_len).ok()?)?;
+	Some((name, expected, data, rest))
+}
+
+/// Writes every member of `archive` into `dir`. Only an unreadable archive is
+/// an error; a damaged member is reported in its own result and the rest are
+/// still restored.
+///
+/// Nothing is written while a storage is open on any of the target files,
+/// since it would keep serving and later save its old contents. Close those
+/// storages first and reopen them afterwards.
+pub fn restore_all(archive: &Path, dir: &Path) -> io::Result<Vec<RestoredFile>> {
+	let bytes = fs::read(archive)?;
+	let mut rest = bytes.strip_prefix(BACKUP_MAGIC).ok_or_else(|| invalid("not a backup archive"))?;
+	fs::create_dir_all(dir)?;
+	async_writer().flush();
+
+	let mut members = rest;
+	while let Some((name, _, _, tail)) = read_member(members) {
+		let name = String::from_utf8_lossy(name);
+		if is_plain_name(&name) && is_open(&dir.join(&*name)) {
+			return Err(io::Error::new(io::ErrorKind::ResourceBusy, format!("{name} is open; close it before restoring")));
+		}
+		members = tail;
+	}
+
+	let durability = Durability::from_u8(SYNC_DURABILITY.load(Ordering::Relaxed));
+	let mut restored = Vec::new();
+
+	while !rest.is_empty() {
+		let Some((name, expected, data, tail)) = read_member(rest) else {
+			restored.push(RestoredFile { name: String::new(), result: Err(invalid("truncated archive")) });
+			break;
+		};
+		rest = tail;
+
+		let name = String::from_utf8_lossy(name).into_owned();
+		let result = if !is_plain_name(&name) {
+			Err(invalid("invalid file name"))
+		} else if checksum(data) != expected {
+			Err(invalid("checksum mismatch"))
+		} else {
+			write_atomic(&dir.join(&name), data, durability, &OsSyncer)
+		};
+		restored.push(RestoredFile { name, result });
+	}
+
+	Ok(restored)
+}
+
+#[cfg(test)]
+mod tests {
+	use bytecheck::CheckBytes;
+	use rkyv::{Archive, Deserialize, Serialize};
+	use tempfile::TempDir;
+
+	use super::*;
+	use crate::{RkyvStorage, load_from_disk};
+
+	#[derive(Archive, Deserialize, Serialize, Debug, Clone, PartialEq, CheckBytes)]
+	#[rkyv(derive(Debug))]
+	struct Entry {
+		text: String,
+	}
+
+	fn entry(text: &str) -> Entry { Entry { text: text.to_owned() } }
+
+	#[test]
+	fn test_backup_and_restore() -> io::Result<()> {
+		let source = TempDir::new()?;
+		let snippets = RkyvStorage::<Entry>::new(source.path().join("snippets.bin"))?;
+		snippets.replace_all(vec![entry("sig"), entry("addr")])?;
+		let clipboard = RkyvStorage::<Entry>::new(source.path().join("clipboard.bin"))?;
+		clipboard.add_async(entry("copied"));
+
+		let archive = source.path().join("backup.smb");
+		let missing = source.path().join("missing.bin");
+		let names = backup_all(&[snippets.path(), clipboard.path(), &missing], &archive)?;
+		assert_eq!(names, ["snippets.bin", "clipboard.bin"]);
+
+		let target = TempDir::new()?;
+		let restored = restore_all(&archive, target.path())?;
+		assert!(restored.iter().all(|r| r.result.is_ok()));
+		assert_eq!(load_from_disk::<Entry>(&target.path().join("snippets.bin"))?, vec![entry("sig"), entry("addr")]);
+		assert_eq!(load_from_disk::<Entry>(&target.path().join("clipboard.bin"))?, vec![entry("copied")]);
+		Ok(())
+	}
+
+	#[test]
+	fn test_corrupt_member_does_not_abort_restore() -> io::Result<()> {
+		let source = TempDir::new()?;
+		fs::write(source.path().join("a.bin"), b"first")?;
+		fs::write(source.path().join("b.bin"), b"second")?;
+		let archive = source.path().join("backup.smb");
+		backup_all(&[&source.path().join("a.bin"), &source.path().join("b.bin")], &archive)?;
+
+		let mut bytes = fs::read(&archive)?;
+		let offset = bytes.windows(5).position(|w| w == b"first").unwrap_or_default();
+		bytes[offset] ^= 0xff;
+		fs::write(&archive, bytes)?;
+
+		let target = TempDir::new()?;
+		let restored = restore_all(&archive, target.path())?;
+		assert_eq!(restored.len(), 2);
+		assert!(restored[0].result.is_err());
+		assert!(!target.path().join("a.bin").exists());
+		assert!(restored[1].result.is_ok());
+		assert_eq!(fs::read(target.path().join("b.bin"))?, b"second");
+		Ok(())
+	}
+
+	#[test]
+	fn test_restore_refuses_open_storage() -> io::Result<()> {
+		let source = TempDir::new()?;
+		let snippets = RkyvStorage::<Entry>::new(source.path().join("snippets.bin"))?;
+		snippets.add(entry("backed up"))?;
+		let archive = source.path().join("backup.smb");
+		backup_all(&[snippets.path()], &archive)?;
+
+		let target = TempDir::new()?;
+		let open = RkyvStorage::<Entry>::new(target.path().join("snippets.bin"))?;
+		open.add(entry("current"))?;
+		let busy = restore_all(&archive, &target.path().join(".")).err().map(|e| e.kind());
+		assert_eq!(busy, Some(io::ErrorKind::ResourceBusy));
+		assert_eq!(load_from_disk::<Entry>(open.path())?, vec![entry("current")]);
+
+		drop(open);
+		assert!(restore_all(&archive, target.path())?.iter().all(|r| r.result.is_ok()));
+		assert_eq!(load_from_disk::<Entry>(&target.path().join("snippets.bin"))?, vec![entry("backed up")]);
+		Ok(())
+	}
+}
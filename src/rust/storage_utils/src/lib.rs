@@ -1,10 +1,246 @@
-use std::{fs::{self, OpenOptions}, io::{self, BufWriter, Write}, path::{Path, PathBuf}, sync::{Arc, OnceLock, mpsc}, thread};
+mod backup;
 
-use parking_lot::RwLock;
+use std::{fs::{self, File, OpenOptions}, io::{self, BufWriter, Write}, path::{Path, PathBuf}, sync::{Arc, OnceLock, atomic::{AtomicU8, Ordering}, mpsc}, thread, time::SystemTime};
+
+pub use backup::{RestoredFile, backup_all, restore_all};
+use chacha20poly1305::{KeyInit, XChaCha20Poly1305, XNonce, aead::{Aead, AeadCore, OsRng}};
+use parking_lot::{Mutex, RwLock};
 use rkyv::{Archive, Deserialize, Serialize, api::high::HighValidator, bytecheck::CheckBytes, rancor::Error};
 
 static ASYNC_WRITER: OnceLock<AsyncWriter> = OnceLock::new();
 
+// Files backing a live `RkyvStorage`, so a restore cannot replace data that an
+// open handle holds in memory and would write back.
+static OPEN_FILES: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+// Saves from `save_to_disk_async` that the writer has not run yet, at most one
+// per path.
+static PENDING_SAVES: Mutex<Vec<(PathBuf, SaveJob)>> = Mutex::new(Vec::new());
+
+// Prefixed to zstd-compressed and encrypted files so plain rkyv files written
+// before either was enabled still load.
+const COMPRESSED_MAGIC: &[u8; 4] = b"RKZ\x01";
+const ENCRYPTED_MAGIC: &[u8; 4] = b"RKE\x01";
+const COMPRESSION_LEVEL: i32 = 3;
+const NONCE_LEN: usize = 24;
+
+// Every payload starts with the three bytes `RKV` and a schema version byte,
+// followed by the rkyv archive; compression and encryption wrap the whole
+// payload. Files without the prefix predate versioning and read as version 0.
+const VERSION_MAGIC: &[u8; 3] = b"RKV";
+const LEGACY_VERSION: u8 = 0;
+pub const DEFAULT_SCHEMA_VERSION: u8 = 1;
+
+/// Converts an archive written under an older schema `version` into the
+/// current layout. `bytes` is the aligned rkyv archive without the prefix.
+pub type Migrator<T> = fn(version: u8, bytes: &[u8]) -> Option<Vec<T>>;
+
+fn serialize<T>(items: &Vec<T>, version: u8) -> io::Result<Vec<u8>>
+where
+	T: for<'a> Serialize<
+		rkyv::api::high::HighSerializer<rkyv::util::AlignedVec, rkyv::ser::allocator::ArenaHandle<'a>, Error>,
+	>,
+{
+	let archive = rkyv::to_bytes::<Error>(items).map_err(|e| io::Error::other(format!("rkyv: {e:?}")))?;
+	let mut bytes = Vec::with_capacity(VERSION_MAGIC.len() + 1 + archive.len());
+	bytes.extend_from_slice(VERSION_MAGIC);
+	bytes.push(version);
+	bytes.extend_from_slice(&archive);
+	Ok(bytes)
+}
+
+fn deserialize<T>(path: &Path, payload: &[u8], version: u8, migrate: Option<Migrator<T>>) -> Option<Vec<T>>
+where
+	T: Archive,
+	T::Archived:
+		for<'a> CheckBytes<HighValidator<'a, Error>> + Deserialize<T, rkyv::rancor::Strategy<rkyv::de::Pool, Error>>,
+{
+	let (found, archive) = match payload.strip_prefix(VERSION_MAGIC).and_then(<[u8]>::split_first) {
+		Some((&found, archive)) => (found, archive),
+		None => (LEGACY_VERSION, payload),
+	};
+	let mut aligned = rkyv::util::AlignedVec::<16>::new();
+	aligned.extend_from_slice(archive);
+
+	if (found == version || found == LEGACY_VERSION)
+		&& let Ok(items) = rkyv::from_bytes::<Vec<T>, Error>(&aligned)
+	{
+		return Some(items);
+	}
+	if found != version
+		&& let Some(items) = migrate.and_then(|migrate| migrate(found, &aligned))
+	{
+		return Some(items);
+	}
+
+	eprintln!("[storage] Could not read {} (schema version {found}, expected {version})", path.display());
+	None
+}
+
+#[derive(Clone, Copy)]
+enum Encoding {
+	Plain,
+	Compressed,
+	Encrypted([u8; 32]),
+}
+
+impl Encoding {
+	fn encode(self, bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+		match self {
+			Self::Plain => Ok(bytes),
+			Self::Compressed => compress(&bytes),
+			Self::Encrypted(key) => encrypt(&key, &bytes),
+		}
+	}
+
+	const fn key(&self) -> Option<&[u8; 32]> {
+		match self {
+			Self::Encrypted(key) => Some(key),
+			Self::Plain | Self::Compressed => None,
+		}
+	}
+}
+
+fn compress(bytes: &[u8]) -> io::Result<Vec<u8>> {
+	let mut out = COMPRESSED_MAGIC.to_vec();
+	zstd::stream::copy_encode(bytes, &mut out, COMPRESSION_LEVEL)?;
+	Ok(out)
+}
+
+fn encrypt(key: &[u8; 32], bytes: &[u8]) -> io::Result<Vec<u8>> {
+	let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+	let sealed =
+		XChaCha20Poly1305::new(key.into()).encrypt(&nonce, bytes).map_err(|_| io::Error::other("encryption failed"))?;
+
+	let mut out = Vec::with_capacity(ENCRYPTED_MAGIC.len() + NONCE_LEN + sealed.len());
+	out.extend_from_slice(ENCRYPTED_MAGIC);
+	out.extend_from_slice(&nonce);
+	out.extend_from_slice(&sealed);
+	Ok(out)
+}
+
+fn decrypt(key: &[u8; 32], bytes: &[u8]) -> io::Result<Vec<u8>> {
+	let invalid = || io::Error::new(io::ErrorKind::InvalidData, "decryption failed");
+	let (nonce, sealed) = bytes.split_at_checked(NONCE_LEN).ok_or_else(invalid)?;
+	XChaCha20Poly1305::new(key.into()).decrypt(XNonce::from_slice(nonce), sealed).map_err(|_| invalid())
+}
+
+// fsync makes writes survive a crash right after the rename, at the cost of a
+// blocking disk flush per save; async saves skip it by default to keep bursts
+// cheap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Durability {
+	None,
+	File,
+	FileAndDirectory,
+}
+
+impl Durability {
+	#[must_use]
+	pub const fn from_u8(value: u8) -> Self {
+		match value {
+			0 => Self::None,
+			1 => Self::File,
+			_ => Self::FileAndDirectory,
+		}
+	}
+}
+
+static SYNC_DURABILITY: AtomicU8 = AtomicU8::new(Durability::FileAndDirectory as u8);
+static ASYNC_DURABILITY: AtomicU8 = AtomicU8::new(Durability::None as u8);
+
+pub fn set_sync_durability(durability: Durability) { SYNC_DURABILITY.store(durability as u8, Ordering::Relaxed); }
+
+pub fn set_async_durability(durability: Durability) { ASYNC_DURABILITY.store(durability as u8, Ordering::Relaxed); }
+
+trait Syncer {
+	fn sync_file(&self, file: &File) -> io::Result<()>;
+	fn sync_dir(&self, dir: &Path) -> io::Result<()>;
+}
+
+struct OsSyncer;
+
+impl Syncer for OsSyncer {
+	fn sync_file(&self, file: &File) -> io::Result<()> { file.sync_all() }
+
+	fn sync_dir(&self, dir: &Path) -> io::Result<()> {
+		#[cfg(unix)]
+		{
+			File::open(dir)?.sync_all()
+		}
+		#[cfg(not(unix))]
+		{
+			let _ = dir;
+			Ok(())
+		}
+	}
+}
+
+fn write_atomic(path: &Path, data: &[u8], durability: Durability, syncer: &dyn Syncer) -> io::Result<()> {
+	let temp_path = path.with_extension("tmp");
+	let file = OpenOptions::new().write(true).create(true).truncate(true).open(&temp_path)?;
+	let mut writer = BufWriter::new(file);
+	writer.write_all(data)?;
+	writer.flush()?;
+	let file = writer.into_inner().map_err(io::IntoInnerError::into_error)?;
+	if durability != Durability::None {
+		syncer.sync_file(&file)?;
+	}
+	drop(file);
+	fs::rename(temp_path, path)?;
+	if durability == Durability::FileAndDirectory
+		&& let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty())
+	{
+		syncer.sync_dir(parent)?;
+	}
+	Ok(())
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+	let mut backup = path.as_os_str().to_owned();
+	backup.push(".bak");
+	PathBuf::from(backup)
+}
+
+// Keeps the file about to be replaced as `<path>.bak` so a primary that later
+// fails to validate can be recovered. A hard link avoids copying the data.
+fn keep_backup(path: &Path) {
+	if !path.exists() {
+		return;
+	}
+	let backup = backup_path(path);
+	let _ = fs::remove_file(&backup);
+	if fs::hard_link(path, &backup).is_err() {
+		let _ = fs::copy(path, &backup);
+	}
+}
+
+// Advisory lock on a `<path>.lock` sidecar that serializes writers across
+// processes sharing one storage file. Released when the file is dropped.
+fn lock_file(path: &Path) -> io::Result<File> {
+	let mut lock_path = path.as_os_str().to_owned();
+	lock_path.push(".lock");
+	let file = OpenOptions::new().write(true).create(true).truncate(false).open(PathBuf::from(lock_path))?;
+	file.lock()?;
+	Ok(file)
+}
+
+// Resolves the directory so differently spelled paths to one file compare
+// equal; the file itself may not exist yet.
+fn file_identity(path: &Path) -> PathBuf {
+	path
+		.parent()
+		.and_then(|parent| fs::canonicalize(if parent.as_os_str().is_empty() { Path::new(".") } else { parent }).ok())
+		.zip(path.file_name())
+		.map_or_else(|| path.to_path_buf(), |(dir, name)| dir.join(name))
+}
+
+fn is_open(path: &Path) -> bool {
+	let identity = file_identity(path);
+	OPEN_FILES.lock().contains(&identity)
+}
+
 fn async_writer() -> &'static AsyncWriter { ASYNC_WRITER.get_or_init(AsyncWriter::new) }
 
 struct AsyncWriter {
@@ -12,7 +248,7 @@ struct AsyncWriter {
 }
 
 enum WriteOp {
-	Save { path: PathBuf, data: Vec<u8> },
+	Run(Box<dyn FnOnce() + Send>),
 	Flush { respond_to: mpsc::Sender<()> },
 	Shutdown,
 }
@@ -26,11 +262,7 @@ impl AsyncWriter {
 			.spawn(move || {
 				while let Ok(op) = rx.recv() {
 					match op {
-						WriteOp::Save { path, data } => {
-							if let Err(e) = Self::write_atomic(&path, &data) {
-								eprintln!("[AsyncWriter] Failed to write {}: {}", path.display(), e);
-							}
-						}
+						WriteOp::Run(job) => job(),
 						WriteOp::Flush { respond_to } => {
 							let _ = respond_to.send(());
 						}
@@ -43,24 +275,11 @@ impl AsyncWriter {
 		Self { tx }
 	}
 
-	fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
-		let temp_path = path.with_extension("tmp");
-		let file = OpenOptions::new().write(true).create(true).truncate(true).open(&temp_path)?;
-		let mut writer = BufWriter::new(file);
-		writer.write_all(data)?;
-		writer.flush()?;
-		drop(writer);
-		fs::rename(temp_path, path)?;
-		Ok(())
-	}
-
-	fn save(&self, path: PathBuf, data: Vec<u8>) { let _ = self.tx.send(WriteOp::Save { path, data }); }
+	fn run(&self, job: Box<dyn FnOnce() + Send>) { let _ = self.tx.send(WriteOp::Run(job)); }
 
-	fn flush(&self) {
+	fn flush(&self) -> bool {
 		let (respond_tx, respond_rx) = mpsc::channel();
-		if self.tx.send(WriteOp::Flush { respond_to: respond_tx }).is_ok() {
-			let _ = respond_rx.recv();
-		}
+		self.tx.send(WriteOp::Flush { respond_to: respond_tx }).is_ok() && respond_rx.recv().is_ok()
 	}
 }
 
@@ -71,6 +290,13 @@ impl Drop for AsyncWriter {
 	}
 }
 
+/// Blocks until every queued async save has been written.
+///
+/// Hosts should call this before quitting, since the writer's own `Drop` runs
+/// during static teardown in no particular order. Returns false if the writer
+/// is gone.
+pub fn flush_all() -> bool { ASYNC_WRITER.get().is_none_or(AsyncWriter::flush) }
+
 pub trait Storage<T>: Send + Sync
 where
 	T: Send + Sync,
@@ -111,6 +337,63 @@ where
 }
 
 pub fn load_from_disk<T>(path: &Path) -> io::Result<Vec<T>>
+where
+	T: Archive,
+	T::Archived:
+		for<'a> CheckBytes<HighValidator<'a, Error>> + Deserialize<T, rkyv::rancor::Strategy<rkyv::de::Pool, Error>>,
+{
+	load_decoded(path, None, DEFAULT_SCHEMA_VERSION, None)
+}
+
+pub fn load_from_disk_versioned<T>(path: &Path, version: u8, migrate: Option<Migrator<T>>) -> io::Result<Vec<T>>
+where
+	T: Archive,
+	T::Archived:
+		for<'a> CheckBytes<HighValidator<'a, Error>> + Deserialize<T, rkyv::rancor::Strategy<rkyv::de::Pool, Error>>,
+{
+	load_decoded(path, None, version, migrate)
+}
+
+pub fn load_from_disk_encrypted<T>(path: &Path, key: &[u8; 32]) -> io::Result<Vec<T>>
+where
+	T: Archive,
+	T::Archived:
+		for<'a> CheckBytes<HighValidator<'a, Error>> + Deserialize<T, rkyv::rancor::Strategy<rkyv::de::Pool, Error>>,
+{
+	load_decoded(path, Some(key), DEFAULT_SCHEMA_VERSION, None)
+}
+
+fn load_decoded<T>(path: &Path, key: Option<&[u8; 32]>, version: u8, migrate: Option<Migrator<T>>) -> io::Result<Vec<T>>
+where
+	T: Archive,
+	T::Archived:
+		for<'a> CheckBytes<HighValidator<'a, Error>> + Deserialize<T, rkyv::rancor::Strategy<rkyv::de::Pool, Error>>,
+{
+	// A file that fails to decrypt is an error rather than an empty store, so
+	// the next save cannot overwrite data sealed under another key.
+	let primary = match decode_file(path, key, version, migrate) {
+		Ok(Some(items)) => return Ok(items),
+		Ok(None) => None,
+		Err(e) if e.kind() == io::ErrorKind::InvalidData => Some(e),
+		Err(e) => return Err(e),
+	};
+
+	let backup = backup_path(path);
+	if backup.exists()
+		&& let Ok(Some(items)) = decode_file(&backup, key, version, migrate)
+	{
+		eprintln!("[storage] Recovered {} items for {} from {}", items.len(), path.display(), backup.display());
+		return Ok(items);
+	}
+	primary.map_or_else(|| Ok(Vec::new()), Err)
+}
+
+fn decode_file<T>(
+	path: &Path,
+	key: Option<&[u8; 32]>,
+	version: u8,
+	migrate: Option<Migrator<T>>,
+) -> io::Result<Option<Vec<T>>>
 where
 	T: Archive,
 	T::Archived:
@@ -118,9 +401,19 @@ where
 {
 	let bytes = fs::read(path)?;
 	if bytes.is_empty() {
-		return Ok(Vec::new());
+		return Ok(Some(Vec::new()));
 	}
-	rkyv::from_bytes::<Vec<T>, Error>(&bytes).or_else(|_| Ok(Vec::new()))
+
+	let decoded = if let Some(compressed) = bytes.strip_prefix(COMPRESSED_MAGIC) {
+		zstd::stream::decode_all(compressed).ok()
+	} else if let Some(sealed) = bytes.strip_prefix(ENCRYPTED_MAGIC) {
+		let key = key.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "file is encrypted"))?;
+		Some(decrypt(key, sealed)?)
+	} else {
+		return Ok(deserialize(path, &bytes, version, migrate));
+	};
+
+	Ok(decoded.and_then(|decoded| deserialize(path, &decoded, version, migrate)))
 }
 
 pub fn save_to_disk<T>(path: &Path, items: &Vec<T>) -> io::Result<()>
@@ -129,18 +422,232 @@ where
 		rkyv::api::high::HighSerializer<rkyv::util::AlignedVec, rkyv::ser::allocator::ArenaHandle<'a>, Error>,
 	>,
 {
-	let bytes = rkyv::to_bytes::<Error>(items).map_err(|e| io::Error::other(format!("rkyv: {e:?}")))?;
-	let temp_path = path.with_extension("tmp");
-	let file = OpenOptions::new().write(true).create(true).truncate(true).open(&temp_path)?;
-	let mut writer = BufWriter::new(file);
-	writer.write_all(&bytes)?;
-	writer.flush()?;
-	drop(writer);
-	fs::rename(temp_path, path)?;
-	Ok(())
+	save_encoded(path, items, Encoding::Plain, DEFAULT_SCHEMA_VERSION)
+}
+
+/// Writes `items` on the background writer thread. A save queued while an
+/// earlier one for the same path is still waiting replaces it, so a burst of
+/// saves costs a single write.
+pub fn save_to_disk_async<T>(path: &Path, items: Vec<T>)
+where
+	T: Send
+		+ 'static
+		+ for<'a> Serialize<
+			rkyv::api::high::HighSerializer<rkyv::util::AlignedVec, rkyv::ser::allocator::ArenaHandle<'a>, Error>,
+		>,
+{
+	let job: SaveJob =
+		Box::new(move |path, durability| write_encoded(path, &items, Encoding::Plain, DEFAULT_SCHEMA_VERSION, durability));
+
+	let mut pending = PENDING_SAVES.lock();
+	if let Some((_, queued)) = pending.iter_mut().find(|(queued_path, _)| queued_path == path) {
+		*queued = job;
+		return;
+	}
+	pending.push((path.to_path_buf(), job));
+	drop(pending);
+
+	let path = path.to_path_buf();
+	async_writer().run(Box::new(move || {
+		let job = {
+			let mut pending = PENDING_SAVES.lock();
+			pending.iter().position(|(queued_path, _)| *queued_path == path).map(|i| pending.swap_remove(i).1)
+		};
+		let durability = Durability::from_u8(ASYNC_DURABILITY.load(Ordering::Relaxed));
+		if let Some(job) = job
+			&& let Err(e) = job(&path, durability)
+		{
+			eprintln!("[AsyncWriter] Failed to write {}: {}", path.display(), e);
+		}
+	}));
+}
+
+pub fn save_to_disk_versioned<T>(path: &Path, items: &Vec<T>, version: u8) -> io::Result<()>
+where
+	T: for<'a> Serialize<
+		rkyv::api::high::HighSerializer<rkyv::util::AlignedVec, rkyv::ser::allocator::ArenaHandle<'a>, Error>,
+	>,
+{
+	save_encoded(path, items, Encoding::Plain, version)
+}
+
+pub fn save_to_disk_compressed<T>(path: &Path, items: &Vec<T>) -> io::Result<()>
+where
+	T: for<'a> Serialize<
+		rkyv::api::high::HighSerializer<rkyv::util::AlignedVec, rkyv::ser::allocator::ArenaHandle<'a>, Error>,
+	>,
+{
+	save_encoded(path, items, Encoding::Compressed, DEFAULT_SCHEMA_VERSION)
+}
+
+pub fn save_to_disk_encrypted<T>(path: &Path, items: &Vec<T>, key: &[u8; 32]) -> io::Result<()>
+where
+	T: for<'a> Serialize<
+		rkyv::api::high::HighSerializer<rkyv::util::AlignedVec, rkyv::ser::allocator::ArenaHandle<'a>, Error>,
+	>,
+{
+	save_encoded(path, items, Encoding::Encrypted(*key), DEFAULT_SCHEMA_VERSION)
+}
+
+fn save_encoded<T>(path: &Path, items: &Vec<T>, encoding: Encoding, version: u8) -> io::Result<()>
+where
+	T: for<'a> Serialize<
+		rkyv::api::high::HighSerializer<rkyv::util::AlignedVec, rkyv::ser::allocator::ArenaHandle<'a>, Error>,
+	>,
+{
+	write_encoded(path, items, encoding, version, Durability::from_u8(SYNC_DURABILITY.load(Ordering::Relaxed)))
+}
+
+fn write_encoded<T>(
+	path: &Path,
+	items: &Vec<T>,
+	encoding: Encoding,
+	version: u8,
+	durability: Durability,
+) -> io::Result<()>
+where
+	T: for<'a> Serialize<
+		rkyv::api::high::HighSerializer<rkyv::util::AlignedVec, rkyv::ser::allocator::ArenaHandle<'a>, Error>,
+	>,
+{
+	let data = encoding.encode(serialize(items, version)?)?;
+	keep_backup(path);
+	write_atomic(path, &data, durability, &OsSyncer)
+}
+
+// An edit made by one of the `*_async` methods. It is kept until a write puts
+// it on disk so it can be replayed onto a file another process changed first.
+type Edit<T> = Box<dyn Fn(&mut Vec<T>) -> bool + Send>;
+
+type SaveJob = Box<dyn FnOnce(&Path, Durability) -> io::Result<()> + Send>;
+
+// The file's modification time and length when this process last read or
+// wrote it. A write only decodes the file again once either has changed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+	modified: SystemTime,
+	len:      u64,
+}
+
+impl FileStamp {
+	fn of(path: &Path) -> Option<Self> {
+		let metadata = fs::metadata(path).ok()?;
+		Some(Self { modified: metadata.modified().ok()?, len: metadata.len() })
+	}
+}
+
+struct DiskState<T> {
+	stamp:   Option<FileStamp>,
+	pending: Vec<Edit<T>>,
 }
 
 #[allow(clippy::rc_buffer)]
+struct Shared<T> {
+	items:    RwLock<Arc<Vec<T>>>,
+	disk:     Mutex<DiskState<T>>,
+	path:     PathBuf,
+	encoding: Encoding,
+	version:  u8,
+	migrate:  Option<Migrator<T>>,
+}
+
+impl<T> Shared<T>
+where
+	T: Archive
+		+ Clone
+		+ for<'a> Serialize<
+			rkyv::api::high::HighSerializer<rkyv::util::AlignedVec, rkyv::ser::allocator::ArenaHandle<'a>, Error>,
+		>,
+	T::Archived:
+		for<'a> CheckBytes<HighValidator<'a, Error>> + Deserialize<T, rkyv::rancor::Strategy<rkyv::de::Pool, Error>>,
+{
+	// Picks up a file another process replaced since this one last touched it,
+	// replaying the edits not yet written on top. An unreadable file keeps the
+	// in-memory items rather than wiping them, but one that fails to decrypt
+	// is an error so it is never overwritten. Callers hold the file lock.
+	fn refresh(&self, disk: &mut DiskState<T>) -> io::Result<()> {
+		let stamp = FileStamp::of(&self.path);
+		if stamp == disk.stamp {
+			return Ok(());
+		}
+		match decode_file(&self.path, self.encoding.key(), self.version, self.migrate) {
+			Ok(Some(mut items)) => {
+				for edit in &disk.pending {
+					edit(&mut items);
+				}
+				*self.items.write() = Arc::new(items);
+			}
+			Err(e) if e.kind() == io::ErrorKind::InvalidData => return Err(e),
+			Ok(None) | Err(_) => {}
+		}
+		disk.stamp = stamp;
+		Ok(())
+	}
+
+	// Applies `mutate` to the latest contents while holding the file lock, so
+	// concurrent writers from other processes are not clobbered, and writes
+	// the result along with any pending edits.
+	#[allow(clippy::significant_drop_tightening)]
+	fn commit<R>(&self, durability: Durability, mutate: impl FnOnce(&mut Vec<T>) -> (R, bool)) -> io::Result<R> {
+		let _lock = lock_file(&self.path)?;
+		let (result, snapshot) = {
+			let mut disk = self.disk.lock();
+			self.refresh(&mut disk)?;
+			let (result, modified) = mutate(Arc::make_mut(&mut self.items.write()));
+			let dirty = modified || !disk.pending.is_empty();
+			disk.pending.clear();
+			(result, dirty.then(|| Arc::clone(&self.items.read())))
+		};
+		if let Some(items) = snapshot {
+			self.write(&items, durability)?;
+		}
+		Ok(result)
+	}
+
+	fn write(&self, items: &Vec<T>, durability: Durability) -> io::Result<()> {
+		write_encoded(&self.path, items, self.encoding, self.version, durability)?;
+		self.disk.lock().stamp = FileStamp::of(&self.path);
+		Ok(())
+	}
+
+	// Applies `edit` in memory now and leaves the write to the async writer,
+	// which merges it with the file under the lock. Bursts of edits share one
+	// queued write.
+	fn edit_async(self: &Arc<Self>, edit: Edit<T>) -> bool
+	where
+		T: Send + Sync + 'static,
+	{
+		let mut disk = self.disk.lock();
+		if !edit(Arc::make_mut(&mut self.items.write())) {
+			return false;
+		}
+		let queued = !disk.pending.is_empty();
+		disk.pending.push(edit);
+		drop(disk);
+
+		if !queued {
+			let shared = Arc::clone(self);
+			async_writer().run(Box::new(move || {
+				let durability = Durability::from_u8(ASYNC_DURABILITY.load(Ordering::Relaxed));
+				if let Err(e) = shared.commit(durability, |_| ((), false)) {
+					eprintln!("[AsyncWriter] Failed to write {}: {}", shared.path.display(), e);
+				}
+			}));
+		}
+		true
+	}
+}
+
+impl<T> Drop for Shared<T> {
+	fn drop(&mut self) {
+		let identity = file_identity(&self.path);
+		let mut open = OPEN_FILES.lock();
+		if let Some(index) = open.iter().position(|path| *path == identity) {
+			open.swap_remove(index);
+		}
+	}
+}
+
 pub struct RkyvStorage<T>
 where
 	T: Archive
@@ -150,8 +657,7 @@ where
 	T::Archived:
 		for<'a> CheckBytes<HighValidator<'a, Error>> + Deserialize<T, rkyv::rancor::Strategy<rkyv::de::Pool, Error>>,
 {
-	items: RwLock<Arc<Vec<T>>>,
-	path:  PathBuf,
+	shared: Arc<Shared<T>>,
 }
 
 impl<T> RkyvStorage<T>
@@ -164,59 +670,118 @@ where
 		for<'a> CheckBytes<HighValidator<'a, Error>> + Deserialize<T, rkyv::rancor::Strategy<rkyv::de::Pool, Error>>,
 {
 	pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
-		let path = path.as_ref().to_path_buf();
+		Self::open(path.as_ref(), Encoding::Plain, DEFAULT_SCHEMA_VERSION, None)
+	}
+
+	pub fn new_compressed(path: impl AsRef<Path>) -> io::Result<Self> {
+		Self::open(path.as_ref(), Encoding::Compressed, DEFAULT_SCHEMA_VERSION, None)
+	}
+
+	pub fn new_encrypted(path: impl AsRef<Path>, key: [u8; 32]) -> io::Result<Self> {
+		Self::open(path.as_ref(), Encoding::Encrypted(key), DEFAULT_SCHEMA_VERSION, None)
+	}
+
+	/// Opens storage whose items are written under schema `version`, running
+	/// `migrate` on files saved by an older one.
+	pub fn new_versioned(path: impl AsRef<Path>, version: u8, migrate: Migrator<T>) -> io::Result<Self> {
+		Self::open(path.as_ref(), Encoding::Plain, version, Some(migrate))
+	}
+
+	pub fn new_encrypted_versioned(
+		path: impl AsRef<Path>,
+		key: [u8; 32],
+		version: u8,
+		migrate: Migrator<T>,
+	) -> io::Result<Self> {
+		Self::open(path.as_ref(), Encoding::Encrypted(key), version, Some(migrate))
+	}
+
+	fn open(path: &Path, encoding: Encoding, version: u8, migrate: Option<Migrator<T>>) -> io::Result<Self> {
+		let path = path.to_path_buf();
 
 		if let Some(parent) = path.parent() {
 			fs::create_dir_all(parent)?;
 		}
 
-		let items = if path.exists() { load_from_disk(&path)? } else { Vec::new() };
-
-		Ok(Self { items: RwLock::new(Arc::new(items)), path })
+		let stamp = FileStamp::of(&path);
+		let items = if path.exists() { load_decoded(&path, encoding.key(), version, migrate)? } else { Vec::new() };
+		OPEN_FILES.lock().push(file_identity(&path));
+
+		Ok(Self {
+			shared: Arc::new(Shared {
+				items: RwLock::new(Arc::new(items)),
+				disk: Mutex::new(DiskState { stamp, pending: Vec::new() }),
+				path,
+				encoding,
+				version,
+				migrate,
+			}),
+		})
 	}
 
 	pub fn add(&self, item: T) -> io::Result<()>
 	where
 		T: Clone,
 	{
-		Arc::make_mut(&mut self.items.write()).push(item);
-		self.save()
+		self.locked_update(|items| {
+			items.push(item);
+			((), true)
+		})
 	}
 
 	pub fn add_async(&self, item: T)
 	where
-		T: Clone,
+		T: Clone + Send + Sync + 'static,
 	{
-		Arc::make_mut(&mut self.items.write()).push(item);
-		self.async_save();
+		self.update_async(move |items| {
+			items.push(item.clone());
+			true
+		});
 	}
 
 	pub fn insert_at_front(&self, item: T) -> io::Result<()>
 	where
 		T: Clone,
 	{
-		Arc::make_mut(&mut self.items.write()).insert(0, item);
-		self.save()
+		self.locked_update(|items| {
+			items.insert(0, item);
+			((), true)
+		})
 	}
 
 	pub fn insert_at_front_async(&self, item: T)
 	where
-		T: Clone,
+		T: Clone + Send + Sync + 'static,
 	{
-		Arc::make_mut(&mut self.items.write()).insert(0, item);
-		self.async_save();
+		self.update_async(move |items| {
+			items.insert(0, item.clone());
+			true
+		});
+	}
+
+	#[must_use]
+	pub fn move_to_front(&self, index: usize) -> bool
+	where
+		T: Clone + Send + Sync + 'static,
+	{
+		self.update_async(move |items| {
+			items.get_mut(..=index).is_some_and(|moved| {
+				moved.rotate_right(1);
+				true
+			})
+		})
 	}
 
 	#[inline]
 	#[must_use]
-	pub fn get_all(&self) -> Arc<Vec<T>> { Arc::clone(&self.items.read()) }
+	pub fn get_all(&self) -> Arc<Vec<T>> { Arc::clone(&self.shared.items.read()) }
 
 	#[must_use]
 	pub fn get_range(&self, start: usize, count: usize) -> Vec<T>
 	where
 		T: Clone,
 	{
-		let items = self.items.read();
+		let items = self.shared.items.read();
 		let end = start.saturating_add(count).min(items.len());
 		items.get(start..end).map_or_else(Vec::new, <[T]>::to_vec)
 	}
@@ -227,7 +792,7 @@ where
 		T: Clone,
 		F: Fn(&T) -> bool,
 	{
-		self.items.read().iter().filter(|item| predicate(item)).cloned().collect()
+		self.shared.items.read().iter().filter(|item| predicate(item)).cloned().collect()
 	}
 
 	#[must_use]
@@ -235,35 +800,73 @@ where
 	where
 		F: Fn(&T) -> bool,
 	{
-		self.items.read().iter().position(predicate)
+		self.shared.items.read().iter().position(predicate)
 	}
 
 	#[must_use]
-	pub fn len(&self) -> usize { self.items.read().len() }
+	pub fn len(&self) -> usize { self.shared.items.read().len() }
 
 	#[must_use]
-	pub fn is_empty(&self) -> bool { self.items.read().is_empty() }
+	pub fn is_empty(&self) -> bool { self.shared.items.read().is_empty() }
 
 	pub fn clear(&self) -> io::Result<()>
 	where
 		T: Clone,
 	{
-		Arc::make_mut(&mut self.items.write()).clear();
-		self.save()
+		self.locked_update(|items| {
+			items.clear();
+			((), true)
+		})
 	}
 
-	pub fn trim_to(&self, max: usize) -> io::Result<Vec<T>>
+	/// Overwrites the file with `items`, discarding async edits not yet
+	/// written.
+	#[allow(clippy::significant_drop_tightening)]
+	pub fn replace_all(&self, items: Vec<T>) -> io::Result<()>
 	where
 		T: Clone,
 	{
-		let mut guard = self.items.write();
-		let items = Arc::make_mut(&mut guard);
-		let removed = if max < items.len() { items.drain(max..).collect() } else { Vec::new() };
-		drop(guard);
-		if !removed.is_empty() {
-			self.save()?;
+		let _lock = lock_file(&self.shared.path)?;
+		let items = Arc::new(items);
+		{
+			let mut disk = self.shared.disk.lock();
+			disk.pending.clear();
+			*self.shared.items.write() = Arc::clone(&items);
 		}
-		Ok(removed)
+		self.shared.write(&items, Durability::from_u8(SYNC_DURABILITY.load(Ordering::Relaxed)))
+	}
+
+	pub fn trim_to(&self, max: usize) -> io::Result<Vec<T>>
+	where
+		T: Clone,
+	{
+		self.trim_to_matching(max, |_| true)
+	}
+
+	/// Keeps the first `max` items for which `counted` holds and removes the
+	/// counted items after them. Items `counted` rejects are always kept.
+	pub fn trim_to_matching(&self, max: usize, counted: impl Fn(&T) -> bool) -> io::Result<Vec<T>>
+	where
+		T: Clone,
+	{
+		self.locked_update(|items| {
+			let mut seen = 0;
+			let mut removed = Vec::new();
+			let mut kept = Vec::with_capacity(items.len());
+			for item in items.drain(..) {
+				if counted(&item) {
+					seen += 1;
+					if seen > max {
+						removed.push(item);
+						continue;
+					}
+				}
+				kept.push(item);
+			}
+			*items = kept;
+			let modified = !removed.is_empty();
+			(removed, modified)
+		})
 	}
 
 	pub fn update<F>(&self, updater: F) -> io::Result<bool>
@@ -271,45 +874,45 @@ where
 		T: Clone,
 		F: FnOnce(&mut Vec<T>) -> bool,
 	{
-		let mut guard = self.items.write();
-		let modified = updater(Arc::make_mut(&mut guard));
-		if modified {
-			drop(guard);
-			self.save()?;
-		}
-		Ok(modified)
+		self.locked_update(|items| {
+			let modified = updater(items);
+			(modified, modified)
+		})
 	}
 
-	pub fn update_async<F>(&self, updater: F) -> bool
+	/// Re-reads the file if it changed, picking up writes made by other
+	/// processes.
+	pub fn reload(&self) -> io::Result<()>
 	where
 		T: Clone,
-		F: FnOnce(&mut Vec<T>) -> bool,
 	{
-		let mut guard = self.items.write();
-		let modified = updater(Arc::make_mut(&mut guard));
-		if modified {
-			drop(guard);
-			self.async_save();
-		}
-		modified
+		let _lock = lock_file(&self.shared.path)?;
+		self.shared.refresh(&mut self.shared.disk.lock())
 	}
 
-	fn save(&self) -> io::Result<()> {
-		let items = self.items.read();
-		save_to_disk(&self.path, &**items)
+	fn locked_update<R>(&self, mutate: impl FnOnce(&mut Vec<T>) -> (R, bool)) -> io::Result<R>
+	where
+		T: Clone,
+	{
+		self.shared.commit(Durability::from_u8(SYNC_DURABILITY.load(Ordering::Relaxed)), mutate)
 	}
 
-	fn async_save(&self) {
-		let items = self.items.read();
-		if let Ok(bytes) = rkyv::to_bytes::<Error>(&**items).map_err(|e| eprintln!("[async_save] rkyv error: {e:?}")) {
-			async_writer().save(self.path.clone(), bytes.to_vec());
-		}
+	/// Applies `updater` in memory and queues the write. The updater runs
+	/// again on the file's latest contents if another process changed it
+	/// before the write.
+	pub fn update_async<F>(&self, updater: F) -> bool
+	where
+		T: Clone + Send + Sync + 'static,
+		F: Fn(&mut Vec<T>) -> bool + Send + 'static,
+	{
+		self.shared.edit_async(Box::new(updater))
 	}
 
-	pub fn flush(&self) { async_writer().flush(); }
+	#[allow(clippy::must_use_candidate)]
+	pub fn flush(&self) -> bool { flush_all() }
 
 	#[must_use]
-	pub fn path(&self) -> &Path { &self.path }
+	pub fn path(&self) -> &Path { &self.shared.path }
 }
 
 impl<T> Storage<T> for RkyvStorage<T>
@@ -403,6 +1006,41 @@ mod tests {
 		Ok(())
 	}
 
+	#[derive(Default)]
+	struct RecordingSyncer {
+		files: std::cell::Cell<usize>,
+		dirs:  std::cell::Cell<usize>,
+	}
+
+	impl Syncer for RecordingSyncer {
+		fn sync_file(&self, _file: &File) -> io::Result<()> {
+			self.files.set(self.files.get() + 1);
+			Ok(())
+		}
+
+		fn sync_dir(&self, _dir: &Path) -> io::Result<()> {
+			self.dirs.set(self.dirs.get() + 1);
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn test_write_atomic_durability() -> io::Result<()> {
+		let temp = NamedTempFile::new()?;
+		let syncer = RecordingSyncer::default();
+
+		write_atomic(temp.path(), b"none", Durability::None, &syncer)?;
+		assert_eq!((syncer.files.get(), syncer.dirs.get()), (0, 0));
+
+		write_atomic(temp.path(), b"file", Durability::File, &syncer)?;
+		assert_eq!((syncer.files.get(), syncer.dirs.get()), (1, 0));
+
+		write_atomic(temp.path(), b"both", Durability::FileAndDirectory, &syncer)?;
+		assert_eq!((syncer.files.get(), syncer.dirs.get()), (2, 1));
+		assert_eq!(fs::read(temp.path())?, b"both");
+		Ok(())
+	}
+
 	#[test]
 	fn test_atomic_write() -> io::Result<()> {
 		let temp = NamedTempFile::new()?;
@@ -423,4 +1061,248 @@ mod tests {
 		assert_eq!(loaded[0].name, "Updated");
 		Ok(())
 	}
+
+	#[test]
+	fn test_replace_all() -> io::Result<()> {
+		let temp = NamedTempFile::new()?;
+		let storage = RkyvStorage::<TestItem>::new(temp.path())?;
+		storage.add(TestItem { id: "1".to_owned(), name: "Old".to_owned() })?;
+
+		storage.replace_all(vec![TestItem { id: "2".to_owned(), name: "New".to_owned() }])?;
+		assert_eq!(storage.len(), 1);
+
+		let loaded: Vec<TestItem> = load_from_disk(temp.path())?;
+		assert_eq!(loaded[0].id, "2");
+		Ok(())
+	}
+
+	#[test]
+	fn test_compressed_round_trip() -> io::Result<()> {
+		let temp = NamedTempFile::new()?;
+		let items: Vec<TestItem> =
+			(0..2000).map(|i| TestItem { id: i.to_string(), name: format!("Clipboard entry number {}", i % 10) }).collect();
+		let raw_len = rkyv::to_bytes::<Error>(&items).map_err(io::Error::other)?.len();
+
+		let storage = RkyvStorage::<TestItem>::new_compressed(temp.path())?;
+		storage.replace_all(items.clone())?;
+		assert!(fs::metadata(temp.path())?.len() < raw_len as u64);
+		assert_eq!(load_from_disk::<TestItem>(temp.path())?, items);
+
+		storage.add_async(TestItem { id: "async".to_owned(), name: "Queued".to_owned() });
+		storage.flush();
+		assert!(fs::read(temp.path())?.starts_with(COMPRESSED_MAGIC));
+		let reloaded = RkyvStorage::<TestItem>::new_compressed(temp.path())?;
+		assert_eq!(reloaded.len(), 2001);
+		assert_eq!(reloaded.get_all()[2000].name, "Queued");
+		Ok(())
+	}
+
+	#[test]
+	fn test_compressed_storage_loads_legacy_file() -> io::Result<()> {
+		let temp = NamedTempFile::new()?;
+		save_to_disk(temp.path(), &vec![TestItem { id: "1".to_owned(), name: "Legacy".to_owned() }])?;
+
+		let storage = RkyvStorage::<TestItem>::new_compressed(temp.path())?;
+		assert_eq!(storage.get_all()[0].name, "Legacy");
+		Ok(())
+	}
+
+	#[test]
+	fn test_encrypted_round_trip() -> io::Result<()> {
+		let temp = NamedTempFile::new()?;
+		let key = [7u8; 32];
+
+		let storage = RkyvStorage::<TestItem>::new_encrypted(temp.path(), key)?;
+		storage.add(TestItem { id: "1".to_owned(), name: "hunter2".to_owned() })?;
+		storage.add_async(TestItem { id: "2".to_owned(), name: "token".to_owned() });
+		storage.flush();
+
+		let on_disk = fs::read(temp.path())?;
+		assert!(on_disk.starts_with(ENCRYPTED_MAGIC));
+		assert!(!on_disk.windows(7).any(|w| w == b"hunter2"));
+
+		let reloaded = RkyvStorage::<TestItem>::new_encrypted(temp.path(), key)?;
+		assert_eq!(reloaded.get_all().iter().map(|i| i.name.as_str()).collect::<Vec<_>>(), ["hunter2", "token"]);
+		Ok(())
+	}
+
+	#[test]
+	fn test_encrypted_wrong_key_is_rejected() -> io::Result<()> {
+		let dir = tempfile::TempDir::new()?;
+		let path = dir.path().join("clipboard.bin");
+		let first = vec![TestItem { id: "1".to_owned(), name: "secret".to_owned() }];
+		save_to_disk_encrypted(&path, &first, &[1; 32])?;
+		save_to_disk_encrypted(&path, &vec![TestItem { id: "2".to_owned(), name: "newer".to_owned() }], &[1; 32])?;
+		let sealed = fs::read(&path)?;
+
+		let rejected = |result: io::Result<Vec<TestItem>>| result.is_err_and(|e| e.kind() == io::ErrorKind::InvalidData);
+		assert!(decrypt(&[2; 32], &sealed[ENCRYPTED_MAGIC.len()..]).is_err());
+		assert!(rejected(load_from_disk_encrypted(&path, &[2; 32])));
+		assert!(rejected(load_from_disk(&path)));
+		assert!(RkyvStorage::<TestItem>::new_encrypted(&path, [2; 32]).is_err());
+		assert_eq!(fs::read(&path)?, sealed);
+
+		let mut tampered = sealed;
+		if let Some(last) = tampered.last_mut() {
+			*last ^= 0xff;
+		}
+		fs::write(&path, tampered)?;
+		assert_eq!(load_from_disk_encrypted::<TestItem>(&path, &[1; 32])?, first);
+
+		fs::write(backup_path(&path), b"garbage")?;
+		assert!(rejected(load_from_disk_encrypted(&path, &[1; 32])));
+		Ok(())
+	}
+
+	#[test]
+	fn test_encrypted_file_changed_to_other_key_is_not_overwritten() -> io::Result<()> {
+		let dir = tempfile::TempDir::new()?;
+		let path = dir.path().join("clipboard.bin");
+		let storage = RkyvStorage::<TestItem>::new_encrypted(&path, [1; 32])?;
+		storage.add(TestItem { id: "1".to_owned(), name: "ours".to_owned() })?;
+
+		save_to_disk_encrypted(&path, &vec![TestItem { id: "2".to_owned(), name: "theirs".to_owned() }], &[2; 32])?;
+		let sealed = fs::read(&path)?;
+		assert!(storage.add(TestItem { id: "3".to_owned(), name: "lost".to_owned() }).is_err());
+		assert_eq!(fs::read(&path)?, sealed);
+		Ok(())
+	}
+
+	#[derive(Archive, Deserialize, Serialize, Debug, Clone, PartialEq, CheckBytes)]
+	#[rkyv(derive(Debug))]
+	struct TestItemV2 {
+		id:     String,
+		name:   String,
+		pinned: bool,
+	}
+
+	fn migrate_test_item(version: u8, bytes: &[u8]) -> Option<Vec<TestItemV2>> {
+		if version != 1 {
+			return None;
+		}
+		let old = rkyv::from_bytes::<Vec<TestItem>, Error>(bytes).ok()?;
+		Some(old.into_iter().map(|i| TestItemV2 { id: i.id, name: i.name, pinned: false }).collect())
+	}
+
+	#[test]
+	fn test_versioned_migration() -> io::Result<()> {
+		let temp = NamedTempFile::new()?;
+		save_to_disk_versioned(temp.path(), &vec![TestItem { id: "1".to_owned(), name: "Old".to_owned() }], 1)?;
+		assert_eq!(&fs::read(temp.path())?[..4], b"RKV\x01");
+
+		let migrated = load_from_disk_versioned::<TestItemV2>(temp.path(), 2, Some(migrate_test_item))?;
+		assert_eq!(migrated, vec![TestItemV2 { id: "1".to_owned(), name: "Old".to_owned(), pinned: false }]);
+		assert!(load_from_disk_versioned::<TestItemV2>(temp.path(), 2, None)?.is_empty());
+
+		save_to_disk_versioned(temp.path(), &migrated, 2)?;
+		assert_eq!(load_from_disk_versioned::<TestItemV2>(temp.path(), 2, None)?, migrated);
+		Ok(())
+	}
+
+	#[test]
+	fn test_unversioned_file_still_loads() -> io::Result<()> {
+		let temp = NamedTempFile::new()?;
+		let items = vec![TestItem { id: "1".to_owned(), name: "Legacy".to_owned() }];
+		fs::write(temp.path(), rkyv::to_bytes::<Error>(&items).map_err(io::Error::other)?)?;
+		assert_eq!(load_from_disk::<TestItem>(temp.path())?, items);
+		Ok(())
+	}
+
+	#[test]
+	fn test_corrupt_primary_falls_back_to_backup() -> io::Result<()> {
+		let dir = tempfile::TempDir::new()?;
+		let path = dir.path().join("snippets.bin");
+		let first = vec![TestItem { id: "1".to_owned(), name: "First".to_owned() }];
+		save_to_disk(&path, &first)?;
+		save_to_disk(&path, &vec![TestItem { id: "2".to_owned(), name: "Second".to_owned() }])?;
+		assert_eq!(load_from_disk::<TestItem>(&backup_path(&path))?, first);
+
+		let mut bytes = fs::read(&path)?;
+		bytes.truncate(bytes.len() / 2);
+		fs::write(&path, bytes)?;
+		assert_eq!(load_from_disk::<TestItem>(&path)?, first);
+
+		fs::write(backup_path(&path), b"garbage")?;
+		assert!(load_from_disk::<TestItem>(&path)?.is_empty());
+		Ok(())
+	}
+
+	#[test]
+	fn test_concurrent_writers_keep_all_entries() -> io::Result<()> {
+		let dir = tempfile::TempDir::new()?;
+		let path = dir.path().join("shared.bin");
+
+		let writers: Vec<_> = ["a", "b"]
+			.into_iter()
+			.map(|prefix| {
+				let path = path.clone();
+				thread::spawn(move || -> io::Result<()> {
+					let storage = RkyvStorage::<TestItem>::new(&path)?;
+					for i in 0..25 {
+						storage.add(TestItem { id: format!("{prefix}{i}"), name: String::new() })?;
+					}
+					Ok(())
+				})
+			})
+			.collect();
+		for writer in writers {
+			writer.join().map_err(|_| io::Error::other("writer panicked"))??;
+		}
+
+		let storage = RkyvStorage::<TestItem>::new(&path)?;
+		assert_eq!(storage.len(), 50);
+
+		let other = RkyvStorage::<TestItem>::new(&path)?;
+		other.add(TestItem { id: "late".to_owned(), name: String::new() })?;
+		storage.reload()?;
+		assert_eq!(storage.len(), 51);
+		Ok(())
+	}
+
+	#[test]
+	fn test_flush_all_writes_pending_saves() -> io::Result<()> {
+		let temp = NamedTempFile::new()?;
+		let storage = RkyvStorage::<TestItem>::new(temp.path())?;
+		storage.insert_at_front_async(TestItem { id: "clip".to_owned(), name: "Last copy".to_owned() });
+
+		assert!(flush_all());
+		let loaded: Vec<TestItem> = load_from_disk(temp.path())?;
+		assert_eq!(loaded[0].name, "Last copy");
+		Ok(())
+	}
+
+	#[test]
+	fn test_async_write_merges_changes_from_other_writers() -> io::Result<()> {
+		let dir = tempfile::TempDir::new()?;
+		let path = dir.path().join("shared.bin");
+		let ours = RkyvStorage::<TestItem>::new(&path)?;
+		let theirs = RkyvStorage::<TestItem>::new(&path)?;
+
+		theirs.add(TestItem { id: "theirs".to_owned(), name: String::new() })?;
+		ours.add_async(TestItem { id: "ours".to_owned(), name: String::new() });
+		assert!(flush_all());
+
+		let ids = |items: &[TestItem]| items.iter().map(|i| i.id.clone()).collect::<Vec<_>>();
+		assert_eq!(ids(&load_from_disk::<TestItem>(&path)?), ["theirs", "ours"]);
+		assert_eq!(ids(&ours.get_all()), ["theirs", "ours"]);
+
+		theirs.add(TestItem { id: "late".to_owned(), name: String::new() })?;
+		assert_eq!(ids(&theirs.get_all()), ["theirs", "ours", "late"]);
+		Ok(())
+	}
+
+	#[test]
+	fn test_save_to_disk_async_keeps_last_save() -> io::Result<()> {
+		let temp = NamedTempFile::new()?;
+		for name in ["first", "second", "third"] {
+			save_to_disk_async(temp.path(), vec![TestItem { id: "usage".to_owned(), name: name.to_owned() }]);
+		}
+
+		assert!(flush_all());
+		let loaded: Vec<TestItem> = load_from_disk(temp.path())?;
+		assert_eq!(loaded.len(), 1);
+		assert_eq!(loaded[0].name, "third");
+		assert!(PENDING_SAVES.lock().is_empty());
+		Ok(())
+	}
 }